@@ -1,6 +1,9 @@
 use cosmwasm_schema::{export_schema, remove_schemas, schema_for};
+use multi_step_peer_to_peer_escrow::asset::Asset;
 use multi_step_peer_to_peer_escrow::msg::{
-    AgreementResponse, AgreementsResponse, ExecuteMsg, InstantiateMsg, QueryMsg, TokenInfo,
+    AgreementDepositsResponse, AgreementResponse, AgreementsResponse, ConfigResponse, ExecuteMsg,
+    InstantiateMsg, MigrateMsg, MultiPartyAgreementResponse, QueryMsg, ReceiveMsg,
+    SignedOfferResponse,
 };
 use multi_step_peer_to_peer_escrow::state::Agreement;
 use std::env;
@@ -17,8 +20,14 @@ fn main() {
     export_schema(&schema_for!(InstantiateMsg), &out_dir);
     export_schema(&schema_for!(ExecuteMsg), &out_dir);
     export_schema(&schema_for!(QueryMsg), &out_dir);
+    export_schema(&schema_for!(MigrateMsg), &out_dir);
+    export_schema(&schema_for!(ReceiveMsg), &out_dir);
     export_schema(&schema_for!(AgreementResponse), &out_dir);
     export_schema(&schema_for!(AgreementsResponse), &out_dir);
-    export_schema(&schema_for!(TokenInfo), &out_dir);
+    export_schema(&schema_for!(AgreementDepositsResponse), &out_dir);
+    export_schema(&schema_for!(ConfigResponse), &out_dir);
+    export_schema(&schema_for!(SignedOfferResponse), &out_dir);
+    export_schema(&schema_for!(MultiPartyAgreementResponse), &out_dir);
+    export_schema(&schema_for!(Asset), &out_dir);
     export_schema(&schema_for!(Agreement), &out_dir);
 }
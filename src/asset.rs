@@ -0,0 +1,327 @@
+use crate::ContractError;
+use cosmwasm_std::{to_json_binary, Addr, Api, BankMsg, Coin, CosmosMsg, Empty, Uint128, WasmMsg};
+use cw20::Cw20ExecuteMsg;
+use cw721::msg::Cw721ExecuteMsg;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// An amount of tokens or a set of token ids of one of the supported token standards.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Asset {
+    Native {
+        denom: String,
+        amount: Uint128,
+    },
+    Cw20 {
+        address: Addr,
+        amount: Uint128,
+    },
+    Cw721 {
+        address: Addr,
+        token_ids: Vec<String>,
+    },
+    Cw1155 {
+        address: Addr,
+        token_id: String,
+        amount: Uint128,
+    },
+}
+
+impl Asset {
+    /// Creates an asset of a bank denom.
+    pub fn native(denom: impl Into<String>, amount: impl Into<Uint128>) -> Self {
+        Asset::Native {
+            denom: denom.into(),
+            amount: amount.into(),
+        }
+    }
+
+    /// Creates an asset of a CW20 token contract.
+    pub fn cw20(address: Addr, amount: impl Into<Uint128>) -> Self {
+        Asset::Cw20 {
+            address,
+            amount: amount.into(),
+        }
+    }
+
+    /// Creates an asset of one or more NFTs of a CW721 collection.
+    pub fn cw721(address: Addr, token_ids: &[&str]) -> Self {
+        Asset::Cw721 {
+            address,
+            token_ids: token_ids.iter().map(|id| id.to_string()).collect(),
+        }
+    }
+
+    /// Validates addresses, denoms, token ids and amounts of an asset received as input.
+    pub fn validate(&self, api: &dyn Api) -> Result<(), ContractError> {
+        let invalid = |reason: &str| ContractError::InvalidAsset {
+            asset: self.to_string(),
+            reason: reason.to_string(),
+        };
+
+        match self {
+            Asset::Native { denom, amount } => {
+                if denom.is_empty() {
+                    return Err(invalid("denom must not be empty"));
+                }
+                if amount.is_zero() {
+                    return Err(invalid("amount must be greater than zero"));
+                }
+            }
+            Asset::Cw20 { address, amount } => {
+                api.addr_validate(address.as_str())?;
+                if amount.is_zero() {
+                    return Err(invalid("amount must be greater than zero"));
+                }
+            }
+            Asset::Cw721 { address, token_ids } => {
+                api.addr_validate(address.as_str())?;
+                if token_ids.is_empty() {
+                    return Err(invalid("token ids must not be empty"));
+                }
+                if token_ids.iter().any(|token_id| token_id.is_empty()) {
+                    return Err(invalid("token id must not be empty"));
+                }
+                if (1..token_ids.len()).any(|i| token_ids[..i].contains(&token_ids[i])) {
+                    return Err(invalid("token ids must be unique"));
+                }
+            }
+            Asset::Cw1155 {
+                address,
+                token_id,
+                amount,
+            } => {
+                api.addr_validate(address.as_str())?;
+                if token_id.is_empty() {
+                    return Err(invalid("token id must not be empty"));
+                }
+                if amount.is_zero() {
+                    return Err(invalid("amount must be greater than zero"));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the denom or contract address identifying the token of this asset.
+    pub fn token(&self) -> String {
+        match self {
+            Asset::Native { denom, .. } => denom.clone(),
+            Asset::Cw20 { address, .. }
+            | Asset::Cw721 { address, .. }
+            | Asset::Cw1155 { address, .. } => address.to_string(),
+        }
+    }
+
+    /// Returns the fungible amount of this asset, each NFT counts as one.
+    pub fn amount(&self) -> Uint128 {
+        match self {
+            Asset::Native { amount, .. }
+            | Asset::Cw20 { amount, .. }
+            | Asset::Cw1155 { amount, .. } => *amount,
+            Asset::Cw721 { token_ids, .. } => Uint128::from(token_ids.len() as u128),
+        }
+    }
+
+    /// Returns true if both assets are of the same token, regardless of amounts and token ids.
+    pub fn is_same_token(&self, other: &Asset) -> bool {
+        match (self, other) {
+            (Asset::Native { denom, .. }, Asset::Native { denom: other, .. }) => denom == other,
+            (Asset::Cw20 { address, .. }, Asset::Cw20 { address: other, .. })
+            | (Asset::Cw721 { address, .. }, Asset::Cw721 { address: other, .. }) => {
+                address == other
+            }
+            (
+                Asset::Cw1155 {
+                    address, token_id, ..
+                },
+                Asset::Cw1155 {
+                    address: other_address,
+                    token_id: other_token_id,
+                    ..
+                },
+            ) => address == other_address && token_id == other_token_id,
+            _ => false,
+        }
+    }
+
+    /// Adds a further deposit of the same token to this asset, returns false if the tokens differ.
+    pub fn merge(&mut self, other: &Asset) -> bool {
+        if !self.is_same_token(other) {
+            return false;
+        }
+        match (self, other) {
+            (
+                Asset::Cw721 { token_ids, .. },
+                Asset::Cw721 {
+                    token_ids: other, ..
+                },
+            ) => token_ids.extend(other.iter().cloned()),
+            (
+                Asset::Native { amount, .. }
+                | Asset::Cw20 { amount, .. }
+                | Asset::Cw1155 { amount, .. },
+                other,
+            ) => *amount += other.amount(),
+            _ => unreachable!("assets of the same token are of the same kind"),
+        }
+        true
+    }
+
+    /// Returns how much of `required` this asset covers, each matching NFT counts as one.
+    pub fn covered_amount(&self, required: &Asset) -> Uint128 {
+        if !self.is_same_token(required) {
+            return Uint128::zero();
+        }
+        match (self, required) {
+            (
+                Asset::Cw721 { token_ids, .. },
+                Asset::Cw721 {
+                    token_ids: required,
+                    ..
+                },
+            ) => Uint128::from(required.iter().filter(|id| token_ids.contains(id)).count() as u128),
+            _ => self.amount(),
+        }
+    }
+
+    /// Returns a copy of this fungible asset holding `amount`, NFTs are returned unchanged.
+    pub fn with_amount(&self, amount: Uint128) -> Asset {
+        let mut asset = self.clone();
+        match &mut asset {
+            Asset::Native { amount: a, .. }
+            | Asset::Cw20 { amount: a, .. }
+            | Asset::Cw1155 { amount: a, .. } => *a = amount,
+            Asset::Cw721 { .. } => {}
+        }
+        asset
+    }
+
+    /// Returns true if this asset is a set of NFTs rather than a fungible amount.
+    pub fn is_nft(&self) -> bool {
+        matches!(self, Asset::Cw721 { .. })
+    }
+
+    /// Builds the messages that transfer this asset from the contract to `recipient`.
+    pub fn transfer_msgs(&self, recipient: &Addr) -> Result<Vec<CosmosMsg>, ContractError> {
+        match self {
+            Asset::Native { denom, amount } => Ok(vec![BankMsg::Send {
+                to_address: recipient.to_string(),
+                amount: vec![Coin::new(*amount, denom)],
+            }
+            .into()]),
+            Asset::Cw20 { address, amount } => Ok(vec![WasmMsg::Execute {
+                contract_addr: address.to_string(),
+                msg: to_json_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: recipient.to_string(),
+                    amount: *amount,
+                })?,
+                funds: vec![],
+            }
+            .into()]),
+            Asset::Cw721 { address, token_ids } => token_ids
+                .iter()
+                .map(|token_id| nft_transfer_msg(address, token_id, recipient))
+                .collect(),
+            Asset::Cw1155 { .. } => Err(ContractError::UnsupportedAsset {
+                asset: self.to_string(),
+            }),
+        }
+    }
+}
+
+/// Merges assets of the same token into one entry each, in a canonical order for comparison.
+pub fn merge_assets(assets: impl IntoIterator<Item = Asset>) -> Vec<Asset> {
+    let mut merged: Vec<Asset> = Vec::new();
+    for asset in assets {
+        if !merged.iter_mut().any(|existing| existing.merge(&asset)) {
+            merged.push(asset);
+        }
+    }
+    for asset in merged.iter_mut() {
+        if let Asset::Cw721 { token_ids, .. } = asset {
+            token_ids.sort();
+        }
+    }
+    merged.sort_by_key(|asset| asset.to_string());
+    merged
+}
+
+/// Builds the messages that transfer assets from the contract to `recipient`, sending all bank
+/// assets together in a single `BankMsg::Send` with its coins sorted by denom.
+pub fn transfer_assets_msgs(
+    assets: &[Asset],
+    recipient: &Addr,
+) -> Result<Vec<CosmosMsg>, ContractError> {
+    let mut coins: Vec<Coin> = Vec::new();
+    let mut messages = Vec::new();
+    for asset in assets {
+        match asset {
+            Asset::Native { denom, amount } => {
+                match coins.iter_mut().find(|coin| coin.denom == *denom) {
+                    Some(coin) => coin.amount += *amount,
+                    None => coins.push(Coin::new(*amount, denom)),
+                }
+            }
+            _ => messages.extend(asset.transfer_msgs(recipient)?),
+        }
+    }
+    if !coins.is_empty() {
+        coins.sort_by(|a, b| a.denom.cmp(&b.denom));
+        messages.insert(
+            0,
+            BankMsg::Send {
+                to_address: recipient.to_string(),
+                amount: coins,
+            }
+            .into(),
+        );
+    }
+    Ok(messages)
+}
+
+/// Formats a list of assets for event attributes and error messages.
+pub fn assets_to_string(assets: &[Asset]) -> String {
+    assets
+        .iter()
+        .map(|asset| asset.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Builds the `TransferNft` message that moves a single NFT from the contract to `recipient`.
+pub fn nft_transfer_msg(
+    collection: &Addr,
+    token_id: &str,
+    recipient: &Addr,
+) -> Result<CosmosMsg, ContractError> {
+    Ok(WasmMsg::Execute {
+        contract_addr: collection.to_string(),
+        msg: to_json_binary(&Cw721ExecuteMsg::<Empty, Empty, Empty>::TransferNft {
+            recipient: recipient.to_string(),
+            token_id: token_id.to_string(),
+        })?,
+        funds: vec![],
+    }
+    .into())
+}
+
+impl fmt::Display for Asset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Asset::Native { denom, amount } => write!(f, "native:{}:{}", denom, amount),
+            Asset::Cw20 { address, amount } => write!(f, "cw20:{}:{}", address, amount),
+            Asset::Cw721 { address, token_ids } => {
+                write!(f, "cw721:{}:{}", address, token_ids.join(","))
+            }
+            Asset::Cw1155 {
+                address,
+                token_id,
+                amount,
+            } => write!(f, "cw1155:{}:{}:{}", address, token_id, amount),
+        }
+    }
+}
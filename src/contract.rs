@@ -1,42 +1,100 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    coin, to_json_binary, Addr, BankMsg, Binary, Deps, DepsMut, Env, MessageInfo, Response,
-    StdResult,
+    from_json, to_json_binary, Addr, Attribute, Binary, CosmosMsg, Deps, DepsMut, Env, Event,
+    HexBinary, MessageInfo, Order, Response, StdResult, Storage, Uint128,
 };
-use cw2::set_contract_version;
+use cw2::{get_contract_version, set_contract_version};
+use cw20::Cw20ReceiveMsg;
+use cw721::receiver::Cw721ReceiveMsg;
+use cw_utils::Expiration;
+use semver::Version;
 
+use crate::asset::{assets_to_string, merge_assets, transfer_assets_msgs, Asset};
 use crate::error::ContractError;
-use crate::msg::{ExecuteMsg, InstantiateMsg, QueryMsg, TokenInfo};
+use crate::msg::{
+    DisputePayout, ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg, ReceiveMsg, SignedOffer,
+};
 use crate::state::{
-    query_accepted_agreement_count, query_agreement, query_agreements_by_counterparty,
-    query_agreements_by_initiator, query_agreements_by_status, query_canceled_agreement_count,
-    query_executed_agreement_count, query_initiated_agreement_count, query_total_agreement_count,
-    Agreement, ACCEPTED_AGREEMENT_COUNT, AGREEMENTS, CANCELED_AGREEMENT_COUNT,
-    EXECUTED_AGREEMENT_COUNT, INITIATED_AGREEMENT_COUNT, TOTAL_AGREEMENT_COUNT,
+    query_accepted_agreement_count, query_accrued_fees, query_agreement, query_agreement_deposits,
+    query_agreements_by_counterparty, query_agreements_by_initiator, query_agreements_by_status,
+    query_asset_rule, query_asset_rules, query_balances, query_cancel_request,
+    query_canceled_agreement_count, query_config, query_counter_offers,
+    query_disputed_agreement_count, query_executed_agreement_count, query_expired_agreement_count,
+    query_fills, query_initiated_agreement_count, query_milestones, query_multi_party_agreement,
+    query_open_offers, query_ownership, query_paused, query_rejected_agreement_count,
+    query_resolved_agreement_count, query_signed_offer, query_total_agreement_count, Agreement,
+    AgreementStatus, AssetListMode, AssetRule, CancelPenalty, CancelPolicy, Config, CounterOffer,
+    CounterOfferStatus, Dispute, Fill, Htlc, Milestone, MultiPartyAgreement, Ownership,
+    Participant, Rejection, ACCEPTED_AGREEMENT_COUNT, ACCRUED_FEES, AGREEMENTS, ASSET_LIST_MODE,
+    ASSET_RULES, BALANCES, CANCELED_AGREEMENT_COUNT, CANCEL_REQUESTS, CONFIG, COUNTER_OFFERS,
+    DEPOSITS, DISPUTED_AGREEMENT_COUNT, EXECUTED_AGREEMENT_COUNT, EXPIRED_AGREEMENT_COUNT, FILLS,
+    INITIATED_AGREEMENT_COUNT, LEGACY_AGREEMENTS, LEGACY_CONFIG, MULTI_PARTY_AGREEMENTS,
+    MULTI_PARTY_AGREEMENT_COUNT, MULTI_PARTY_DEPOSITS, OWNERSHIP, PAUSED, REJECTED_AGREEMENT_COUNT,
+    RESOLVED_AGREEMENT_COUNT, REVOKED_OFFER_ID, TOTAL_AGREEMENT_COUNT, USED_OFFERS,
 };
 use crate::utils::{
-    assert_agreement_has_status, assert_contract_has_sufficient_funds,
-    assert_funds_match_token_amount, assert_sender_authorized,
+    assert_agreement_has_status, assert_agreement_has_sufficient_funds,
+    assert_agreement_not_expired, assert_arbiter_valid, assert_asset_allowed,
+    assert_asset_supported, assert_basket_valid, assert_cancel_penalty_valid,
+    assert_deposit_complete, assert_deposit_matches_assets, assert_deposit_matches_token_amount,
+    assert_deposit_outstanding, assert_expiration_in_future, assert_fees_valid, assert_htlc_valid,
+    assert_milestones_valid, assert_nft_matches_asset, assert_no_deposit, assert_not_paused,
+    assert_offer_unused, assert_owner, assert_owner_or_guardian, assert_participants_valid,
+    assert_preimage_matches, assert_sender_authorized,
     assert_sender_is_different_from_counterparty, assert_sender_matches_counterparty,
+    assert_signed_by, assert_top_up_matches, fill_payout, outstanding_assets, signed_offer_digest,
+    Deposit,
 };
 
 const CONTRACT_NAME: &str = "crates.io:multi-step-peer-to-peer-escrow";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+// First version storing agreement statuses as `AgreementStatus` rather than strings
+const STATUS_ENUM_VERSION: Version = Version::new(0, 1, 0);
 
-pub const STATUS_INITIATED: &str = "initiated";
-pub const STATUS_ACCEPTED: &str = "accepted";
-pub const STATUS_EXECUTED: &str = "executed";
-pub const STATUS_CANCELED: &str = "canceled";
+// Basis points making up a whole share
+pub const BPS_DENOMINATOR: u128 = 10_000;
 
 /// Handles contract instantiation, initializing necessary storage.
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
     deps: DepsMut,
     _env: Env,
-    _info: MessageInfo,
-    _msg: InstantiateMsg,
+    info: MessageInfo,
+    msg: InstantiateMsg,
 ) -> Result<Response, ContractError> {
+    // Save the owner, defaulting to the sender
+    let owner = match msg.owner {
+        Some(owner) => deps.api.addr_validate(owner.as_str())?,
+        None => info.sender,
+    };
+    OWNERSHIP.save(
+        deps.storage,
+        &Ownership {
+            owner: Some(owner.clone()),
+            pending_owner: None,
+        },
+    )?;
+
+    // Validate and save the config, the fee recipient defaults to the owner
+    assert_fees_valid(msg.initiator_fee_bps, msg.counterparty_fee_bps)?;
+    let fee_recipient = match msg.fee_recipient {
+        Some(fee_recipient) => deps.api.addr_validate(fee_recipient.as_str())?,
+        None => owner.clone(),
+    };
+    CONFIG.save(
+        deps.storage,
+        &Config {
+            fee_recipient,
+            initiator_fee_bps: msg.initiator_fee_bps,
+            counterparty_fee_bps: msg.counterparty_fee_bps,
+            guardian: msg
+                .guardian
+                .map(|guardian| deps.api.addr_validate(guardian.as_str()))
+                .transpose()?,
+        },
+    )?;
+
     // Set contract version and initialize agreement count
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
     TOTAL_AGREEMENT_COUNT.save(deps.storage, &0)?;
@@ -44,14 +102,118 @@ pub fn instantiate(
     ACCEPTED_AGREEMENT_COUNT.save(deps.storage, &0)?;
     EXECUTED_AGREEMENT_COUNT.save(deps.storage, &0)?;
     CANCELED_AGREEMENT_COUNT.save(deps.storage, &0)?;
+    EXPIRED_AGREEMENT_COUNT.save(deps.storage, &0)?;
+    DISPUTED_AGREEMENT_COUNT.save(deps.storage, &0)?;
+    RESOLVED_AGREEMENT_COUNT.save(deps.storage, &0)?;
+    REJECTED_AGREEMENT_COUNT.save(deps.storage, &0)?;
+    MULTI_PARTY_AGREEMENT_COUNT.save(deps.storage, &0)?;
 
     // Return success response with attributes
     Ok(Response::new()
         .add_attribute("method", "instantiate")
+        .add_attribute("owner", owner)
         .add_attribute("contract_name", CONTRACT_NAME)
         .add_attribute("contract_version", CONTRACT_VERSION))
 }
 
+/// Migrates the contract state written by an earlier version to the current format.
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(deps: DepsMut, _env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
+    // Ensure the stored state belongs to this contract
+    let version = get_contract_version(deps.storage)?;
+    if version.contract != CONTRACT_NAME {
+        return Err(ContractError::InvalidMigration {
+            reason: format!("cannot migrate from contract `{}`", version.contract),
+        });
+    }
+
+    // Rewrite every agreement stored by the first version with string statuses and tokens to the
+    // current format, and re-save agreements of later versions unchanged. Either builds the
+    // initiator, counterparty and status indexes for agreements stored without them
+    let stored_version =
+        Version::parse(&version.version).map_err(|err| ContractError::InvalidMigration {
+            reason: format!("invalid stored version `{}`: {}", version.version, err),
+        })?;
+    let legacy = stored_version < STATUS_ENUM_VERSION;
+    let agreements = if legacy {
+        LEGACY_AGREEMENTS
+            .range(deps.storage, None, None, Order::Ascending)
+            .map(|item| {
+                let (id, legacy_agreement) = item?;
+                Ok((id, legacy_agreement.into_agreement()?))
+            })
+            .collect::<Result<Vec<_>, ContractError>>()?
+    } else {
+        AGREEMENTS
+            .range(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?
+    };
+    let migrated_agreements = agreements.len();
+    for (id, agreement) in agreements {
+        // Remove the legacy entry first, saving reads the stored agreement to update its indexes
+        if legacy {
+            LEGACY_AGREEMENTS.remove(deps.storage, id);
+            backfill_deposits(deps.storage, &agreement)?;
+        }
+        AGREEMENTS.save(deps.storage, id, &agreement)?;
+    }
+
+    // Initialize the counts added after the stored version
+    for count in [
+        EXPIRED_AGREEMENT_COUNT,
+        DISPUTED_AGREEMENT_COUNT,
+        RESOLVED_AGREEMENT_COUNT,
+        REJECTED_AGREEMENT_COUNT,
+        MULTI_PARTY_AGREEMENT_COUNT,
+    ] {
+        if count.may_load(deps.storage)?.is_none() {
+            count.save(deps.storage, &0)?;
+        }
+    }
+
+    // Set the owner of versions without ownership, taking the owner kept in the config of versions
+    // that had one. Re-save the config without that owner, or create it taking no fees until the
+    // owner sets them if it is missing
+    if OWNERSHIP.may_load(deps.storage)?.is_none() {
+        let owner = match LEGACY_CONFIG
+            .may_load(deps.storage)?
+            .and_then(|config| config.owner)
+        {
+            Some(owner) => owner,
+            None => {
+                let owner = msg.owner.ok_or(ContractError::InvalidMigration {
+                    reason: "an owner is required to migrate a contract without one".to_string(),
+                })?;
+                deps.api.addr_validate(owner.as_str())?
+            }
+        };
+        OWNERSHIP.save(
+            deps.storage,
+            &Ownership {
+                owner: Some(owner.clone()),
+                pending_owner: None,
+            },
+        )?;
+        let config = CONFIG.may_load(deps.storage)?.unwrap_or(Config {
+            fee_recipient: owner,
+            initiator_fee_bps: 0,
+            counterparty_fee_bps: 0,
+            guardian: None,
+        });
+        CONFIG.save(deps.storage, &config)?;
+    }
+
+    // Set the current contract version
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    // Return success response with attributes
+    Ok(Response::new()
+        .add_attribute("method", "migrate")
+        .add_attribute("from_version", version.version)
+        .add_attribute("to_version", CONTRACT_VERSION)
+        .add_attribute("migrated_agreements", migrated_agreements.to_string()))
+}
+
 /// Executes contract functions based on incoming messages.
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn execute(
@@ -65,32 +227,350 @@ pub fn execute(
             initiator_token,
             counterparty_token,
             counterparty,
+            expires,
+            arbiter,
+            arbiter_fee_bps,
+            milestones,
+            htlc,
+            cancel_policy,
+            cancel_penalty,
+            initiator_assets,
+            counterparty_assets,
         } => initiate_agreement(
             deps,
-            info,
+            env,
+            info.sender,
+            Deposit::Native(info.funds),
             initiator_token,
             counterparty_token,
             counterparty,
+            expires,
+            arbiter,
+            arbiter_fee_bps,
+            milestones,
+            htlc,
+            cancel_policy,
+            cancel_penalty,
+            initiator_assets,
+            counterparty_assets,
         ),
-        ExecuteMsg::AcceptAgreement { id } => accept_agreement(deps, info, id),
-        ExecuteMsg::ExecuteAgreement { id } => execute_agreement(deps, env, info, id),
+        ExecuteMsg::AcceptAgreement { id } => {
+            accept_agreement(deps, env, info.sender, Deposit::Native(info.funds), id)
+        }
+        ExecuteMsg::ExecuteAgreement { id, preimage } => {
+            execute_agreement(deps, env, info, id, preimage)
+        }
         ExecuteMsg::CancelAgreement { id } => cancel_agreement(deps, env, info, id),
+        ExecuteMsg::ClaimExpired { id } => claim_expired(deps, env, id),
+        ExecuteMsg::RefundHtlc { id } => refund_htlc(deps, env, id),
+        ExecuteMsg::RequestCancel { id } => request_cancel(deps, info, id),
+        ExecuteMsg::ConfirmCancel { id } => confirm_cancel(deps, info, id),
+        ExecuteMsg::RejectAgreement { id, reason } => reject_agreement(deps, info, id, reason),
+        ExecuteMsg::RaiseDispute { id, reason } => raise_dispute(deps, info, id, reason),
+        ExecuteMsg::ResolveDispute { id, payout } => resolve_dispute(deps, info, id, payout),
+        ExecuteMsg::ApproveMilestone { id, index } => approve_milestone(deps, info, id, index),
+        ExecuteMsg::FillAgreement { id } => {
+            fill_agreement(deps, env, info.sender, Deposit::Native(info.funds), id)
+        }
+        ExecuteMsg::AmendAgreement {
+            id,
+            counterparty,
+            counterparty_token,
+            initiator_token,
+        } => amend_agreement(
+            deps,
+            env,
+            info.sender,
+            Deposit::Native(info.funds),
+            id,
+            counterparty,
+            counterparty_token,
+            initiator_token,
+        ),
+        ExecuteMsg::ProposeCounterOffer {
+            id,
+            initiator_token,
+            counterparty_token,
+        } => propose_counter_offer(
+            deps,
+            env,
+            info.sender,
+            Deposit::Native(info.funds),
+            id,
+            initiator_token,
+            counterparty_token,
+        ),
+        ExecuteMsg::AcceptCounterOffer { id } => {
+            accept_counter_offer(deps, env, info.sender, Deposit::Native(info.funds), id)
+        }
+        ExecuteMsg::TransferOwnership { new_owner } => transfer_ownership(deps, info, new_owner),
+        ExecuteMsg::AcceptOwnership {} => accept_ownership(deps, info),
+        ExecuteMsg::RenounceOwnership {} => renounce_ownership(deps, info),
+        ExecuteMsg::UpdateConfig {
+            fee_recipient,
+            initiator_fee_bps,
+            counterparty_fee_bps,
+        } => update_config(
+            deps,
+            info,
+            fee_recipient,
+            initiator_fee_bps,
+            counterparty_fee_bps,
+        ),
+        ExecuteMsg::SetGuardian { guardian } => set_guardian(deps, info, guardian),
+        ExecuteMsg::Pause {
+            initiate,
+            accept,
+            execute,
+        } => set_paused(deps, info, true, initiate, accept, execute),
+        ExecuteMsg::Unpause {
+            initiate,
+            accept,
+            execute,
+        } => set_paused(deps, info, false, initiate, accept, execute),
+        ExecuteMsg::AddAssetRule {
+            token,
+            allowed,
+            min_amount,
+            max_amount,
+        } => add_asset_rule(deps, info, token, allowed, min_amount, max_amount),
+        ExecuteMsg::RemoveAssetRule { token } => remove_asset_rule(deps, info, token),
+        ExecuteMsg::SetAssetListMode { mode } => set_asset_list_mode(deps, info, mode),
+        ExecuteMsg::Deposit {} => deposit_balance(deps, info.sender, Deposit::Native(info.funds)),
+        ExecuteMsg::Withdraw { asset } => withdraw_balance(deps, info, asset),
+        ExecuteMsg::RevokeSignedOffer { offer } => revoke_signed_offer(deps, env, info, offer),
+        ExecuteMsg::AcceptSignedOffer {
+            offer,
+            signature,
+            pubkey,
+        } => accept_signed_offer(
+            deps,
+            env,
+            info.sender,
+            Deposit::Native(info.funds),
+            offer,
+            signature,
+            pubkey,
+        ),
+        ExecuteMsg::InitiateMultiPartyAgreement { participants } => {
+            initiate_multi_party_agreement(deps, info, participants)
+        }
+        ExecuteMsg::DepositMultiParty { id } => {
+            deposit_multi_party(deps, info.sender, Deposit::Native(info.funds), id)
+        }
+        ExecuteMsg::ExecuteMultiPartyAgreement { id } => {
+            execute_multi_party_agreement(deps, info, id)
+        }
+        ExecuteMsg::CancelMultiPartyAgreement { id } => {
+            cancel_multi_party_agreement(deps, info, id)
+        }
+        ExecuteMsg::Receive(msg) => receive_cw20(deps, env, info, msg),
+        ExecuteMsg::ReceiveNft(msg) => receive_nft(deps, env, info, msg),
+    }
+}
+
+/// Handles a CW20 `Send`, using the received tokens as the deposit of the original sender.
+fn receive_cw20(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    wrapper: Cw20ReceiveMsg,
+) -> Result<Response, ContractError> {
+    // The CW20 contract is the message sender, the depositor is the sender of the tokens
+    let sender = deps.api.addr_validate(&wrapper.sender)?;
+    let deposit = Deposit::Cw20 {
+        contract: info.sender,
+        amount: wrapper.amount,
+    };
+
+    receive_deposit(deps, env, sender, deposit, from_json(&wrapper.msg)?)
+}
+
+/// Handles a CW721 `SendNft`, using the received NFT as the deposit of the original owner.
+fn receive_nft(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    wrapper: Cw721ReceiveMsg,
+) -> Result<Response, ContractError> {
+    // The CW721 contract is the message sender, the depositor is the previous owner of the NFT
+    let sender = deps.api.addr_validate(&wrapper.sender)?;
+    let deposit = Deposit::Cw721 {
+        contract: info.sender,
+        token_id: wrapper.token_id,
+    };
+
+    receive_deposit(deps, env, sender, deposit, from_json(&wrapper.msg)?)
+}
+
+/// Dispatches a hook message received together with a CW20 or CW721 deposit.
+fn receive_deposit(
+    deps: DepsMut,
+    env: Env,
+    sender: Addr,
+    deposit: Deposit,
+    msg: ReceiveMsg,
+) -> Result<Response, ContractError> {
+    match msg {
+        ReceiveMsg::InitiateAgreement {
+            initiator_token,
+            counterparty_token,
+            counterparty,
+            expires,
+            arbiter,
+            arbiter_fee_bps,
+            milestones,
+            htlc,
+            cancel_policy,
+            cancel_penalty,
+            initiator_assets,
+            counterparty_assets,
+        } => initiate_agreement(
+            deps,
+            env,
+            sender,
+            deposit,
+            initiator_token,
+            counterparty_token,
+            counterparty,
+            expires,
+            arbiter,
+            arbiter_fee_bps,
+            milestones,
+            htlc,
+            cancel_policy,
+            cancel_penalty,
+            initiator_assets,
+            counterparty_assets,
+        ),
+        ReceiveMsg::AcceptAgreement { id } => accept_agreement(deps, env, sender, deposit, id),
+        ReceiveMsg::DepositNft { id } => deposit_nft(deps, sender, deposit, id),
+        ReceiveMsg::FillAgreement { id } => fill_agreement(deps, env, sender, deposit, id),
+        ReceiveMsg::AmendAgreement {
+            id,
+            counterparty,
+            counterparty_token,
+            initiator_token,
+        } => amend_agreement(
+            deps,
+            env,
+            sender,
+            deposit,
+            id,
+            counterparty,
+            counterparty_token,
+            initiator_token,
+        ),
+        ReceiveMsg::ProposeCounterOffer {
+            id,
+            initiator_token,
+            counterparty_token,
+        } => propose_counter_offer(
+            deps,
+            env,
+            sender,
+            deposit,
+            id,
+            initiator_token,
+            counterparty_token,
+        ),
+        ReceiveMsg::AcceptCounterOffer { id } => {
+            accept_counter_offer(deps, env, sender, deposit, id)
+        }
+        ReceiveMsg::Deposit {} => deposit_balance(deps, sender, deposit),
+        ReceiveMsg::AcceptSignedOffer {
+            offer,
+            signature,
+            pubkey,
+        } => accept_signed_offer(deps, env, sender, deposit, offer, signature, pubkey),
+        ReceiveMsg::DepositMultiParty { id } => deposit_multi_party(deps, sender, deposit, id),
     }
 }
 
 /// Initiates a new agreement between initiator and counterparty.
+#[allow(clippy::too_many_arguments)]
 fn initiate_agreement(
     deps: DepsMut,
-    info: MessageInfo,
-    initiator_token: TokenInfo,
-    counterparty_token: TokenInfo,
-    counterparty: Addr,
+    env: Env,
+    sender: Addr,
+    deposit: Deposit,
+    initiator_token: Asset,
+    counterparty_token: Asset,
+    counterparty: Option<Addr>,
+    expires: Option<Expiration>,
+    arbiter: Option<Addr>,
+    arbiter_fee_bps: Option<u16>,
+    milestones: Option<Vec<Uint128>>,
+    htlc: Option<Htlc>,
+    cancel_policy: Option<CancelPolicy>,
+    cancel_penalty: Option<CancelPenalty>,
+    initiator_assets: Option<Vec<Asset>>,
+    counterparty_assets: Option<Vec<Asset>>,
 ) -> Result<Response, ContractError> {
-    // Verify that initiator's funds match the token amount provided
-    assert_funds_match_token_amount(&info.funds, &initiator_token)?;
+    // Reject new agreements while initiating is paused
+    assert_not_paused(deps.storage, "initiate", |paused| paused.initiate)?;
+
+    // Validate both assets and ensure they can be escrowed
+    initiator_token.validate(deps.api)?;
+    counterparty_token.validate(deps.api)?;
+    assert_asset_supported(&initiator_token)?;
+    assert_asset_supported(&counterparty_token)?;
+
+    // Validate the further assets of each side, which form a basket of bank assets with its token
+    let initiator_assets = initiator_assets.unwrap_or_default();
+    let counterparty_assets = counterparty_assets.unwrap_or_default();
+    for asset in initiator_assets.iter().chain(&counterparty_assets) {
+        asset.validate(deps.api)?;
+    }
+    assert_basket_valid(&initiator_token, &initiator_assets)?;
+    assert_basket_valid(&counterparty_token, &counterparty_assets)?;
+    let initiator_basket: Vec<Asset> = std::iter::once(&initiator_token)
+        .chain(&initiator_assets)
+        .cloned()
+        .collect();
+
+    // Verify that initiator's deposit matches the assets provided exactly, and all assets and
+    // their amounts are allowed
+    assert_deposit_matches_assets(&deposit, &initiator_basket)?;
+    for asset in initiator_basket
+        .iter()
+        .chain([&counterparty_token])
+        .chain(&counterparty_assets)
+    {
+        assert_asset_allowed(deps.storage, asset)?;
+    }
 
-    // Ensure initiator is different from counterparty
-    assert_sender_is_different_from_counterparty(&info.sender, &counterparty)?;
+    // Validate the counterparty address and ensure initiator is different from counterparty
+    if let Some(counterparty) = &counterparty {
+        deps.api.addr_validate(counterparty.as_str())?;
+        assert_sender_is_different_from_counterparty(&sender, counterparty)?;
+    }
+
+    // Ensure the agreement does not expire before it can be accepted
+    assert_expiration_in_future(&expires, &env.block)?;
+
+    // Validate the arbiter address, then ensure it is neither party and its fee is a valid share
+    if let Some(arbiter) = &arbiter {
+        deps.api.addr_validate(arbiter.as_str())?;
+    }
+    let arbiter_fee_bps = arbiter_fee_bps.unwrap_or_default();
+    let parties: Vec<&Addr> = std::iter::once(&sender).chain(&counterparty).collect();
+    assert_arbiter_valid(&arbiter, arbiter_fee_bps, &parties)?;
+
+    // Ensure the milestones split the counterparty token exactly
+    let milestones = milestones.unwrap_or_default();
+    assert_milestones_valid(&milestones, &counterparty_token)?;
+    if !milestones.is_empty() && !counterparty_assets.is_empty() {
+        return Err(ContractError::InvalidBasket {
+            reason: "milestones cannot split a basket of assets".to_string(),
+        });
+    }
+
+    // Ensure the hash time lock, if any, can be unlocked and refunded
+    assert_htlc_valid(&htlc, &milestones, &env.block)?;
+
+    // Ensure the cancel penalty can be paid out of the deposits
+    assert_cancel_penalty_valid(&cancel_penalty, &initiator_token, &counterparty_token)?;
 
     // Generate new agreement ID and update agreement status counts
     let id =
@@ -100,45 +580,132 @@ fn initiate_agreement(
     // Create agreement struct and save to storage
     let agreement = Agreement {
         id,
-        initiator: info.sender.clone(),
+        initiator: sender.clone(),
         counterparty: counterparty.clone(),
         initiator_token: initiator_token.clone(),
         counterparty_token: counterparty_token.clone(),
-        status: STATUS_INITIATED.to_string(),
+        status: AgreementStatus::Initiated,
+        expires,
+        arbiter,
+        arbiter_fee_bps,
+        dispute: None,
+        milestones: milestones
+            .into_iter()
+            .map(|amount| Milestone {
+                amount,
+                released: false,
+            })
+            .collect(),
+        filled: Uint128::zero(),
+        htlc,
+        rejection: None,
+        cancel_policy: cancel_policy.unwrap_or_default(),
+        cancel_penalty,
+        initiator_assets,
+        counterparty_assets,
     };
     AGREEMENTS.save(deps.storage, id, &agreement)?;
 
+    // Record the initiator's deposit in the escrow ledger of the agreement
+    record_deposit(deps.storage, id, &sender, &deposit)?;
+
     // Return success response with attributes
     Ok(Response::new()
         .add_attribute("method", "initiate_agreement")
         .add_attribute("id", id.to_string())
         .add_attribute("status", agreement.status.to_string())
-        .add_attribute("initiator", info.sender.clone())
-        .add_attribute("counterparty", counterparty.clone())
-        .add_attribute("initiator_token", initiator_token.into_string())
-        .add_attribute("counterparty_token", counterparty_token.into_string()))
+        .add_attribute("initiator", sender)
+        .add_attribute("counterparty", agreement.counterparty_label())
+        .add_attribute("initiator_token", initiator_token.to_string())
+        .add_attribute("counterparty_token", counterparty_token.to_string())
+        .add_attributes(basket_attributes(&agreement)))
 }
 
 /// Accepts an agreement by its ID, progressing its status to accepted.
-fn accept_agreement(deps: DepsMut, info: MessageInfo, id: u64) -> Result<Response, ContractError> {
+fn accept_agreement(
+    deps: DepsMut,
+    env: Env,
+    sender: Addr,
+    deposit: Deposit,
+    id: u64,
+) -> Result<Response, ContractError> {
+    // Reject acceptance while accepting is paused
+    assert_not_paused(deps.storage, "accept", |paused| paused.accept)?;
+
     // Load agreement from storage by ID
     let mut agreement = AGREEMENTS.load(deps.storage, id)?;
 
-    // Verify sender matches the counterparty of the agreement
-    assert_sender_matches_counterparty(&info.sender, &agreement.counterparty)?;
+    // Verify sender matches the counterparty of the agreement, or take an open offer
+    match &agreement.counterparty {
+        Some(counterparty) => assert_sender_matches_counterparty(&sender, counterparty)?,
+        None => {
+            assert_sender_is_different_from_counterparty(&agreement.initiator, &sender)?;
+            assert_arbiter_valid(
+                &agreement.arbiter,
+                agreement.arbiter_fee_bps,
+                &[&agreement.initiator, &sender],
+            )?;
+            agreement.counterparty = Some(sender.clone());
+        }
+    }
 
-    // Verify sender's funds match the counterparty's token amount
-    assert_funds_match_token_amount(&info.funds, &agreement.counterparty_token)?;
+    // Verify sender's deposit matches the counterparty's assets, which are still allowed
+    let counterparty_basket = agreement.counterparty_basket();
+    assert_deposit_matches_assets(&deposit, &counterparty_basket)?;
+    for asset in &counterparty_basket {
+        assert_asset_allowed(deps.storage, asset)?;
+    }
 
     // Assert agreement status is INITIATED before accepting
-    assert_agreement_has_status(&agreement.status, &[STATUS_INITIATED])?;
+    assert_agreement_has_status(&agreement.status, &[AgreementStatus::Initiated])?;
+
+    // The counterparty answers a pending counter-offer by accepting it or proposing another
+    if pending_counter_offer(deps.storage, id)?.is_some() {
+        return Err(ContractError::CounterOfferPending { id });
+    }
+
+    // Partially filled agreements can only be completed by further fills
+    if !agreement.filled.is_zero() {
+        return Err(ContractError::InvalidFill {
+            id,
+            reason: "agreement is partially filled".to_string(),
+        });
+    }
+
+    // Reject acceptance once the agreement has expired, or its timelock has passed and the
+    // initiator may refund the hash time locked deposit
+    assert_agreement_not_expired(id, &agreement.expires, &env.block)?;
+    if let Some(htlc) = &agreement.htlc {
+        assert_agreement_not_expired(id, &Some(htlc.timelock), &env.block)?;
+    }
+
+    // Ensure all of the initiator's NFTs have been escrowed
+    assert_deposit_complete(
+        deps.storage,
+        id,
+        &agreement.initiator,
+        &agreement.initiator_token,
+    )?;
+
+    // Record the deposit and wait for the rest if the counterparty sends more than one NFT
+    record_deposit(deps.storage, id, &sender, &deposit)?;
+    if assert_deposit_complete(deps.storage, id, &sender, &agreement.counterparty_token).is_err() {
+        // Save the agreement, the first deposit to an open offer claims it for the sender
+        AGREEMENTS.save(deps.storage, id, &agreement)?;
+        return Ok(Response::new()
+            .add_attribute("method", "accept_agreement")
+            .add_attribute("id", id.to_string())
+            .add_attribute("status", agreement.status.to_string())
+            .add_attribute("depositor", sender)
+            .add_attribute("deposit", deposit_to_string(&deposit)));
+    }
 
     // Update agreement status counts
     INITIATED_AGREEMENT_COUNT.update(deps.storage, |count| -> StdResult<_> { Ok(count - 1) })?;
     ACCEPTED_AGREEMENT_COUNT.update(deps.storage, |count| -> StdResult<_> { Ok(count + 1) })?;
 
     // Update agreement status to ACCEPTED and save back to storage
-    agreement.status = STATUS_ACCEPTED.to_string();
+    agreement.status = AgreementStatus::Accepted;
     AGREEMENTS.save(deps.storage, id, &agreement)?;
 
     // Return success response with attributes
@@ -146,62 +713,118 @@ fn accept_agreement(deps: DepsMut, info: MessageInfo, id: u64) -> Result<Respons
         .add_attribute("method", "accept_agreement")
         .add_attribute("id", id.to_string())
         .add_attribute("status", agreement.status.to_string())
-        .add_attribute("initiator", agreement.initiator)
-        .add_attribute("counterparty", agreement.counterparty)
-        .add_attribute("initiator_token", agreement.initiator_token.into_string())
+        .add_attribute("initiator", &agreement.initiator)
+        .add_attribute("counterparty", agreement.counterparty_label())
+        .add_attribute("initiator_token", agreement.initiator_token.to_string())
         .add_attribute(
             "counterparty_token",
-            agreement.counterparty_token.into_string(),
+            agreement.counterparty_token.to_string(),
         ))
 }
 
+/// Escrows a further NFT of the initiator of an agreement whose initiator token is several NFTs.
+fn deposit_nft(
+    deps: DepsMut,
+    sender: Addr,
+    deposit: Deposit,
+    id: u64,
+) -> Result<Response, ContractError> {
+    // Further NFTs of the initiator complete the initiation of an agreement
+    assert_not_paused(deps.storage, "initiate", |paused| paused.initiate)?;
+
+    // Load agreement from storage by ID
+    let agreement = AGREEMENTS.load(deps.storage, id)?;
+
+    // Verify sender is the initiator of the agreement
+    assert_sender_authorized(&sender, &[&agreement.initiator])?;
+
+    // Verify the deposit is one of the initiator's NFTs
+    match &deposit {
+        Deposit::Cw721 { contract, token_id } => {
+            assert_nft_matches_asset(contract, token_id, &agreement.initiator_token)?
+        }
+        _ => {
+            return Err(ContractError::UnexpectedFunds {
+                expected: agreement.initiator_token.to_string(),
+                found: deposit_to_string(&deposit),
+            })
+        }
+    }
+
+    // Assert agreement status is INITIATED before depositing
+    assert_agreement_has_status(&agreement.status, &[AgreementStatus::Initiated])?;
+
+    // Record the NFT in the escrow ledger of the agreement
+    record_deposit(deps.storage, id, &sender, &deposit)?;
+
+    // Return success response with attributes
+    Ok(Response::new()
+        .add_attribute("method", "deposit_nft")
+        .add_attribute("id", id.to_string())
+        .add_attribute("status", agreement.status.to_string())
+        .add_attribute("depositor", sender)
+        .add_attribute("deposit", deposit_to_string(&deposit)))
+}
+
 /// Executes an accepted agreement, transferring tokens between parties.
 fn execute_agreement(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
     id: u64,
+    preimage: Option<HexBinary>,
 ) -> Result<Response, ContractError> {
+    // Reject execution while executing is paused
+    assert_not_paused(deps.storage, "execute", |paused| paused.execute)?;
+
     // Load agreement from storage by ID
     let mut agreement = AGREEMENTS.load(deps.storage, id)?;
 
     // Verify sender is authorized (either initiator or counterparty)
-    assert_sender_authorized(
-        &info.sender,
-        &[&agreement.initiator, &agreement.counterparty],
-    )?;
+    assert_sender_authorized(&info.sender, &agreement.parties())?;
 
     // Assert agreement status is ACCEPTED before execution
-    assert_agreement_has_status(&agreement.status, &[STATUS_ACCEPTED])?;
+    assert_agreement_has_status(&agreement.status, &[AgreementStatus::Accepted])?;
+
+    // Milestone agreements are only executed by approving their last milestone
+    if !agreement.milestones.is_empty() {
+        return Err(ContractError::MilestoneAgreement { id });
+    }
+
+    // Hash time locked agreements execute with the preimage of the hashlock before the timelock
+    if let Some(htlc) = &agreement.htlc {
+        if htlc.timelock.is_expired(&env.block) {
+            return Err(ContractError::AgreementExpired { id });
+        }
+        assert_preimage_matches(id, htlc, &preimage)?;
+    }
 
-    // Verify contract has sufficient funds for initiator and counterparty tokens
-    assert_contract_has_sufficient_funds(&deps, &env, &agreement.initiator_token)?;
-    assert_contract_has_sufficient_funds(&deps, &env, &agreement.counterparty_token)?;
+    // Verify the agreement's escrow holds the assets of the initiator and the counterparty
+    let counterparty = agreement.accepted_counterparty()?.clone();
+    for asset in agreement.initiator_basket() {
+        assert_agreement_has_sufficient_funds(deps.storage, id, &agreement.initiator, &asset)?;
+    }
+    for asset in agreement.counterparty_basket() {
+        assert_agreement_has_sufficient_funds(deps.storage, id, &counterparty, &asset)?;
+    }
 
     // Update agreement status counts
     ACCEPTED_AGREEMENT_COUNT.update(deps.storage, |count| -> StdResult<_> { Ok(count - 1) })?;
     EXECUTED_AGREEMENT_COUNT.update(deps.storage, |count| -> StdResult<_> { Ok(count + 1) })?;
 
-    // Define messages to send tokens from one party to the other
-    let messages = vec![
-        BankMsg::Send {
-            to_address: agreement.counterparty.to_string(),
-            amount: vec![coin(
-                agreement.initiator_token.amount,
-                &agreement.initiator_token.address,
-            )],
-        },
-        BankMsg::Send {
-            to_address: agreement.initiator.to_string(),
-            amount: vec![coin(
-                agreement.counterparty_token.amount,
-                &agreement.counterparty_token.address,
-            )],
-        },
-    ];
+    // Define messages to send each party's deposits to the other party, minus the fees
+    let config = CONFIG.load(deps.storage)?;
+    let mut fees = Vec::new();
+    let mut messages =
+        settle_deposits(deps.storage, &config, &agreement, &counterparty, &mut fees)?;
+    let (fee_messages, fee_attributes) = pay_fees(&config, &fees)?;
+    messages.extend(fee_messages);
+
+    // Drop any request to cancel, consent only applies while the agreement is accepted
+    CANCEL_REQUESTS.remove(deps.storage, id);
 
     // Update agreement status to EXECUTED and save back to storage
-    agreement.status = STATUS_EXECUTED.to_string();
+    agreement.status = AgreementStatus::Executed;
     AGREEMENTS.save(deps.storage, id, &agreement)?;
 
     // Return success response with messages and attributes
@@ -210,19 +833,26 @@ fn execute_agreement(
         .add_attribute("method", "execute_agreement")
         .add_attribute("id", id.to_string())
         .add_attribute("status", agreement.status.to_string())
-        .add_attribute("initiator", agreement.initiator)
-        .add_attribute("counterparty", agreement.counterparty)
-        .add_attribute("initiator_token", agreement.initiator_token.into_string())
+        .add_attribute("initiator", &agreement.initiator)
+        .add_attribute("counterparty", agreement.counterparty_label())
+        .add_attribute("initiator_token", agreement.initiator_token.to_string())
         .add_attribute(
             "counterparty_token",
-            agreement.counterparty_token.into_string(),
+            agreement.counterparty_token.to_string(),
+        )
+        .add_attributes(basket_attributes(&agreement))
+        .add_attributes(fee_attributes)
+        .add_attributes(
+            preimage
+                .filter(|_| agreement.htlc.is_some())
+                .map(|preimage| Attribute::new("preimage", preimage.to_hex())),
         ))
 }
 
 /// Cancels an initiated or accepted agreement, refunding tokens if necessary.
 fn cancel_agreement(
     deps: DepsMut,
-    env: Env,
+    _env: Env,
     info: MessageInfo,
     id: u64,
 ) -> Result<Response, ContractError> {
@@ -230,72 +860,1922 @@ fn cancel_agreement(
     let mut agreement = AGREEMENTS.load(deps.storage, id)?;
 
     // Verify sender is authorized (either initiator or counterparty)
-    assert_sender_authorized(
-        &info.sender,
-        &[&agreement.initiator, &agreement.counterparty],
-    )?;
+    assert_sender_authorized(&info.sender, &agreement.parties())?;
 
     // Assert agreement status is INITIATED or ACCEPTED before cancellation
-    assert_agreement_has_status(&agreement.status, &[STATUS_INITIATED, STATUS_ACCEPTED])?;
+    assert_agreement_has_status(
+        &agreement.status,
+        &[AgreementStatus::Initiated, AgreementStatus::Accepted],
+    )?;
 
-    // Update agreement status counts based on agreement status
-    if agreement.status == STATUS_INITIATED {
-        INITIATED_AGREEMENT_COUNT
-            .update(deps.storage, |count| -> StdResult<_> { Ok(count - 1) })?;
+    // Accepted agreements requiring mutual consent are canceled by request and confirmation
+    if agreement.status == AgreementStatus::Accepted
+        && agreement.cancel_policy == CancelPolicy::MutualConsent
+    {
+        return Err(ContractError::MutualCancelRequired { id });
     }
-    if agreement.status == STATUS_ACCEPTED {
-        ACCEPTED_AGREEMENT_COUNT.update(deps.storage, |count| -> StdResult<_> { Ok(count - 1) })?;
+
+    // Canceling an accepted agreement alone costs the sender its penalty, which is held back
+    // from its refund and paid to the other party
+    let mut penalty_messages: Vec<CosmosMsg> = Vec::new();
+    let mut penalty_attributes = Vec::new();
+    if agreement.status == AgreementStatus::Accepted {
+        if let Some(penalty) = agreement.cancel_penalty(&info.sender) {
+            let counterparty = agreement.accepted_counterparty()?;
+            let recipient = if info.sender == agreement.initiator {
+                counterparty
+            } else {
+                &agreement.initiator
+            };
+            assert_agreement_has_sufficient_funds(deps.storage, id, &info.sender, &penalty)?;
+            release_deposit(deps.storage, id, &info.sender, &penalty)?;
+            penalty_messages = penalty.transfer_msgs(recipient)?;
+            penalty_attributes = vec![
+                Attribute::new("penalty", penalty.to_string()),
+                Attribute::new("penalty_paid_by", &info.sender),
+                Attribute::new("penalty_paid_to", recipient),
+            ];
+        }
     }
-    CANCELED_AGREEMENT_COUNT.update(deps.storage, |count| -> StdResult<_> { Ok(count + 1) })?;
 
-    // Vector to hold refund messages
-    let mut messages: Vec<BankMsg> = Vec::new();
+    // Cancel the agreement and refund every deposit
+    let messages = cancel_and_refund(deps.storage, &mut agreement)?;
 
-    // Refund initiator's tokens if they have sufficient funds stored in the contract
-    if assert_contract_has_sufficient_funds(&deps, &env, &agreement.initiator_token).is_ok() {
-        messages.push(BankMsg::Send {
-            to_address: agreement.initiator.to_string(),
-            amount: vec![coin(
-                agreement.initiator_token.amount,
-                &agreement.initiator_token.address,
-            )],
-        });
+    // Return success response with refund and penalty messages and attributes
+    Ok(Response::new()
+        .add_messages(messages)
+        .add_messages(penalty_messages)
+        .add_attribute("method", "cancel_agreement")
+        .add_attribute("id", id.to_string())
+        .add_attribute("status", agreement.status.to_string())
+        .add_attribute("initiator", &agreement.initiator)
+        .add_attribute("counterparty", agreement.counterparty_label())
+        .add_attribute("initiator_token", agreement.initiator_token.to_string())
+        .add_attribute(
+            "counterparty_token",
+            agreement.counterparty_token.to_string(),
+        )
+        .add_attributes(penalty_attributes))
+}
+
+/// Asks the other party to cancel an accepted agreement that requires mutual consent.
+fn request_cancel(deps: DepsMut, info: MessageInfo, id: u64) -> Result<Response, ContractError> {
+    // Load agreement from storage by ID
+    let agreement = AGREEMENTS.load(deps.storage, id)?;
+
+    // Verify sender is authorized (either initiator or counterparty)
+    assert_sender_authorized(&info.sender, &agreement.parties())?;
+
+    // Assert agreement status is ACCEPTED, initiated agreements are simply canceled
+    assert_agreement_has_status(&agreement.status, &[AgreementStatus::Accepted])?;
+
+    // Requests are only needed when the agreement requires mutual consent
+    if agreement.cancel_policy != CancelPolicy::MutualConsent {
+        return Err(ContractError::UnilateralCancel { id });
     }
 
-    // Refund counterparty's tokens if they have sufficient funds stored in the contract
-    if assert_contract_has_sufficient_funds(&deps, &env, &agreement.counterparty_token).is_ok() {
-        messages.push(BankMsg::Send {
-            to_address: agreement.counterparty.to_string(),
-            amount: vec![coin(
-                agreement.counterparty_token.amount,
-                &agreement.counterparty_token.address,
-            )],
-        });
+    // Record the sender as the party requesting to cancel, replacing an earlier request
+    CANCEL_REQUESTS.save(deps.storage, id, &info.sender)?;
+
+    // Return success response with attributes
+    Ok(Response::new()
+        .add_attribute("method", "request_cancel")
+        .add_attribute("id", id.to_string())
+        .add_attribute("requested_by", info.sender))
+}
+
+/// Confirms the other party's request to cancel an accepted agreement, refunding both parties.
+fn confirm_cancel(deps: DepsMut, info: MessageInfo, id: u64) -> Result<Response, ContractError> {
+    // Load agreement from storage by ID
+    let mut agreement = AGREEMENTS.load(deps.storage, id)?;
+
+    // Verify sender is authorized (either initiator or counterparty)
+    assert_sender_authorized(&info.sender, &agreement.parties())?;
+
+    // Assert agreement status is ACCEPTED before cancellation
+    assert_agreement_has_status(&agreement.status, &[AgreementStatus::Accepted])?;
+
+    // Verify the other party requested to cancel
+    let requested_by = CANCEL_REQUESTS
+        .may_load(deps.storage, id)?
+        .ok_or(ContractError::NoCancelRequest { id })?;
+    if requested_by == info.sender {
+        return Err(ContractError::NoCancelRequest { id });
+    }
+
+    // Cancel the agreement and refund every deposit
+    let messages = cancel_and_refund(deps.storage, &mut agreement)?;
+
+    // Return success response with refund messages and attributes
+    Ok(Response::new()
+        .add_messages(messages)
+        .add_attribute("method", "confirm_cancel")
+        .add_attribute("id", id.to_string())
+        .add_attribute("status", agreement.status.to_string())
+        .add_attribute("requested_by", requested_by)
+        .add_attribute("confirmed_by", info.sender))
+}
+
+/// Cancels an initiated or accepted agreement and returns the messages refunding its deposits.
+fn cancel_and_refund(
+    storage: &mut dyn Storage,
+    agreement: &mut Agreement,
+) -> Result<Vec<CosmosMsg>, ContractError> {
+    let id = agreement.id;
+
+    // Once accepted, a hash time locked agreement can only be executed or refunded, so that
+    // neither party can back out after the preimage was revealed on another chain
+    if agreement.htlc.is_some() && agreement.status == AgreementStatus::Accepted {
+        return Err(ContractError::HtlcLocked { id });
+    }
+
+    // Once a milestone has been released the agreement can only run to completion
+    if agreement
+        .milestones
+        .iter()
+        .any(|milestone| milestone.released)
+    {
+        return Err(ContractError::MilestoneReleased { id });
     }
 
+    // Update agreement status counts based on agreement status
+    if agreement.status == AgreementStatus::Initiated {
+        INITIATED_AGREEMENT_COUNT.update(storage, |count| -> StdResult<_> { Ok(count - 1) })?;
+    }
+    if agreement.status == AgreementStatus::Accepted {
+        ACCEPTED_AGREEMENT_COUNT.update(storage, |count| -> StdResult<_> { Ok(count - 1) })?;
+    }
+    CANCELED_AGREEMENT_COUNT.update(storage, |count| -> StdResult<_> { Ok(count + 1) })?;
+
+    // Refund every deposit recorded for this agreement to the party that made it
+    let mut messages: Vec<CosmosMsg> = Vec::new();
+    for (depositor, assets) in take_deposits(storage, id)? {
+        messages.extend(transfer_assets_msgs(&assets, &depositor)?);
+    }
+
+    // Drop any request to cancel, it has been settled by this cancellation
+    CANCEL_REQUESTS.remove(storage, id);
+
     // Update agreement status to CANCELED and save back to storage
-    agreement.status = STATUS_CANCELED.to_string();
+    agreement.status = AgreementStatus::Canceled;
+    AGREEMENTS.save(storage, id, agreement)?;
+
+    Ok(messages)
+}
+
+/// Declines an initiated agreement on behalf of its counterparty, refunding the initiator.
+fn reject_agreement(
+    deps: DepsMut,
+    info: MessageInfo,
+    id: u64,
+    reason: String,
+) -> Result<Response, ContractError> {
+    // Load agreement from storage by ID
+    let mut agreement = AGREEMENTS.load(deps.storage, id)?;
+
+    // Verify sender is the counterparty, open offers are simply left untaken
+    let counterparty = agreement.accepted_counterparty()?;
+    assert_sender_matches_counterparty(&info.sender, counterparty)?;
+
+    // Assert agreement status is INITIATED, accepted agreements are canceled instead
+    assert_agreement_has_status(&agreement.status, &[AgreementStatus::Initiated])?;
+
+    // Update agreement status counts
+    INITIATED_AGREEMENT_COUNT.update(deps.storage, |count| -> StdResult<_> { Ok(count - 1) })?;
+    REJECTED_AGREEMENT_COUNT.update(deps.storage, |count| -> StdResult<_> { Ok(count + 1) })?;
+
+    // Refund every deposit recorded for this agreement to the party that made it
+    let mut messages: Vec<CosmosMsg> = Vec::new();
+    for (depositor, assets) in take_deposits(deps.storage, id)? {
+        messages.extend(transfer_assets_msgs(&assets, &depositor)?);
+    }
+
+    // Update agreement status to REJECTED, record the rejection and save back to storage
+    agreement.status = AgreementStatus::Rejected;
+    agreement.rejection = Some(Rejection {
+        rejected_by: info.sender.clone(),
+        reason: reason.clone(),
+    });
     AGREEMENTS.save(deps.storage, id, &agreement)?;
 
-    // Return success response with refund messages and attributes
+    // Return success response with refund messages and attributes, the reason may be empty
     Ok(Response::new()
         .add_messages(messages)
-        .add_attribute("method", "cancel_agreement")
+        .add_attribute("method", "reject_agreement")
         .add_attribute("id", id.to_string())
         .add_attribute("status", agreement.status.to_string())
-        .add_attribute("initiator", agreement.initiator)
-        .add_attribute("counterparty", agreement.counterparty)
-        .add_attribute("initiator_token", agreement.initiator_token.into_string())
-        .add_attribute(
-            "counterparty_token",
-            agreement.counterparty_token.into_string(),
-        ))
+        .add_attribute("initiator", &agreement.initiator)
+        .add_attribute("rejected_by", info.sender)
+        .add_attributes((!reason.is_empty()).then(|| Attribute::new("reason", reason))))
 }
 
-/// Handles incoming queries to retrieve agreement information.
-#[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
-    match msg {
+/// Refunds the deposits of an initiated agreement that expired before being accepted.
+fn claim_expired(deps: DepsMut, env: Env, id: u64) -> Result<Response, ContractError> {
+    // Load agreement from storage by ID
+    let mut agreement = AGREEMENTS.load(deps.storage, id)?;
+
+    // Assert agreement status is INITIATED, accepted agreements no longer expire
+    assert_agreement_has_status(&agreement.status, &[AgreementStatus::Initiated])?;
+
+    // Verify the agreement has expired, anyone may claim the refund afterwards
+    if assert_agreement_not_expired(id, &agreement.expires, &env.block).is_ok() {
+        return Err(ContractError::AgreementNotExpired { id });
+    }
+
+    // Update agreement status counts
+    INITIATED_AGREEMENT_COUNT.update(deps.storage, |count| -> StdResult<_> { Ok(count - 1) })?;
+    EXPIRED_AGREEMENT_COUNT.update(deps.storage, |count| -> StdResult<_> { Ok(count + 1) })?;
+
+    // Refund every deposit recorded for this agreement to the party that made it
+    let mut messages: Vec<CosmosMsg> = Vec::new();
+    for (depositor, assets) in take_deposits(deps.storage, id)? {
+        messages.extend(transfer_assets_msgs(&assets, &depositor)?);
+    }
+
+    // Update agreement status to EXPIRED and save back to storage
+    agreement.status = AgreementStatus::Expired;
+    AGREEMENTS.save(deps.storage, id, &agreement)?;
+
+    // Return success response with refund messages and attributes
+    Ok(Response::new()
+        .add_messages(messages)
+        .add_attribute("method", "claim_expired")
+        .add_attribute("id", id.to_string())
+        .add_attribute("status", agreement.status.to_string())
+        .add_attribute("initiator", &agreement.initiator)
+        .add_attribute("counterparty", agreement.counterparty_label())
+        .add_attribute("initiator_token", agreement.initiator_token.to_string())
+        .add_attribute(
+            "counterparty_token",
+            agreement.counterparty_token.to_string(),
+        ))
+}
+
+/// Refunds the deposits of an initiated or accepted HTLC agreement once its timelock has passed.
+fn refund_htlc(deps: DepsMut, env: Env, id: u64) -> Result<Response, ContractError> {
+    // Load agreement from storage by ID
+    let mut agreement = AGREEMENTS.load(deps.storage, id)?;
+
+    // Ensure the agreement is hash time locked
+    let htlc = agreement
+        .htlc
+        .clone()
+        .ok_or(ContractError::NotHtlc { id })?;
+
+    // Assert agreement status is INITIATED or ACCEPTED before refunding
+    assert_agreement_has_status(
+        &agreement.status,
+        &[AgreementStatus::Initiated, AgreementStatus::Accepted],
+    )?;
+
+    // Verify the timelock has passed, anyone may claim the refund afterwards
+    if !htlc.timelock.is_expired(&env.block) {
+        return Err(ContractError::AgreementNotExpired { id });
+    }
+
+    // Update agreement status counts based on agreement status
+    if agreement.status == AgreementStatus::Initiated {
+        INITIATED_AGREEMENT_COUNT
+            .update(deps.storage, |count| -> StdResult<_> { Ok(count - 1) })?;
+    }
+    if agreement.status == AgreementStatus::Accepted {
+        ACCEPTED_AGREEMENT_COUNT.update(deps.storage, |count| -> StdResult<_> { Ok(count - 1) })?;
+    }
+    EXPIRED_AGREEMENT_COUNT.update(deps.storage, |count| -> StdResult<_> { Ok(count + 1) })?;
+
+    // Refund every deposit recorded for this agreement to the party that made it
+    let mut messages: Vec<CosmosMsg> = Vec::new();
+    for (depositor, assets) in take_deposits(deps.storage, id)? {
+        messages.extend(transfer_assets_msgs(&assets, &depositor)?);
+    }
+
+    // Drop any request to cancel, consent only applies while the agreement is accepted
+    CANCEL_REQUESTS.remove(deps.storage, id);
+
+    // Update agreement status to EXPIRED and save back to storage
+    agreement.status = AgreementStatus::Expired;
+    AGREEMENTS.save(deps.storage, id, &agreement)?;
+
+    // Return success response with refund messages and attributes
+    Ok(Response::new()
+        .add_messages(messages)
+        .add_attribute("method", "refund_htlc")
+        .add_attribute("id", id.to_string())
+        .add_attribute("status", agreement.status.to_string())
+        .add_attribute("initiator", &agreement.initiator)
+        .add_attribute("counterparty", agreement.counterparty_label())
+        .add_attribute("timelock", htlc.timelock.to_string()))
+}
+
+/// Credits fungible deposits to the sender's withdrawable balance.
+fn deposit_balance(
+    deps: DepsMut,
+    sender: Addr,
+    deposit: Deposit,
+) -> Result<Response, ContractError> {
+    // Only fungible assets can be held as balances
+    let assets = deposit.to_assets();
+    if assets.is_empty() {
+        return Err(ContractError::InsufficientFunds);
+    }
+    if let Some(nft) = assets.iter().find(|asset| asset.is_nft()) {
+        return Err(ContractError::UnsupportedAsset {
+            asset: nft.to_string(),
+        });
+    }
+
+    // Add each asset to the sender's balance of its token
+    for asset in &assets {
+        let token = asset.token();
+        BALANCES.update(deps.storage, (&sender, &token), |balance| -> StdResult<_> {
+            Ok(match balance {
+                Some(balance) => balance.with_amount(balance.amount() + asset.amount()),
+                None => asset.clone(),
+            })
+        })?;
+    }
+
+    // Return success response with attributes
+    Ok(Response::new()
+        .add_attribute("method", "deposit")
+        .add_attribute("owner", sender)
+        .add_attribute("deposit", assets_to_string(&assets)))
+}
+
+/// Withdraws an asset from the sender's balance.
+fn withdraw_balance(
+    deps: DepsMut,
+    info: MessageInfo,
+    asset: Asset,
+) -> Result<Response, ContractError> {
+    // Validate the asset to withdraw
+    asset.validate(deps.api)?;
+
+    // Deduct the asset from the sender's balance
+    debit_balance(deps.storage, &info.sender, &asset)?;
+
+    // Return success response with transfer messages and attributes
+    Ok(Response::new()
+        .add_messages(asset.transfer_msgs(&info.sender)?)
+        .add_attribute("method", "withdraw")
+        .add_attribute("owner", info.sender)
+        .add_attribute("asset", asset.to_string()))
+}
+
+/// Settles an offer signed by its initiator in one step, paying the initiator token from the
+/// initiator's balance and the counterparty token from the sender's deposit.
+fn accept_signed_offer(
+    deps: DepsMut,
+    env: Env,
+    sender: Addr,
+    deposit: Deposit,
+    offer: SignedOffer,
+    signature: Binary,
+    pubkey: Binary,
+) -> Result<Response, ContractError> {
+    // A signed offer is initiated, accepted and executed at once
+    assert_not_paused(deps.storage, "initiate", |paused| paused.initiate)?;
+    assert_not_paused(deps.storage, "accept", |paused| paused.accept)?;
+    assert_not_paused(deps.storage, "execute", |paused| paused.execute)?;
+
+    // Validate the offered assets, signed offers only settle fungible assets
+    deps.api.addr_validate(offer.initiator.as_str())?;
+    for asset in [&offer.initiator_token, &offer.counterparty_token] {
+        asset.validate(deps.api)?;
+        assert_asset_supported(asset)?;
+        if asset.is_nft() {
+            return Err(ContractError::UnsupportedAsset {
+                asset: asset.to_string(),
+            });
+        }
+        assert_asset_allowed(deps.storage, asset)?;
+    }
+
+    // Verify the sender may take the offer and deposited the counterparty token
+    if let Some(counterparty) = &offer.counterparty {
+        assert_sender_matches_counterparty(&sender, counterparty)?;
+    }
+    assert_sender_is_different_from_counterparty(&offer.initiator, &sender)?;
+    assert_deposit_matches_token_amount(&deposit, &offer.counterparty_token)?;
+
+    // Reject offers that never expire, expired offers and offers that have already been
+    // accepted or revoked
+    if matches!(offer.expires, Expiration::Never {}) {
+        return Err(ContractError::InvalidExpiration {
+            expires: offer.expires.to_string(),
+        });
+    }
+    if offer.expires.is_expired(&env.block) {
+        return Err(ContractError::SignedOfferExpired {});
+    }
+    let digest = signed_offer_digest(&env, &offer)?;
+    assert_offer_unused(deps.storage, &digest)?;
+
+    // Verify the initiator signed the offer
+    assert_signed_by(deps.api, &digest, &signature, &pubkey, &offer.initiator)?;
+
+    // Move the initiator token from the initiator's balance into escrow
+    debit_balance(deps.storage, &offer.initiator, &offer.initiator_token)?;
+
+    // Generate new agreement ID, the agreement is executed right away
+    let id =
+        TOTAL_AGREEMENT_COUNT.update(deps.storage, |count| -> StdResult<_> { Ok(count + 1) })?;
+    EXECUTED_AGREEMENT_COUNT.update(deps.storage, |count| -> StdResult<_> { Ok(count + 1) })?;
+    USED_OFFERS.save(deps.storage, &digest, &id)?;
+
+    // Record the agreement and both deposits, then settle them
+    let agreement = Agreement {
+        id,
+        initiator: offer.initiator.clone(),
+        initiator_token: offer.initiator_token,
+        counterparty: Some(sender.clone()),
+        counterparty_token: offer.counterparty_token,
+        status: AgreementStatus::Executed,
+        expires: Some(offer.expires),
+        arbiter: None,
+        arbiter_fee_bps: 0,
+        dispute: None,
+        milestones: vec![],
+        filled: Uint128::zero(),
+        htlc: None,
+        rejection: None,
+        cancel_policy: CancelPolicy::default(),
+        initiator_assets: vec![],
+        counterparty_assets: vec![],
+        cancel_penalty: None,
+    };
+    AGREEMENTS.save(deps.storage, id, &agreement)?;
+    DEPOSITS.save(
+        deps.storage,
+        (id, &agreement.initiator),
+        &vec![agreement.initiator_token.clone()],
+    )?;
+    record_deposit(deps.storage, id, &sender, &deposit)?;
+    let config = CONFIG.load(deps.storage)?;
+    let mut fees = Vec::new();
+    let mut messages = settle_deposits(deps.storage, &config, &agreement, &sender, &mut fees)?;
+    let (fee_messages, fee_attributes) = pay_fees(&config, &fees)?;
+    messages.extend(fee_messages);
+
+    // Return success response with messages and attributes
+    Ok(Response::new()
+        .add_messages(messages)
+        .add_attribute("method", "accept_signed_offer")
+        .add_attribute("id", id.to_string())
+        .add_attribute("status", agreement.status.to_string())
+        .add_attribute("initiator", &agreement.initiator)
+        .add_attribute("counterparty", sender)
+        .add_attribute("initiator_token", agreement.initiator_token.to_string())
+        .add_attribute(
+            "counterparty_token",
+            agreement.counterparty_token.to_string(),
+        )
+        .add_attribute("nonce", offer.nonce.to_string())
+        .add_attribute("digest", HexBinary::from(digest).to_hex())
+        .add_attributes(fee_attributes))
+}
+
+/// Revokes an offer signed off-chain by the sender before anyone accepts it.
+fn revoke_signed_offer(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    offer: SignedOffer,
+) -> Result<Response, ContractError> {
+    // Verify the sender is the initiator who signed the offer
+    assert_sender_authorized(&info.sender, &[&offer.initiator])?;
+
+    // Ensure the offer has not been accepted or revoked already
+    let digest = signed_offer_digest(&env, &offer)?;
+    assert_offer_unused(deps.storage, &digest)?;
+
+    // Record the digest as used, so that the offer can no longer be accepted
+    USED_OFFERS.save(deps.storage, &digest, &REVOKED_OFFER_ID)?;
+
+    // Return success response with attributes
+    Ok(Response::new()
+        .add_attribute("method", "revoke_signed_offer")
+        .add_attribute("initiator", info.sender)
+        .add_attribute("nonce", offer.nonce.to_string())
+        .add_attribute("digest", HexBinary::from(digest).to_hex()))
+}
+
+/// Freezes an accepted agreement with an arbiter until the arbiter resolves the dispute.
+fn raise_dispute(
+    deps: DepsMut,
+    info: MessageInfo,
+    id: u64,
+    reason: String,
+) -> Result<Response, ContractError> {
+    // Load agreement from storage by ID
+    let mut agreement = AGREEMENTS.load(deps.storage, id)?;
+
+    // Verify sender is authorized (either initiator or counterparty)
+    assert_sender_authorized(&info.sender, &agreement.parties())?;
+
+    // Assert agreement status is ACCEPTED, only fully funded agreements can be disputed
+    assert_agreement_has_status(&agreement.status, &[AgreementStatus::Accepted])?;
+
+    // Hash time locked agreements settle by the preimage or the timelock, an arbiter could
+    // otherwise split the escrow after the preimage was revealed on another chain
+    if agreement.htlc.is_some() {
+        return Err(ContractError::HtlcLocked { id });
+    }
+
+    // Ensure an arbiter was appointed to resolve the dispute
+    let arbiter = agreement
+        .arbiter
+        .clone()
+        .ok_or(ContractError::NoArbiter { id })?;
+
+    // Update agreement status counts
+    ACCEPTED_AGREEMENT_COUNT.update(deps.storage, |count| -> StdResult<_> { Ok(count - 1) })?;
+    DISPUTED_AGREEMENT_COUNT.update(deps.storage, |count| -> StdResult<_> { Ok(count + 1) })?;
+
+    // Drop any request to cancel, consent only applies while the agreement is accepted
+    CANCEL_REQUESTS.remove(deps.storage, id);
+
+    // Update agreement status to DISPUTED, record the dispute and save back to storage
+    agreement.status = AgreementStatus::Disputed;
+    agreement.dispute = Some(Dispute {
+        raised_by: info.sender.clone(),
+        reason: reason.clone(),
+    });
+    AGREEMENTS.save(deps.storage, id, &agreement)?;
+
+    // Return success response with attributes
+    Ok(Response::new()
+        .add_attribute("method", "raise_dispute")
+        .add_attribute("id", id.to_string())
+        .add_attribute("status", agreement.status.to_string())
+        .add_attribute("raised_by", info.sender)
+        .add_attribute("arbiter", arbiter)
+        .add_attribute("reason", reason))
+}
+
+/// Settles a disputed agreement by paying out the escrow as split by the arbiter, minus its fee.
+fn resolve_dispute(
+    deps: DepsMut,
+    info: MessageInfo,
+    id: u64,
+    payout: DisputePayout,
+) -> Result<Response, ContractError> {
+    // Load agreement from storage by ID
+    let mut agreement = AGREEMENTS.load(deps.storage, id)?;
+
+    // Verify sender is the arbiter of the agreement
+    let arbiter = agreement
+        .arbiter
+        .clone()
+        .ok_or(ContractError::NoArbiter { id })?;
+    assert_sender_authorized(&info.sender, &[&arbiter])?;
+
+    // Assert agreement status is DISPUTED before resolving
+    assert_agreement_has_status(&agreement.status, &[AgreementStatus::Disputed])?;
+
+    // Validate every awarded asset
+    for asset in payout.initiator.iter().chain(&payout.counterparty) {
+        asset.validate(deps.api)?;
+    }
+
+    // Take the arbiter fee from every fungible asset held in escrow for this agreement
+    let escrowed = merge_assets(
+        load_deposits(deps.storage, id)?
+            .into_iter()
+            .flat_map(|(_, assets)| assets),
+    );
+    let mut fees: Vec<Asset> = Vec::new();
+    let mut remainder: Vec<Asset> = Vec::new();
+    for asset in escrowed {
+        let fee = if asset.is_nft() {
+            Uint128::zero()
+        } else {
+            asset
+                .amount()
+                .multiply_ratio(agreement.arbiter_fee_bps, BPS_DENOMINATOR)
+        };
+        if !fee.is_zero() {
+            fees.push(asset.with_amount(fee));
+        }
+        if asset.is_nft() || asset.amount() > fee {
+            remainder.push(asset.with_amount(asset.amount() - fee));
+        }
+    }
+
+    // Ensure the payout splits exactly what is left in escrow between the parties
+    let awarded = merge_assets(payout.initiator.iter().chain(&payout.counterparty).cloned());
+    if awarded != merge_assets(remainder.clone()) {
+        return Err(ContractError::InvalidPayout {
+            expected: assets_to_string(&remainder),
+            found: assets_to_string(&awarded),
+        });
+    }
+
+    // Clear the escrow ledger, all of it is paid out below
+    take_deposits(deps.storage, id)?;
+
+    // Update agreement status counts
+    DISPUTED_AGREEMENT_COUNT.update(deps.storage, |count| -> StdResult<_> { Ok(count - 1) })?;
+    RESOLVED_AGREEMENT_COUNT.update(deps.storage, |count| -> StdResult<_> { Ok(count + 1) })?;
+
+    // Define messages paying the awarded assets to each party and the fee to the arbiter
+    let mut messages = transfer_assets_msgs(&payout.initiator, &agreement.initiator)?;
+    let counterparty = agreement.accepted_counterparty()?;
+    messages.extend(transfer_assets_msgs(&payout.counterparty, counterparty)?);
+    messages.extend(transfer_assets_msgs(&fees, &arbiter)?);
+
+    // Update agreement status to RESOLVED and save back to storage
+    agreement.status = AgreementStatus::Resolved;
+    AGREEMENTS.save(deps.storage, id, &agreement)?;
+
+    // Return success response with messages and attributes
+    Ok(Response::new()
+        .add_messages(messages)
+        .add_attribute("method", "resolve_dispute")
+        .add_attribute("id", id.to_string())
+        .add_attribute("status", agreement.status.to_string())
+        .add_attribute("arbiter", arbiter)
+        .add_attribute("initiator_payout", assets_to_string(&payout.initiator))
+        .add_attribute(
+            "counterparty_payout",
+            assets_to_string(&payout.counterparty),
+        )
+        .add_attribute("arbiter_fee", assets_to_string(&fees)))
+}
+
+/// Releases the next milestone of an accepted agreement from the counterparty's deposit to the
+/// initiator, executing the agreement with the last one.
+fn approve_milestone(
+    deps: DepsMut,
+    info: MessageInfo,
+    id: u64,
+    index: u32,
+) -> Result<Response, ContractError> {
+    // Releasing milestones pays out the escrow like executing does
+    assert_not_paused(deps.storage, "execute", |paused| paused.execute)?;
+
+    // Load agreement from storage by ID
+    let mut agreement = AGREEMENTS.load(deps.storage, id)?;
+
+    // Verify sender is the initiator of the agreement
+    assert_sender_authorized(&info.sender, &[&agreement.initiator])?;
+
+    // Assert agreement status is ACCEPTED before releasing milestones
+    assert_agreement_has_status(&agreement.status, &[AgreementStatus::Accepted])?;
+
+    // Ensure the milestone is the next one to be released, milestones are released in order
+    let next = agreement
+        .milestones
+        .iter()
+        .position(|milestone| !milestone.released);
+    if next != Some(index as usize) {
+        return Err(ContractError::InvalidMilestone {
+            id,
+            index,
+            next: next.map(|next| next.to_string()).unwrap_or_default(),
+        });
+    }
+
+    // Verify the counterparty's deposit still holds the milestone amount
+    let counterparty = agreement.accepted_counterparty()?.clone();
+    let milestone = &mut agreement.milestones[index as usize];
+    let released = agreement.counterparty_token.with_amount(milestone.amount);
+    assert_agreement_has_sufficient_funds(deps.storage, id, &counterparty, &released)?;
+
+    // Release the milestone amount from the counterparty's deposit to the initiator, minus the fee
+    release_deposit(deps.storage, id, &counterparty, &released)?;
+    milestone.released = true;
+    let config = CONFIG.load(deps.storage)?;
+    let mut fees = Vec::new();
+    let mut messages = pay_minus_fees(
+        deps.storage,
+        std::slice::from_ref(&released),
+        config.counterparty_fee_bps,
+        &agreement.initiator,
+        &mut fees,
+    )?;
+
+    // Execute the agreement once the last milestone is released
+    if agreement
+        .milestones
+        .iter()
+        .all(|milestone| milestone.released)
+    {
+        // Update agreement status counts
+        ACCEPTED_AGREEMENT_COUNT.update(deps.storage, |count| -> StdResult<_> { Ok(count - 1) })?;
+        EXECUTED_AGREEMENT_COUNT.update(deps.storage, |count| -> StdResult<_> { Ok(count + 1) })?;
+
+        // Send each party's remaining deposits to the other party, minus the fees
+        messages.extend(settle_deposits(
+            deps.storage,
+            &config,
+            &agreement,
+            &counterparty,
+            &mut fees,
+        )?);
+
+        // Drop any request to cancel, consent only applies while the agreement is accepted
+        CANCEL_REQUESTS.remove(deps.storage, id);
+
+        agreement.status = AgreementStatus::Executed;
+    }
+
+    // Save the agreement with the released milestone back to storage
+    AGREEMENTS.save(deps.storage, id, &agreement)?;
+
+    // Pay the fees to the fee recipient
+    let (fee_messages, fee_attributes) = pay_fees(&config, &fees)?;
+    messages.extend(fee_messages);
+
+    // Return success response with messages and attributes
+    Ok(Response::new()
+        .add_messages(messages)
+        .add_attribute("method", "approve_milestone")
+        .add_attribute("id", id.to_string())
+        .add_attribute("index", index.to_string())
+        .add_attribute("status", agreement.status.to_string())
+        .add_attribute("released", released.to_string())
+        .add_attributes(fee_attributes))
+}
+
+/// Fills part of an initiated agreement, paying the deposit to the initiator and a proportional
+/// share of the initiator token to the sender.
+fn fill_agreement(
+    deps: DepsMut,
+    env: Env,
+    sender: Addr,
+    deposit: Deposit,
+    id: u64,
+) -> Result<Response, ContractError> {
+    // A fill both takes and settles part of the agreement
+    assert_not_paused(deps.storage, "accept", |paused| paused.accept)?;
+    assert_not_paused(deps.storage, "execute", |paused| paused.execute)?;
+
+    // Load agreement from storage by ID
+    let mut agreement = AGREEMENTS.load(deps.storage, id)?;
+    let invalid = |reason: &str| ContractError::InvalidFill {
+        id,
+        reason: reason.to_string(),
+    };
+
+    // Verify sender matches the counterparty of the agreement, anyone else may fill open offers
+    match &agreement.counterparty {
+        Some(counterparty) => assert_sender_matches_counterparty(&sender, counterparty)?,
+        None => assert_sender_is_different_from_counterparty(&agreement.initiator, &sender)?,
+    }
+
+    // Assert agreement status is INITIATED before filling
+    assert_agreement_has_status(&agreement.status, &[AgreementStatus::Initiated])?;
+
+    // Reject fills once the agreement has expired
+    assert_agreement_not_expired(id, &agreement.expires, &env.block)?;
+
+    // Only fungible tokens exchanged without milestones can be split over several fills
+    if agreement.initiator_token.is_nft() || agreement.counterparty_token.is_nft() {
+        return Err(invalid("NFTs cannot be partially filled"));
+    }
+    if !agreement.milestones.is_empty() {
+        return Err(invalid("milestone agreements cannot be partially filled"));
+    }
+    if agreement.htlc.is_some() {
+        return Err(invalid(
+            "hash time locked agreements cannot be partially filled",
+        ));
+    }
+    if agreement.is_basket() {
+        return Err(invalid("basket agreements cannot be partially filled"));
+    }
+
+    // Verify the deposit holds nothing but the counterparty token, and no more than is left
+    let amount: Uint128 = deposit
+        .to_assets()
+        .iter()
+        .map(|asset| asset.covered_amount(&agreement.counterparty_token))
+        .sum();
+    if amount.is_zero() {
+        return Err(ContractError::InsufficientFunds);
+    }
+    assert_deposit_matches_token_amount(
+        &deposit,
+        &agreement.counterparty_token.with_amount(amount),
+    )?;
+    assert_asset_allowed(deps.storage, &agreement.counterparty_token)?;
+    if agreement.filled + amount > agreement.counterparty_token.amount() {
+        return Err(invalid(&format!(
+            "fill of `{}` exceeds the unfilled `{}`",
+            amount,
+            agreement.counterparty_token.amount() - agreement.filled
+        )));
+    }
+
+    // Work out the share of the initiator token paid for the fill
+    let payout = fill_payout(
+        agreement.initiator_token.amount(),
+        agreement.counterparty_token.amount(),
+        agreement.filled,
+        amount,
+    );
+    if payout.is_zero() {
+        return Err(invalid("fill is too small to pay out any initiator token"));
+    }
+    let payout_asset = agreement.initiator_token.with_amount(payout);
+    assert_agreement_has_sufficient_funds(deps.storage, id, &agreement.initiator, &payout_asset)?;
+
+    // Release the payout from the initiator's deposit and record the fill
+    release_deposit(deps.storage, id, &agreement.initiator, &payout_asset)?;
+    let index = FILLS
+        .prefix(id)
+        .keys(deps.storage, None, None, Order::Descending)
+        .next()
+        .transpose()?
+        .map_or(0, |index| index + 1);
+    FILLS.save(
+        deps.storage,
+        (id, index),
+        &Fill {
+            filler: sender.clone(),
+            amount,
+            payout,
+        },
+    )?;
+    agreement.filled += amount;
+
+    // Pay the fill to the initiator and the payout to the sender, minus the fee of each side
+    let config = CONFIG.load(deps.storage)?;
+    let mut fees = Vec::new();
+    let mut messages = pay_minus_fees(
+        deps.storage,
+        &[payout_asset],
+        config.initiator_fee_bps,
+        &sender,
+        &mut fees,
+    )?;
+    messages.extend(pay_minus_fees(
+        deps.storage,
+        &[agreement.counterparty_token.with_amount(amount)],
+        config.counterparty_fee_bps,
+        &agreement.initiator,
+        &mut fees,
+    )?);
+    let (fee_messages, fee_attributes) = pay_fees(&config, &fees)?;
+    messages.extend(fee_messages);
+
+    // Execute the agreement once it is completely filled
+    if agreement.filled == agreement.counterparty_token.amount() {
+        // Update agreement status counts
+        INITIATED_AGREEMENT_COUNT
+            .update(deps.storage, |count| -> StdResult<_> { Ok(count - 1) })?;
+        EXECUTED_AGREEMENT_COUNT.update(deps.storage, |count| -> StdResult<_> { Ok(count + 1) })?;
+
+        // Clear the escrow ledger, the fills paid out all of it
+        take_deposits(deps.storage, id)?;
+
+        agreement.status = AgreementStatus::Executed;
+    }
+
+    // Save the filled agreement back to storage
+    AGREEMENTS.save(deps.storage, id, &agreement)?;
+
+    // Return success response with messages and attributes
+    Ok(Response::new()
+        .add_messages(messages)
+        .add_attribute("method", "fill_agreement")
+        .add_attribute("id", id.to_string())
+        .add_attribute("index", index.to_string())
+        .add_attribute("status", agreement.status.to_string())
+        .add_attribute("filler", sender)
+        .add_attribute("amount", amount.to_string())
+        .add_attribute("payout", payout.to_string())
+        .add_attribute("remaining", agreement.remaining().to_string())
+        .add_attributes(fee_attributes))
+}
+
+/// Replaces the counterparty and tokens of an initiated agreement, rebalancing the initiator's
+/// deposit to the new initiator token.
+#[allow(clippy::too_many_arguments)]
+fn amend_agreement(
+    deps: DepsMut,
+    env: Env,
+    sender: Addr,
+    deposit: Deposit,
+    id: u64,
+    counterparty: Option<Addr>,
+    counterparty_token: Asset,
+    initiator_token: Asset,
+) -> Result<Response, ContractError> {
+    // Top-ups of the initiator deposit are new deposits
+    assert_not_paused(deps.storage, "initiate", |paused| paused.initiate)?;
+
+    // Load agreement from storage by ID
+    let mut agreement = AGREEMENTS.load(deps.storage, id)?;
+
+    // Verify sender is the initiator of the agreement
+    if sender != agreement.initiator {
+        return Err(ContractError::Unauthorized {
+            expected: agreement.initiator.to_string(),
+            found: sender.to_string(),
+        });
+    }
+
+    // Assert agreement status is INITIATED and it has not expired before amending
+    assert_agreement_has_status(&agreement.status, &[AgreementStatus::Initiated])?;
+    assert_agreement_not_expired(id, &agreement.expires, &env.block)?;
+
+    // Partially filled agreements keep the terms their fills were made on
+    if !agreement.filled.is_zero() {
+        return Err(ContractError::InvalidFill {
+            id,
+            reason: "agreement is partially filled".to_string(),
+        });
+    }
+
+    // Amendments replace single tokens, so the terms of baskets cannot be amended
+    if agreement.is_basket() {
+        return Err(ContractError::BasketAgreement { id });
+    }
+
+    // Validate the new tokens, the initiator's NFTs cannot be exchanged for others
+    for asset in [&initiator_token, &counterparty_token] {
+        asset.validate(deps.api)?;
+        assert_asset_supported(asset)?;
+        assert_asset_allowed(deps.storage, asset)?;
+    }
+    if initiator_token != agreement.initiator_token
+        && (initiator_token.is_nft() || agreement.initiator_token.is_nft())
+    {
+        return Err(ContractError::UnsupportedAsset {
+            asset: initiator_token.to_string(),
+        });
+    }
+
+    // Validate the new counterparty address and ensure it differs from the initiator and the
+    // arbiter
+    if let Some(counterparty) = &counterparty {
+        deps.api.addr_validate(counterparty.as_str())?;
+        assert_sender_is_different_from_counterparty(&sender, counterparty)?;
+    }
+    let parties: Vec<&Addr> = std::iter::once(&sender).chain(&counterparty).collect();
+    assert_arbiter_valid(&agreement.arbiter, agreement.arbiter_fee_bps, &parties)?;
+
+    // Ensure the milestones still split the counterparty token exactly
+    let milestones: Vec<Uint128> = agreement.milestones.iter().map(|m| m.amount).collect();
+    assert_milestones_valid(&milestones, &counterparty_token)?;
+
+    // Verify the deposit tops up the escrowed initiator deposit to the new initiator token
+    let held = load_initiator_deposit(deps.storage, &agreement)?;
+    assert_top_up_matches(&deposit, &held, &initiator_token)?;
+
+    // The amended terms replace a pending counter-offer
+    if let Some((round, mut offer)) = pending_counter_offer(deps.storage, id)? {
+        offer.status = CounterOfferStatus::Superseded;
+        COUNTER_OFFERS.save(deps.storage, (id, round), &offer)?;
+    }
+
+    // Rebalance the initiator's deposit, refunding whatever exceeds the new initiator token
+    record_deposit(deps.storage, id, &sender, &deposit)?;
+    let mut messages = refund_initiator_surplus(deps.storage, &agreement, &initiator_token)?;
+
+    // Refund a partial deposit the counterparty made on the previous terms
+    if counterparty != agreement.counterparty || counterparty_token != agreement.counterparty_token
+    {
+        messages.extend(refund_counterparty_deposits(deps.storage, &agreement)?);
+    }
+
+    // Update the agreement terms and save back to storage
+    let amended = Event::new("amend_agreement")
+        .add_attribute("id", id.to_string())
+        .add_attribute("previous_counterparty", agreement.counterparty_label())
+        .add_attribute(
+            "previous_initiator_token",
+            agreement.initiator_token.to_string(),
+        )
+        .add_attribute(
+            "previous_counterparty_token",
+            agreement.counterparty_token.to_string(),
+        );
+    agreement.counterparty = counterparty;
+    agreement.initiator_token = initiator_token;
+    agreement.counterparty_token = counterparty_token;
+    AGREEMENTS.save(deps.storage, id, &agreement)?;
+
+    // Return success response with refund messages, attributes and the amend event
+    Ok(Response::new()
+        .add_messages(messages)
+        .add_attribute("method", "amend_agreement")
+        .add_attribute("id", id.to_string())
+        .add_attribute("status", agreement.status.to_string())
+        .add_event(
+            amended
+                .add_attribute("counterparty", agreement.counterparty_label())
+                .add_attribute("initiator_token", agreement.initiator_token.to_string())
+                .add_attribute(
+                    "counterparty_token",
+                    agreement.counterparty_token.to_string(),
+                ),
+        ))
+}
+
+/// Proposes new terms for an initiated agreement, which take effect once the other party
+/// accepts them.
+fn propose_counter_offer(
+    deps: DepsMut,
+    env: Env,
+    sender: Addr,
+    deposit: Deposit,
+    id: u64,
+    initiator_token: Asset,
+    counterparty_token: Asset,
+) -> Result<Response, ContractError> {
+    // Top-ups of the initiator deposit are new deposits
+    assert_not_paused(deps.storage, "initiate", |paused| paused.initiate)?;
+
+    // Load agreement from storage by ID
+    let agreement = AGREEMENTS.load(deps.storage, id)?;
+
+    // Verify sender is one of the parties of an agreement taken by a counterparty
+    agreement.accepted_counterparty()?;
+    assert_sender_authorized(&sender, &agreement.parties())?;
+
+    // Assert agreement status is INITIATED and it has not expired before negotiating
+    assert_agreement_has_status(&agreement.status, &[AgreementStatus::Initiated])?;
+    assert_agreement_not_expired(id, &agreement.expires, &env.block)?;
+
+    // Milestones split the counterparty token, so their terms cannot be renegotiated
+    if !agreement.milestones.is_empty() {
+        return Err(ContractError::MilestoneAgreement { id });
+    }
+
+    // Counter-offers replace single tokens, so the terms of baskets cannot be renegotiated
+    if agreement.is_basket() {
+        return Err(ContractError::BasketAgreement { id });
+    }
+
+    // Partially filled agreements keep the terms their fills were made on
+    if !agreement.filled.is_zero() {
+        return Err(ContractError::InvalidFill {
+            id,
+            reason: "agreement is partially filled".to_string(),
+        });
+    }
+
+    // Validate the proposed tokens, the initiator's deposit can only be rebalanced if fungible
+    for asset in [&initiator_token, &counterparty_token] {
+        asset.validate(deps.api)?;
+        assert_asset_supported(asset)?;
+        assert_asset_allowed(deps.storage, asset)?;
+    }
+    if let Some(nft) = [&agreement.initiator_token, &initiator_token]
+        .into_iter()
+        .find(|asset| asset.is_nft())
+    {
+        return Err(ContractError::UnsupportedAsset {
+            asset: nft.to_string(),
+        });
+    }
+
+    // Supersede the pending counter-offer, refunding a top-up made for it
+    if let Some((pending, mut offer)) = pending_counter_offer(deps.storage, id)? {
+        offer.status = CounterOfferStatus::Superseded;
+        COUNTER_OFFERS.save(deps.storage, (id, pending), &offer)?;
+    }
+    let messages = refund_initiator_surplus(deps.storage, &agreement, &agreement.initiator_token)?;
+
+    // The initiator escrows any increase of its remaining deposit along with its proposal
+    if sender == agreement.initiator {
+        let held = load_initiator_deposit(deps.storage, &agreement)?;
+        assert_top_up_matches(&deposit, &held, &initiator_token)?;
+        record_deposit(deps.storage, id, &sender, &deposit)?;
+    } else {
+        assert_no_deposit(&deposit)?;
+    }
+
+    // Record the counter-offer as the next round of the negotiation
+    let round = COUNTER_OFFERS
+        .prefix(id)
+        .keys(deps.storage, None, None, Order::Descending)
+        .next()
+        .transpose()?
+        .map_or(0, |round| round + 1);
+    let offer = CounterOffer {
+        proposer: sender,
+        initiator_token,
+        counterparty_token,
+        status: CounterOfferStatus::Pending,
+    };
+    COUNTER_OFFERS.save(deps.storage, (id, round), &offer)?;
+
+    // Return success response with refund messages and attributes
+    Ok(Response::new()
+        .add_messages(messages)
+        .add_attribute("method", "propose_counter_offer")
+        .add_attribute("id", id.to_string())
+        .add_attribute("round", round.to_string())
+        .add_attribute("proposer", offer.proposer)
+        .add_attribute("initiator_token", offer.initiator_token.to_string())
+        .add_attribute("counterparty_token", offer.counterparty_token.to_string()))
+}
+
+/// Accepts the pending counter-offer of the other party, making its tokens the terms of the
+/// agreement and rebalancing the initiator's deposit.
+fn accept_counter_offer(
+    deps: DepsMut,
+    env: Env,
+    sender: Addr,
+    deposit: Deposit,
+    id: u64,
+) -> Result<Response, ContractError> {
+    // Reject acceptance while accepting is paused
+    assert_not_paused(deps.storage, "accept", |paused| paused.accept)?;
+
+    // Load agreement from storage by ID
+    let mut agreement = AGREEMENTS.load(deps.storage, id)?;
+
+    // Assert agreement status is INITIATED and it has not expired before accepting
+    assert_agreement_has_status(&agreement.status, &[AgreementStatus::Initiated])?;
+    assert_agreement_not_expired(id, &agreement.expires, &env.block)?;
+
+    // Partially filled agreements keep the terms their fills were made on
+    if !agreement.filled.is_zero() {
+        return Err(ContractError::InvalidFill {
+            id,
+            reason: "agreement is partially filled".to_string(),
+        });
+    }
+
+    // Load the pending counter-offer, which only the other party can accept
+    let (round, mut offer) =
+        pending_counter_offer(deps.storage, id)?.ok_or(ContractError::NoCounterOffer { id })?;
+    let counterparty = agreement.accepted_counterparty()?;
+    let acceptor = if offer.proposer == agreement.initiator {
+        counterparty
+    } else {
+        &agreement.initiator
+    };
+    if sender != acceptor {
+        return Err(ContractError::Unauthorized {
+            expected: acceptor.to_string(),
+            found: sender.to_string(),
+        });
+    }
+
+    // The initiator tops up its escrowed deposit when accepting a higher initiator token
+    if sender == agreement.initiator {
+        let held = load_initiator_deposit(deps.storage, &agreement)?;
+        assert_top_up_matches(&deposit, &held, &offer.initiator_token)?;
+        record_deposit(deps.storage, id, &sender, &deposit)?;
+    } else {
+        assert_no_deposit(&deposit)?;
+    }
+
+    // Refund whatever the initiator's deposit holds beyond the new initiator token
+    let mut messages = refund_initiator_surplus(deps.storage, &agreement, &offer.initiator_token)?;
+
+    // Refund a partial deposit the counterparty made towards the previous counterparty token
+    if offer.counterparty_token != agreement.counterparty_token {
+        messages.extend(refund_counterparty_deposits(deps.storage, &agreement)?);
+    }
+
+    // Update the agreement terms and the counter-offer, then save both back to storage
+    agreement.initiator_token = offer.initiator_token.clone();
+    agreement.counterparty_token = offer.counterparty_token.clone();
+    AGREEMENTS.save(deps.storage, id, &agreement)?;
+    offer.status = CounterOfferStatus::Accepted;
+    COUNTER_OFFERS.save(deps.storage, (id, round), &offer)?;
+
+    // Return success response with refund messages and attributes
+    Ok(Response::new()
+        .add_messages(messages)
+        .add_attribute("method", "accept_counter_offer")
+        .add_attribute("id", id.to_string())
+        .add_attribute("round", round.to_string())
+        .add_attribute("acceptor", sender)
+        .add_attribute("initiator_token", agreement.initiator_token.to_string())
+        .add_attribute(
+            "counterparty_token",
+            agreement.counterparty_token.to_string(),
+        ))
+}
+
+/// Initiates an agreement between several participants, who each deposit their share before it
+/// can be executed.
+fn initiate_multi_party_agreement(
+    deps: DepsMut,
+    info: MessageInfo,
+    participants: Vec<Participant>,
+) -> Result<Response, ContractError> {
+    // Reject new agreements while initiating is paused
+    assert_not_paused(deps.storage, "initiate", |paused| paused.initiate)?;
+
+    // Deposits are made by each participant afterwards
+    assert_no_deposit(&Deposit::Native(info.funds))?;
+
+    // Validate every participant and its assets, and ensure they can be escrowed
+    for participant in &participants {
+        deps.api.addr_validate(participant.address.as_str())?;
+        for asset in participant.deposit.iter().chain(&participant.payout) {
+            asset.validate(deps.api)?;
+            assert_asset_supported(asset)?;
+            assert_asset_allowed(deps.storage, asset)?;
+        }
+    }
+
+    // Ensure the payouts redistribute exactly the deposits
+    assert_participants_valid(&participants)?;
+
+    // Generate new multi-party agreement ID, create the agreement and save it to storage
+    let id = MULTI_PARTY_AGREEMENT_COUNT
+        .update(deps.storage, |count| -> StdResult<_> { Ok(count + 1) })?;
+    let agreement = MultiPartyAgreement {
+        id,
+        creator: info.sender.clone(),
+        participants,
+        status: AgreementStatus::Initiated,
+    };
+    MULTI_PARTY_AGREEMENTS.save(deps.storage, id, &agreement)?;
+
+    // Return success response with attributes
+    Ok(Response::new()
+        .add_attribute("method", "initiate_multi_party_agreement")
+        .add_attribute("id", id.to_string())
+        .add_attribute("status", agreement.status.to_string())
+        .add_attribute("creator", info.sender)
+        .add_attribute(
+            "participants",
+            agreement
+                .addresses()
+                .iter()
+                .map(|address| address.to_string())
+                .collect::<Vec<_>>()
+                .join(","),
+        ))
+}
+
+/// Deposits assets towards the sender's share of a multi-party agreement, which is accepted
+/// once every participant has deposited in full.
+fn deposit_multi_party(
+    deps: DepsMut,
+    sender: Addr,
+    deposit: Deposit,
+    id: u64,
+) -> Result<Response, ContractError> {
+    // Deposits to multi-party agreements are accepted like counterparty deposits
+    assert_not_paused(deps.storage, "accept", |paused| paused.accept)?;
+
+    // Load multi-party agreement from storage by ID
+    let mut agreement = MULTI_PARTY_AGREEMENTS.load(deps.storage, id)?;
+
+    // Verify sender is one of the participants
+    assert_sender_authorized(&sender, &agreement.addresses())?;
+
+    // Assert agreement status is INITIATED before depositing
+    assert_agreement_has_status(&agreement.status, &[AgreementStatus::Initiated])?;
+
+    // Verify the deposit is part of what the sender still owes
+    let mut escrowed = MULTI_PARTY_DEPOSITS
+        .may_load(deps.storage, (id, &sender))?
+        .unwrap_or_default();
+    let required = agreement
+        .participant(&sender)
+        .map(|participant| participant.deposit.as_slice())
+        .unwrap_or_default();
+    assert_deposit_outstanding(&deposit, &outstanding_assets(&escrowed, required))?;
+
+    // Record the deposit in the escrow ledger of the agreement
+    for asset in deposit.to_assets() {
+        if !escrowed.iter_mut().any(|held| held.merge(&asset)) {
+            escrowed.push(asset);
+        }
+    }
+    MULTI_PARTY_DEPOSITS.save(deps.storage, (id, &sender), &escrowed)?;
+
+    // Update agreement status to ACCEPTED once every participant has deposited in full
+    let mut complete = true;
+    for participant in &agreement.participants {
+        let held = MULTI_PARTY_DEPOSITS
+            .may_load(deps.storage, (id, &participant.address))?
+            .unwrap_or_default();
+        complete &= outstanding_assets(&held, &participant.deposit).is_empty();
+    }
+    if complete {
+        agreement.status = AgreementStatus::Accepted;
+        MULTI_PARTY_AGREEMENTS.save(deps.storage, id, &agreement)?;
+    }
+
+    // Return success response with attributes
+    Ok(Response::new()
+        .add_attribute("method", "deposit_multi_party")
+        .add_attribute("id", id.to_string())
+        .add_attribute("status", agreement.status.to_string())
+        .add_attribute("depositor", sender)
+        .add_attribute("deposit", deposit_to_string(&deposit)))
+}
+
+/// Executes a multi-party agreement every participant has deposited to, paying each participant
+/// its payout. Multi-party agreements have no initiator or counterparty side to charge, so they
+/// are exempt from the protocol fees.
+fn execute_multi_party_agreement(
+    deps: DepsMut,
+    info: MessageInfo,
+    id: u64,
+) -> Result<Response, ContractError> {
+    // Reject execution while executing is paused
+    assert_not_paused(deps.storage, "execute", |paused| paused.execute)?;
+
+    // Load multi-party agreement from storage by ID
+    let mut agreement = MULTI_PARTY_AGREEMENTS.load(deps.storage, id)?;
+
+    // Verify sender is one of the participants
+    assert_sender_authorized(&info.sender, &agreement.addresses())?;
+
+    // Assert agreement status is ACCEPTED, meaning every deposit is complete
+    assert_agreement_has_status(&agreement.status, &[AgreementStatus::Accepted])?;
+
+    // Clear the escrow ledger, the payouts add up to all of it
+    take_multi_party_deposits(deps.storage, id)?;
+
+    // Define messages paying each participant its payout
+    let mut messages: Vec<CosmosMsg> = Vec::new();
+    for participant in &agreement.participants {
+        messages.extend(transfer_assets_msgs(
+            &participant.payout,
+            &participant.address,
+        )?);
+    }
+
+    // Update agreement status to EXECUTED and save back to storage
+    agreement.status = AgreementStatus::Executed;
+    MULTI_PARTY_AGREEMENTS.save(deps.storage, id, &agreement)?;
+
+    // Return success response with messages and attributes
+    Ok(Response::new()
+        .add_messages(messages)
+        .add_attribute("method", "execute_multi_party_agreement")
+        .add_attribute("id", id.to_string())
+        .add_attribute("status", agreement.status.to_string())
+        .add_attribute("executed_by", info.sender))
+}
+
+/// Cancels a multi-party agreement that has not executed, refunding every participant whatever
+/// it has deposited so far.
+fn cancel_multi_party_agreement(
+    deps: DepsMut,
+    info: MessageInfo,
+    id: u64,
+) -> Result<Response, ContractError> {
+    // Load multi-party agreement from storage by ID
+    let mut agreement = MULTI_PARTY_AGREEMENTS.load(deps.storage, id)?;
+
+    // Verify sender is one of the participants
+    assert_sender_authorized(&info.sender, &agreement.addresses())?;
+
+    // Assert agreement status is INITIATED or ACCEPTED before canceling
+    assert_agreement_has_status(
+        &agreement.status,
+        &[AgreementStatus::Initiated, AgreementStatus::Accepted],
+    )?;
+
+    // Refund every deposit recorded for this agreement to the participant that made it
+    let mut messages: Vec<CosmosMsg> = Vec::new();
+    for (depositor, assets) in take_multi_party_deposits(deps.storage, id)? {
+        messages.extend(transfer_assets_msgs(&assets, &depositor)?);
+    }
+
+    // Update agreement status to CANCELED and save back to storage
+    agreement.status = AgreementStatus::Canceled;
+    MULTI_PARTY_AGREEMENTS.save(deps.storage, id, &agreement)?;
+
+    // Return success response with refund messages and attributes
+    Ok(Response::new()
+        .add_messages(messages)
+        .add_attribute("method", "cancel_multi_party_agreement")
+        .add_attribute("id", id.to_string())
+        .add_attribute("status", agreement.status.to_string())
+        .add_attribute("canceled_by", info.sender))
+}
+
+/// Proposes a new owner of the contract, replacing any earlier proposal.
+fn transfer_ownership(
+    deps: DepsMut,
+    info: MessageInfo,
+    new_owner: Addr,
+) -> Result<Response, ContractError> {
+    // Verify sender is the owner of the contract
+    assert_owner(deps.storage, &info.sender)?;
+
+    // Record the proposed owner, ownership only changes once it accepts
+    let new_owner = deps.api.addr_validate(new_owner.as_str())?;
+    OWNERSHIP.update(deps.storage, |mut ownership| -> StdResult<_> {
+        ownership.pending_owner = Some(new_owner.clone());
+        Ok(ownership)
+    })?;
+
+    // Return success response with attributes
+    Ok(Response::new()
+        .add_attribute("method", "transfer_ownership")
+        .add_attribute("owner", info.sender)
+        .add_attribute("pending_owner", new_owner))
+}
+
+/// Completes an ownership transfer, making the proposed owner the owner of the contract.
+fn accept_ownership(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+    // Load ownership from storage
+    let mut ownership = OWNERSHIP.load(deps.storage)?;
+
+    // Verify sender is the proposed owner
+    let pending_owner = ownership
+        .pending_owner
+        .take()
+        .ok_or(ContractError::NoPendingOwner {})?;
+    assert_sender_authorized(&info.sender, &[&pending_owner])?;
+
+    // Hand over ownership and save back to storage
+    ownership.owner = Some(pending_owner);
+    OWNERSHIP.save(deps.storage, &ownership)?;
+
+    // Return success response with attributes
+    Ok(Response::new()
+        .add_attribute("method", "accept_ownership")
+        .add_attribute("owner", info.sender))
+}
+
+/// Leaves the contract without an owner, dropping any pending ownership transfer.
+fn renounce_ownership(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+    // Verify sender is the owner of the contract
+    assert_owner(deps.storage, &info.sender)?;
+
+    // Clear the owner and the proposed owner
+    OWNERSHIP.save(deps.storage, &Ownership::default())?;
+
+    // Return success response with attributes
+    Ok(Response::new()
+        .add_attribute("method", "renounce_ownership")
+        .add_attribute("previous_owner", info.sender))
+}
+
+/// Sets or removes the guardian allowed to pause the contract, callable by the owner.
+fn set_guardian(
+    deps: DepsMut,
+    info: MessageInfo,
+    guardian: Option<Addr>,
+) -> Result<Response, ContractError> {
+    // Verify sender is the owner of the contract
+    assert_owner(deps.storage, &info.sender)?;
+
+    // Validate and save the guardian
+    let guardian = guardian
+        .map(|guardian| deps.api.addr_validate(guardian.as_str()))
+        .transpose()?;
+    CONFIG.update(deps.storage, |mut config| -> StdResult<_> {
+        config.guardian = guardian.clone();
+        Ok(config)
+    })?;
+
+    // Return success response with attributes
+    Ok(Response::new()
+        .add_attribute("method", "set_guardian")
+        .add_attribute(
+            "guardian",
+            guardian.map_or_else(|| "none".to_string(), |guardian| guardian.to_string()),
+        ))
+}
+
+/// Pauses or unpauses the selected operations, callable by the owner or guardian.
+fn set_paused(
+    deps: DepsMut,
+    info: MessageInfo,
+    paused: bool,
+    initiate: bool,
+    accept: bool,
+    execute: bool,
+) -> Result<Response, ContractError> {
+    // Verify sender is the owner or guardian of the contract
+    assert_owner_or_guardian(deps.storage, &info.sender)?;
+
+    // Update the selected flags, leaving the others unchanged
+    let mut flags = PAUSED.may_load(deps.storage)?.unwrap_or_default();
+    if initiate {
+        flags.initiate = paused;
+    }
+    if accept {
+        flags.accept = paused;
+    }
+    if execute {
+        flags.execute = paused;
+    }
+    PAUSED.save(deps.storage, &flags)?;
+
+    // Return success response with attributes
+    Ok(Response::new()
+        .add_attribute("method", if paused { "pause" } else { "unpause" })
+        .add_attribute("sender", info.sender)
+        .add_attribute("initiate_paused", flags.initiate.to_string())
+        .add_attribute("accept_paused", flags.accept.to_string())
+        .add_attribute("execute_paused", flags.execute.to_string()))
+}
+
+/// Adds or replaces the rule of a bank denom or token contract address.
+fn add_asset_rule(
+    deps: DepsMut,
+    info: MessageInfo,
+    token: String,
+    allowed: bool,
+    min_amount: Option<Uint128>,
+    max_amount: Option<Uint128>,
+) -> Result<Response, ContractError> {
+    // Verify sender is the owner of the contract
+    assert_owner(deps.storage, &info.sender)?;
+
+    // Validate the token and limits
+    if token.is_empty() {
+        return Err(ContractError::InvalidConfig {
+            reason: "token must not be empty".to_string(),
+        });
+    }
+    if let (Some(min_amount), Some(max_amount)) = (min_amount, max_amount) {
+        if min_amount > max_amount {
+            return Err(ContractError::InvalidConfig {
+                reason: "minimum amount cannot exceed the maximum amount".to_string(),
+            });
+        }
+    }
+
+    // Save the rule, replacing any earlier rule of the token
+    ASSET_RULES.save(
+        deps.storage,
+        &token,
+        &AssetRule {
+            allowed,
+            min_amount,
+            max_amount,
+        },
+    )?;
+
+    // Return success response with attributes
+    Ok(Response::new()
+        .add_attribute("method", "add_asset_rule")
+        .add_attribute("token", token)
+        .add_attribute("allowed", allowed.to_string()))
+}
+
+/// Removes the rule of a bank denom or token contract address.
+fn remove_asset_rule(
+    deps: DepsMut,
+    info: MessageInfo,
+    token: String,
+) -> Result<Response, ContractError> {
+    // Verify sender is the owner of the contract
+    assert_owner(deps.storage, &info.sender)?;
+
+    // Remove the rule, unlisted tokens are treated according to the list mode
+    ASSET_RULES.remove(deps.storage, &token);
+
+    // Return success response with attributes
+    Ok(Response::new()
+        .add_attribute("method", "remove_asset_rule")
+        .add_attribute("token", token))
+}
+
+/// Sets whether assets without a rule can be escrowed.
+fn set_asset_list_mode(
+    deps: DepsMut,
+    info: MessageInfo,
+    mode: AssetListMode,
+) -> Result<Response, ContractError> {
+    // Verify sender is the owner of the contract
+    assert_owner(deps.storage, &info.sender)?;
+
+    // Save the mode
+    ASSET_LIST_MODE.save(deps.storage, &mode)?;
+
+    // Return success response with attributes
+    let mode = match mode {
+        AssetListMode::Denylist => "denylist",
+        AssetListMode::Allowlist => "allowlist",
+    };
+    Ok(Response::new()
+        .add_attribute("method", "set_asset_list_mode")
+        .add_attribute("mode", mode))
+}
+
+/// Updates the fee recipient and fees of the config, callable by the owner.
+fn update_config(
+    deps: DepsMut,
+    info: MessageInfo,
+    fee_recipient: Option<Addr>,
+    initiator_fee_bps: Option<u16>,
+    counterparty_fee_bps: Option<u16>,
+) -> Result<Response, ContractError> {
+    // Verify sender is the owner of the contract
+    assert_owner(deps.storage, &info.sender)?;
+
+    // Load config from storage
+    let mut config = CONFIG.load(deps.storage)?;
+
+    // Update the fields that were set and validate the resulting fees
+    if let Some(fee_recipient) = fee_recipient {
+        config.fee_recipient = deps.api.addr_validate(fee_recipient.as_str())?;
+    }
+    if let Some(initiator_fee_bps) = initiator_fee_bps {
+        config.initiator_fee_bps = initiator_fee_bps;
+    }
+    if let Some(counterparty_fee_bps) = counterparty_fee_bps {
+        config.counterparty_fee_bps = counterparty_fee_bps;
+    }
+    assert_fees_valid(config.initiator_fee_bps, config.counterparty_fee_bps)?;
+
+    // Save the config back to storage
+    CONFIG.save(deps.storage, &config)?;
+
+    // Return success response with attributes
+    Ok(Response::new()
+        .add_attribute("method", "update_config")
+        .add_attribute("fee_recipient", config.fee_recipient)
+        .add_attribute("initiator_fee_bps", config.initiator_fee_bps.to_string())
+        .add_attribute(
+            "counterparty_fee_bps",
+            config.counterparty_fee_bps.to_string(),
+        ))
+}
+
+/// Takes the escrowed deposits of an agreement and builds the messages sending each party's
+/// deposits to the other party, adding the protocol fee of each side to `fees`.
+fn settle_deposits(
+    storage: &mut dyn Storage,
+    config: &Config,
+    agreement: &Agreement,
+    counterparty: &Addr,
+    fees: &mut Vec<Asset>,
+) -> Result<Vec<CosmosMsg>, ContractError> {
+    let mut messages: Vec<CosmosMsg> = Vec::new();
+    for (depositor, assets) in take_deposits(storage, agreement.id)? {
+        let (recipient, fee_bps) = if depositor == agreement.initiator {
+            (counterparty, config.initiator_fee_bps)
+        } else {
+            (&agreement.initiator, config.counterparty_fee_bps)
+        };
+        messages.extend(pay_minus_fees(storage, &assets, fee_bps, recipient, fees)?);
+    }
+
+    Ok(messages)
+}
+
+/// Builds the messages paying assets to a recipient minus the protocol fee, adding the fees taken
+/// to `fees`.
+fn pay_minus_fees(
+    storage: &mut dyn Storage,
+    assets: &[Asset],
+    fee_bps: u16,
+    recipient: &Addr,
+    fees: &mut Vec<Asset>,
+) -> Result<Vec<CosmosMsg>, ContractError> {
+    let mut payout: Vec<Asset> = Vec::new();
+    for asset in assets {
+        let fee = collect_fee(storage, asset, fee_bps)?;
+        if asset.is_nft() || asset.amount() > fee {
+            payout.push(asset.with_amount(asset.amount() - fee));
+        }
+        if !fee.is_zero() {
+            fees.push(asset.with_amount(fee));
+        }
+    }
+    transfer_assets_msgs(&payout, recipient)
+}
+
+/// Builds the messages paying the fees taken to the fee recipient and the attributes listing them.
+fn pay_fees(
+    config: &Config,
+    fees: &[Asset],
+) -> Result<(Vec<CosmosMsg>, Vec<Attribute>), ContractError> {
+    let messages = transfer_assets_msgs(fees, &config.fee_recipient)?;
+    let mut fee_attributes = Vec::new();
+    if !fees.is_empty() {
+        fee_attributes.push(Attribute::new("fees", assets_to_string(fees)));
+        fee_attributes.push(Attribute::new("fee_recipient", &config.fee_recipient));
+    }
+
+    Ok((messages, fee_attributes))
+}
+
+/// Returns the attributes listing the further assets of each side of a basket agreement.
+fn basket_attributes(agreement: &Agreement) -> Vec<Attribute> {
+    [
+        ("initiator_assets", &agreement.initiator_assets),
+        ("counterparty_assets", &agreement.counterparty_assets),
+    ]
+    .into_iter()
+    .filter(|(_, assets)| !assets.is_empty())
+    .map(|(key, assets)| Attribute::new(key, assets_to_string(assets)))
+    .collect()
+}
+
+/// Returns the fee taken from an asset paid out on execution and adds it to the accrued fees.
+fn collect_fee(storage: &mut dyn Storage, asset: &Asset, fee_bps: u16) -> StdResult<Uint128> {
+    if asset.is_nft() {
+        return Ok(Uint128::zero());
+    }
+    let fee = asset.amount().multiply_ratio(fee_bps, BPS_DENOMINATOR);
+    if !fee.is_zero() {
+        ACCRUED_FEES.update(storage, &asset.token(), |accrued| -> StdResult<_> {
+            Ok(accrued.unwrap_or_default() + fee)
+        })?;
+    }
+    Ok(fee)
+}
+
+/// Records the tokens escrowed for an agreement of the first version in the deposit ledger, which
+/// that version did not keep: the initiator token once initiated, and both tokens once accepted.
+fn backfill_deposits(storage: &mut dyn Storage, agreement: &Agreement) -> StdResult<()> {
+    if matches!(
+        agreement.status,
+        AgreementStatus::Initiated | AgreementStatus::Accepted
+    ) {
+        DEPOSITS.save(
+            storage,
+            (agreement.id, &agreement.initiator),
+            &vec![agreement.initiator_token.clone()],
+        )?;
+    }
+    if let (AgreementStatus::Accepted, Some(counterparty)) =
+        (&agreement.status, &agreement.counterparty)
+    {
+        DEPOSITS.save(
+            storage,
+            (agreement.id, counterparty),
+            &vec![agreement.counterparty_token.clone()],
+        )?;
+    }
+    Ok(())
+}
+
+/// Returns the latest counter-offer of an agreement if it still awaits the other party.
+fn pending_counter_offer(storage: &dyn Storage, id: u64) -> StdResult<Option<(u32, CounterOffer)>> {
+    let latest = COUNTER_OFFERS
+        .prefix(id)
+        .range(storage, None, None, Order::Descending)
+        .next()
+        .transpose()?;
+    Ok(latest.filter(|(_, offer)| offer.status == CounterOfferStatus::Pending))
+}
+
+/// Reduces the initiator's deposit of an initiated agreement to the required initiator token,
+/// returning the messages that refund the rest to the initiator.
+fn refund_initiator_surplus(
+    storage: &mut dyn Storage,
+    agreement: &Agreement,
+    required: &Asset,
+) -> Result<Vec<CosmosMsg>, ContractError> {
+    let escrowed = load_initiator_deposit(storage, agreement)?;
+    let mut messages = Vec::new();
+    let mut kept = Vec::new();
+    for asset in escrowed {
+        let surplus = asset
+            .amount()
+            .saturating_sub(required.covered_amount(&asset));
+        if !surplus.is_zero() {
+            messages.extend(
+                asset
+                    .with_amount(surplus)
+                    .transfer_msgs(&agreement.initiator)?,
+            );
+        }
+        if surplus < asset.amount() {
+            kept.push(asset.with_amount(asset.amount() - surplus));
+        }
+    }
+    DEPOSITS.save(storage, (agreement.id, &agreement.initiator), &kept)?;
+    Ok(messages)
+}
+
+/// Returns the assets the escrow ledger holds for the initiator of an agreement.
+fn load_initiator_deposit(storage: &dyn Storage, agreement: &Agreement) -> StdResult<Vec<Asset>> {
+    Ok(DEPOSITS
+        .may_load(storage, (agreement.id, &agreement.initiator))?
+        .unwrap_or_default())
+}
+
+/// Removes every deposit but the initiator's from the escrow ledger of an initiated agreement
+/// whose counterparty terms change, returning the messages refunding them to their depositors.
+fn refund_counterparty_deposits(
+    storage: &mut dyn Storage,
+    agreement: &Agreement,
+) -> Result<Vec<CosmosMsg>, ContractError> {
+    let mut messages = Vec::new();
+    for (depositor, assets) in load_deposits(storage, agreement.id)? {
+        if depositor != agreement.initiator {
+            DEPOSITS.remove(storage, (agreement.id, &depositor));
+            messages.extend(transfer_assets_msgs(&assets, &depositor)?);
+        }
+    }
+    Ok(messages)
+}
+
+/// Deducts an asset from the withdrawable balance of its owner.
+fn debit_balance(
+    storage: &mut dyn Storage,
+    owner: &Addr,
+    asset: &Asset,
+) -> Result<(), ContractError> {
+    let token = asset.token();
+    let balance = BALANCES
+        .may_load(storage, (owner, &token))?
+        .filter(|balance| balance.is_same_token(asset) && balance.amount() >= asset.amount())
+        .ok_or(ContractError::InsufficientBalance {
+            asset: asset.to_string(),
+        })?;
+    let remaining = balance.amount() - asset.amount();
+    if remaining.is_zero() {
+        BALANCES.remove(storage, (owner, &token));
+    } else {
+        BALANCES.save(storage, (owner, &token), &balance.with_amount(remaining))?;
+    }
+    Ok(())
+}
+
+/// Adds a deposit to the escrow ledger of an agreement under the address that made it.
+fn record_deposit(
+    storage: &mut dyn Storage,
+    id: u64,
+    depositor: &Addr,
+    deposit: &Deposit,
+) -> StdResult<()> {
+    let mut assets = DEPOSITS
+        .may_load(storage, (id, depositor))?
+        .unwrap_or_default();
+    for asset in deposit.to_assets() {
+        if !assets.iter_mut().any(|escrowed| escrowed.merge(&asset)) {
+            assets.push(asset);
+        }
+    }
+    DEPOSITS.save(storage, (id, depositor), &assets)
+}
+
+/// Deducts a released asset from a depositor's entry in the escrow ledger of an agreement.
+fn release_deposit(
+    storage: &mut dyn Storage,
+    id: u64,
+    depositor: &Addr,
+    released: &Asset,
+) -> StdResult<()> {
+    let mut assets = DEPOSITS
+        .may_load(storage, (id, depositor))?
+        .unwrap_or_default();
+    for asset in assets.iter_mut() {
+        if asset.is_same_token(released) {
+            *asset = asset.with_amount(asset.amount().checked_sub(released.amount())?);
+        }
+    }
+    assets.retain(|asset| !asset.amount().is_zero());
+    DEPOSITS.save(storage, (id, depositor), &assets)
+}
+
+/// Returns the escrow ledger of an agreement, grouped by depositor.
+fn load_deposits(storage: &dyn Storage, id: u64) -> StdResult<Vec<(Addr, Vec<Asset>)>> {
+    DEPOSITS
+        .prefix(id)
+        .range(storage, None, None, Order::Ascending)
+        .collect()
+}
+
+/// Removes and returns the escrow ledger of an agreement, grouped by depositor.
+fn take_deposits(storage: &mut dyn Storage, id: u64) -> StdResult<Vec<(Addr, Vec<Asset>)>> {
+    let deposits = load_deposits(storage, id)?;
+    for (depositor, _) in &deposits {
+        DEPOSITS.remove(storage, (id, depositor));
+    }
+    Ok(deposits)
+}
+
+/// Removes and returns the escrow ledger of a multi-party agreement, grouped by participant.
+fn take_multi_party_deposits(
+    storage: &mut dyn Storage,
+    id: u64,
+) -> StdResult<Vec<(Addr, Vec<Asset>)>> {
+    let deposits = MULTI_PARTY_DEPOSITS
+        .prefix(id)
+        .range(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for (depositor, _) in &deposits {
+        MULTI_PARTY_DEPOSITS.remove(storage, (id, depositor));
+    }
+    Ok(deposits)
+}
+
+fn deposit_to_string(deposit: &Deposit) -> String {
+    match deposit {
+        Deposit::Native(funds) => funds
+            .iter()
+            .map(|coin| coin.to_string())
+            .collect::<Vec<_>>()
+            .join(","),
+        Deposit::Cw20 { contract, amount } => format!("cw20:{}:{}", contract, amount),
+        Deposit::Cw721 { contract, token_id } => format!("cw721:{}:{}", contract, token_id),
+    }
+}
+
+/// Handles incoming queries to retrieve agreement information.
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
         QueryMsg::GetAgreement { id } => to_json_binary(&query_agreement(deps, id)?),
         QueryMsg::GetTotalAgreementCount {} => to_json_binary(&query_total_agreement_count(deps)?),
         QueryMsg::GetInitiatedAgreementCount {} => {
@@ -310,50 +2790,80 @@ pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
         QueryMsg::GetCanceledAgreementCount {} => {
             to_json_binary(&query_canceled_agreement_count(deps)?)
         }
+        QueryMsg::GetExpiredAgreementCount {} => {
+            to_json_binary(&query_expired_agreement_count(deps)?)
+        }
+        QueryMsg::GetDisputedAgreementCount {} => {
+            to_json_binary(&query_disputed_agreement_count(deps)?)
+        }
+        QueryMsg::GetResolvedAgreementCount {} => {
+            to_json_binary(&query_resolved_agreement_count(deps)?)
+        }
+        QueryMsg::GetRejectedAgreementCount {} => {
+            to_json_binary(&query_rejected_agreement_count(deps)?)
+        }
         QueryMsg::GetAgreementsByInitiator {
             initiator,
-            page,
-            page_size,
-        } => {
-            let start_after = page.checked_mul(page_size).unwrap_or(0);
-            let end_before = start_after.checked_add(page_size).unwrap_or(u64::MAX);
-
-            to_json_binary(&query_agreements_by_initiator(
-                deps,
-                initiator,
-                start_after,
-                end_before,
-            )?)
-        }
+            start_after,
+            limit,
+        } => to_json_binary(&query_agreements_by_initiator(
+            deps,
+            initiator,
+            start_after,
+            limit,
+        )?),
         QueryMsg::GetAgreementsByCounterparty {
             counterparty,
-            page,
-            page_size,
-        } => {
-            let start_after = page.checked_mul(page_size).unwrap_or(0);
-            let end_before = start_after.checked_add(page_size).unwrap_or(u64::MAX);
-
-            to_json_binary(&query_agreements_by_counterparty(
-                deps,
-                counterparty,
-                start_after,
-                end_before,
-            )?)
-        }
+            start_after,
+            limit,
+        } => to_json_binary(&query_agreements_by_counterparty(
+            deps,
+            counterparty,
+            start_after,
+            limit,
+        )?),
         QueryMsg::GetAgreementsByStatus {
             status,
-            page,
-            page_size,
-        } => {
-            let start_after = page.checked_mul(page_size).unwrap_or(0);
-            let end_before = start_after.checked_add(page_size).unwrap_or(u64::MAX);
-
-            to_json_binary(&query_agreements_by_status(
-                deps,
-                status,
-                start_after,
-                end_before,
-            )?)
+            start_after,
+            limit,
+        } => to_json_binary(&query_agreements_by_status(
+            deps,
+            status,
+            start_after,
+            limit,
+        )?),
+        QueryMsg::GetOpenOffers { start_after, limit } => {
+            to_json_binary(&query_open_offers(deps, start_after, limit)?)
+        }
+        QueryMsg::GetMilestones { id } => to_json_binary(&query_milestones(deps, id)?),
+        QueryMsg::GetCancelRequest { id } => to_json_binary(&query_cancel_request(deps, id)?),
+        QueryMsg::GetAgreementDeposits { id } => {
+            to_json_binary(&query_agreement_deposits(deps, id)?)
+        }
+        QueryMsg::GetMultiPartyAgreement { id } => {
+            to_json_binary(&query_multi_party_agreement(deps, id)?)
+        }
+        QueryMsg::GetFills {
+            id,
+            start_after,
+            limit,
+        } => to_json_binary(&query_fills(deps, id, start_after, limit)?),
+        QueryMsg::GetOwnership {} => to_json_binary(&query_ownership(deps)?),
+        QueryMsg::GetPaused {} => to_json_binary(&query_paused(deps)?),
+        QueryMsg::GetAssetRule { token } => to_json_binary(&query_asset_rule(deps, token)?),
+        QueryMsg::GetAssetRules { start_after, limit } => {
+            to_json_binary(&query_asset_rules(deps, start_after, limit)?)
+        }
+        QueryMsg::GetConfig {} => to_json_binary(&query_config(deps)?),
+        QueryMsg::GetAccruedFees { denom } => to_json_binary(&query_accrued_fees(deps, denom)?),
+        QueryMsg::GetCounterOffers {
+            id,
+            start_after,
+            limit,
+        } => to_json_binary(&query_counter_offers(deps, id, start_after, limit)?),
+        QueryMsg::GetBalances { owner } => to_json_binary(&query_balances(deps, owner)?),
+        QueryMsg::GetSignedOffer { offer } => {
+            to_json_binary(&query_signed_offer(deps, env, offer)?)
         }
     }
 }
@@ -29,4 +29,124 @@ pub enum ContractError {
         initiator: String,
         counterparty: String,
     },
+
+    #[error("Invalid asset `{asset}`: {reason}")]
+    InvalidAsset { asset: String, reason: String },
+
+    #[error("Unsupported asset: `{asset}` cannot be escrowed")]
+    UnsupportedAsset { asset: String },
+
+    #[error("Incomplete deposit: `{asset}` has not been fully escrowed")]
+    IncompleteDeposit { asset: String },
+
+    #[error("Invalid expiration: `{expires}` has already passed")]
+    InvalidExpiration { expires: String },
+
+    #[error("Agreement `{id}` has expired")]
+    AgreementExpired { id: u64 },
+
+    #[error("Agreement `{id}` has not expired yet")]
+    AgreementNotExpired { id: u64 },
+
+    #[error("Invalid arbiter: {reason}")]
+    InvalidArbiter { reason: String },
+
+    #[error("Agreement `{id}` has no arbiter")]
+    NoArbiter { id: u64 },
+
+    #[error("Invalid payout: expected `{expected}`, but found `{found}`")]
+    InvalidPayout { expected: String, found: String },
+
+    #[error("Invalid milestones: {reason}")]
+    InvalidMilestones { reason: String },
+
+    #[error("Invalid milestone `{index}` of agreement `{id}`: next milestone is `{next}`")]
+    InvalidMilestone { id: u64, index: u32, next: String },
+
+    #[error("Agreement `{id}` is executed by approving its milestones")]
+    MilestoneAgreement { id: u64 },
+
+    #[error("Agreement `{id}` has released milestones and can no longer be canceled")]
+    MilestoneReleased { id: u64 },
+
+    #[error("Unknown agreement status `{status}`")]
+    UnknownAgreementStatus { status: String },
+
+    #[error("Invalid migration: {reason}")]
+    InvalidMigration { reason: String },
+
+    #[error("Agreement `{id}` is an open offer without a counterparty")]
+    OpenOffer { id: u64 },
+
+    #[error("Invalid fill of agreement `{id}`: {reason}")]
+    InvalidFill { id: u64, reason: String },
+
+    #[error("Invalid config: {reason}")]
+    InvalidConfig { reason: String },
+
+    #[error("Contract has no owner")]
+    NoOwner {},
+
+    #[error("No ownership transfer is pending")]
+    NoPendingOwner {},
+
+    #[error("Contract is paused: `{operation}` is disabled")]
+    Paused { operation: String },
+
+    #[error("Asset `{asset}` is not allowed: {reason}")]
+    AssetNotAllowed { asset: String, reason: String },
+
+    #[error("Invalid HTLC: {reason}")]
+    InvalidHtlc { reason: String },
+
+    #[error("Invalid preimage for the hashlock of agreement `{id}`")]
+    InvalidPreimage { id: u64 },
+
+    #[error("Agreement `{id}` is hash time locked and can only be executed or refunded")]
+    HtlcLocked { id: u64 },
+
+    #[error("Agreement `{id}` is not hash time locked")]
+    NotHtlc { id: u64 },
+
+    #[error("Invalid signature or public key of the signed offer")]
+    InvalidSignature {},
+
+    #[error("Signed offer has expired")]
+    SignedOfferExpired {},
+
+    #[error("Signed offer has already been accepted")]
+    SignedOfferUsed {},
+
+    #[error("Signed offer has been revoked")]
+    SignedOfferRevoked {},
+
+    #[error("Insufficient balance of `{asset}`")]
+    InsufficientBalance { asset: String },
+
+    #[error("Agreement `{id}` has no pending counter-offer")]
+    NoCounterOffer { id: u64 },
+
+    #[error("Agreement `{id}` has a pending counter-offer")]
+    CounterOfferPending { id: u64 },
+
+    #[error("Agreement `{id}` can only be canceled with the consent of both parties")]
+    MutualCancelRequired { id: u64 },
+
+    #[error("Agreement `{id}` can be canceled by either party alone")]
+    UnilateralCancel { id: u64 },
+
+    #[error("Agreement `{id}` has no pending cancel request")]
+    NoCancelRequest { id: u64 },
+
+    #[error("Invalid cancel penalty: {reason}")]
+    InvalidCancelPenalty { reason: String },
+
+    #[error("Invalid basket: {reason}")]
+    InvalidBasket { reason: String },
+
+    #[error("Agreement `{id}` swaps baskets of assets, its terms cannot be changed")]
+    BasketAgreement { id: u64 },
+
+    #[error("Invalid participants: {reason}")]
+    InvalidParticipants { reason: String },
 }
@@ -1,21 +1,27 @@
 #[cfg(test)]
-use crate::contract::STATUS_INITIATED;
+use crate::asset::Asset;
 #[cfg(test)]
 use crate::contract::{execute, instantiate, query};
 #[cfg(test)]
 use crate::msg::{
     AcceptedAgreementCountResponse, CanceledAgreementCountResponse, ExecuteMsg,
     ExecutedAgreementCountResponse, InitiatedAgreementCountResponse, InstantiateMsg, QueryMsg,
-    TokenInfo, TotalAgreementCountResponse,
+    TotalAgreementCountResponse,
 };
 #[cfg(test)]
+use crate::state::{AgreementStatus, CancelPenalty, CancelPolicy, Htlc};
+#[cfg(test)]
+use crate::ContractError;
+#[cfg(test)]
 use cosmwasm_std::testing::{
     message_info, mock_dependencies_with_balances, mock_env, MockApi, MockQuerier, MockStorage,
 };
 #[cfg(test)]
 use cosmwasm_std::Addr;
 #[cfg(test)]
-use cosmwasm_std::{coin, coins, from_json, Attribute, OwnedDeps};
+use cosmwasm_std::{coin, coins, from_json, Attribute, Coin, OwnedDeps, Response, Uint128};
+#[cfg(test)]
+use cw_utils::Expiration;
 #[cfg(test)]
 use schemars::JsonSchema;
 #[cfg(test)]
@@ -25,29 +31,6 @@ use serde::{Deserialize, Serialize};
 #[cfg(test)]
 pub struct CwTemplateContract(pub Addr);
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::msg::ExecuteMsg;
-    use cosmwasm_std::{to_json_binary, Coin, CosmosMsg, StdResult, WasmMsg};
-
-    impl CwTemplateContract {
-        pub fn addr(&self) -> Addr {
-            self.0.clone()
-        }
-
-        pub fn call<T: Into<ExecuteMsg>>(&self, msg: T, funds: Vec<Coin>) -> StdResult<CosmosMsg> {
-            let msg = to_json_binary(&msg.into())?;
-            Ok(WasmMsg::Execute {
-                contract_addr: self.addr().into(),
-                msg,
-                funds,
-            }
-            .into())
-        }
-    }
-}
-
 // Helper function to check agreement counts
 #[cfg(test)]
 pub fn check_agreement_counts(
@@ -90,26 +73,102 @@ pub fn check_agreement_counts(
 pub fn initialize_contract() -> OwnedDeps<MockStorage, MockApi, MockQuerier> {
     let mut deps = mock_dependencies_with_balances(&[
         (
-            (&Addr::unchecked("initiator")).as_ref(),
+            Addr::unchecked("initiator").as_ref(),
             &[coin(1000, "tokenA")],
         ),
         (
-            (&Addr::unchecked("counterparty")).as_ref(),
+            Addr::unchecked("counterparty").as_ref(),
             &[coin(2000, "tokenB")],
         ),
         (
-            (&Addr::unchecked("cosmos2contract")).as_ref(),
+            mock_env().contract.address.as_str(),
             &[coin(1000, "tokenA"), coin(2000, "tokenB")],
         ),
     ]);
 
-    let msg = InstantiateMsg {};
+    let msg = InstantiateMsg::default();
     let info = message_info(&Addr::unchecked("creator"), &[]);
     let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
     assert_eq!(0, _res.messages.len());
     deps
 }
 
+// Fields of an `ExecuteMsg::InitiateAgreement`, defaulting to 1000 tokenA offered to
+// "counterparty" for 2000 tokenB, tests override the fields they exercise
+#[derive(Clone, Debug)]
+#[cfg(test)]
+pub struct InitiateAgreement {
+    pub initiator_token: Asset,
+    pub counterparty_token: Asset,
+    pub counterparty: Option<Addr>,
+    pub expires: Option<Expiration>,
+    pub arbiter: Option<Addr>,
+    pub arbiter_fee_bps: Option<u16>,
+    pub milestones: Option<Vec<Uint128>>,
+    pub htlc: Option<Htlc>,
+    pub cancel_policy: Option<CancelPolicy>,
+    pub cancel_penalty: Option<CancelPenalty>,
+    pub initiator_assets: Option<Vec<Asset>>,
+    pub counterparty_assets: Option<Vec<Asset>>,
+}
+
+#[cfg(test)]
+impl Default for InitiateAgreement {
+    fn default() -> Self {
+        InitiateAgreement {
+            initiator_token: Asset::native("tokenA", 1000u128),
+            counterparty_token: Asset::native("tokenB", 2000u128),
+            counterparty: Some(addr("counterparty")),
+            expires: None,
+            arbiter: None,
+            arbiter_fee_bps: None,
+            milestones: None,
+            htlc: None,
+            cancel_policy: None,
+            cancel_penalty: None,
+            initiator_assets: None,
+            counterparty_assets: None,
+        }
+    }
+}
+
+#[cfg(test)]
+impl From<InitiateAgreement> for ExecuteMsg {
+    fn from(msg: InitiateAgreement) -> Self {
+        ExecuteMsg::InitiateAgreement {
+            initiator_token: msg.initiator_token,
+            counterparty_token: msg.counterparty_token,
+            counterparty: msg.counterparty,
+            expires: msg.expires,
+            arbiter: msg.arbiter,
+            arbiter_fee_bps: msg.arbiter_fee_bps,
+            milestones: msg.milestones,
+            htlc: msg.htlc,
+            cancel_policy: msg.cancel_policy,
+            cancel_penalty: msg.cancel_penalty,
+            initiator_assets: msg.initiator_assets,
+            counterparty_assets: msg.counterparty_assets,
+        }
+    }
+}
+
+// Helper function to initiate an agreement as "initiator" with the given funds
+#[cfg(test)]
+pub fn initiate(
+    deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>,
+    msg: InitiateAgreement,
+    funds: &[Coin],
+) -> Result<Response, ContractError> {
+    let info = message_info(&Addr::unchecked("initiator"), funds);
+    execute(deps.as_mut(), mock_env(), info, msg.into())
+}
+
+// Helper function to make the valid address of a named test account
+#[cfg(test)]
+pub fn addr(name: &str) -> Addr {
+    MockApi::default().addr_make(name)
+}
+
 // Helper function to initiate an agreement
 #[cfg(test)]
 pub fn initiate_new_agreement(
@@ -118,21 +177,24 @@ pub fn initiate_new_agreement(
     initiator_amount: u128,
     counterparty: &str,
     counterparty_amount: u128,
-) -> (TokenInfo, TokenInfo, Addr) {
-    let initiator_token = TokenInfo {
-        address: Addr::unchecked("tokenA"),
-        amount: initiator_amount,
-    };
-    let counterparty_token = TokenInfo {
-        address: Addr::unchecked("tokenB"),
-        amount: counterparty_amount,
-    };
-    let counterparty_addr = Addr::unchecked(counterparty);
+) -> (Asset, Asset, Addr) {
+    let initiator_token = Asset::native("tokenA", initiator_amount);
+    let counterparty_token = Asset::native("tokenB", counterparty_amount);
+    let counterparty_addr = addr(counterparty);
 
     let msg = ExecuteMsg::InitiateAgreement {
         initiator_token: initiator_token.clone(),
         counterparty_token: counterparty_token.clone(),
-        counterparty: counterparty_addr.clone(),
+        counterparty: Some(counterparty_addr.clone()),
+        expires: None,
+        arbiter: None,
+        arbiter_fee_bps: None,
+        milestones: None,
+        htlc: None,
+        cancel_policy: None,
+        cancel_penalty: None,
+        initiator_assets: None,
+        counterparty_assets: None,
     };
     let info = message_info(
         &Addr::unchecked(initiator),
@@ -153,7 +215,7 @@ pub fn initiate_new_agreement(
             },
             Attribute {
                 key: "status".to_string(),
-                value: STATUS_INITIATED.to_string()
+                value: AgreementStatus::Initiated.to_string()
             },
             Attribute {
                 key: "initiator".to_string(),
@@ -161,21 +223,44 @@ pub fn initiate_new_agreement(
             },
             Attribute {
                 key: "counterparty".to_string(),
-                value: counterparty.to_string()
+                value: counterparty_addr.to_string()
             },
             Attribute {
                 key: "initiator_token".to_string(),
-                value: initiator_token.clone().into_string()
+                value: initiator_token.to_string()
             },
             Attribute {
                 key: "counterparty_token".to_string(),
-                value: counterparty_token.clone().into_string()
+                value: counterparty_token.to_string()
             }
         ]
     );
 
     // Check agreement status counts
-    check_agreement_counts(&deps, 1, 1, 0, 0, 0);
+    check_agreement_counts(deps, 1, 1, 0, 0, 0);
 
     (initiator_token, counterparty_token, counterparty_addr)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::msg::ExecuteMsg;
+    use cosmwasm_std::{to_json_binary, Coin, CosmosMsg, StdResult, WasmMsg};
+
+    impl CwTemplateContract {
+        pub fn addr(&self) -> Addr {
+            self.0.clone()
+        }
+
+        pub fn call<T: Into<ExecuteMsg>>(&self, msg: T, funds: Vec<Coin>) -> StdResult<CosmosMsg> {
+            let msg = to_json_binary(&msg.into())?;
+            Ok(WasmMsg::Execute {
+                contract_addr: self.addr().into(),
+                msg,
+                funds,
+            }
+            .into())
+        }
+    }
+}
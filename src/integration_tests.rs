@@ -1,5 +1,6 @@
 #[cfg(test)]
 mod tests {
+    use crate::asset::Asset;
     use crate::helpers::CwTemplateContract;
     use crate::msg::InstantiateMsg;
     use cosmwasm_std::{Addr, Empty};
@@ -10,6 +11,25 @@ mod tests {
             crate::contract::execute,
             crate::contract::instantiate,
             crate::contract::query,
+        )
+        .with_migrate(crate::contract::migrate);
+        Box::new(contract)
+    }
+
+    pub fn contract_cw20() -> Box<dyn Contract<Empty>> {
+        let contract = ContractWrapper::new(
+            cw20_base::contract::execute,
+            cw20_base::contract::instantiate,
+            cw20_base::contract::query,
+        );
+        Box::new(contract)
+    }
+
+    pub fn contract_cw721() -> Box<dyn Contract<Empty>> {
+        let contract = ContractWrapper::new(
+            cw721_base::entry::execute,
+            cw721_base::entry::instantiate,
+            cw721_base::entry::query,
         );
         Box::new(contract)
     }
@@ -19,12 +39,13 @@ mod tests {
     const ADMIN: &str = "admin";
     const TOKEN_A: &str = "TokenA";
     const TOKEN_B: &str = "TokenB";
+    const TOKEN_C: &str = "TokenC";
 
     fn proper_instantiate() -> (App, CwTemplateContract) {
         let mut app = App::default();
         let cw_template_id = app.store_code(contract_template());
 
-        let msg = InstantiateMsg {};
+        let msg = InstantiateMsg::default();
         let cw_template_contract_addr = app
             .instantiate_contract(
                 cw_template_id,
@@ -43,8 +64,10 @@ mod tests {
 
     mod agreement_tests {
         use super::*;
-        use crate::msg::{ExecuteMsg, TokenInfo};
-        use cosmwasm_std::coins;
+        use crate::msg::{DisputePayout, ExecuteMsg};
+        use crate::state::Participant;
+        use cosmwasm_std::{coins, Coin};
+        use cw_utils::Expiration;
 
         #[test]
         fn initiate_agreement() {
@@ -69,15 +92,18 @@ mod tests {
             });
 
             let msg = ExecuteMsg::InitiateAgreement {
-                initiator_token: TokenInfo {
-                    address: Addr::unchecked(TOKEN_A),
-                    amount: 1000u128,
-                },
-                counterparty_token: TokenInfo {
-                    address: Addr::unchecked(TOKEN_B),
-                    amount: 2000u128,
-                },
-                counterparty: counterparty_addr.clone(),
+                initiator_token: Asset::native(TOKEN_A, 1000u128),
+                counterparty_token: Asset::native(TOKEN_B, 2000u128),
+                counterparty: Some(counterparty_addr.clone()),
+                expires: None,
+                arbiter: None,
+                arbiter_fee_bps: None,
+                milestones: None,
+                htlc: None,
+                cancel_policy: None,
+                cancel_penalty: None,
+                initiator_assets: None,
+                counterparty_assets: None,
             };
 
             let cosmos_msg = cw_template_contract
@@ -126,15 +152,18 @@ mod tests {
             });
 
             let initiate_msg = ExecuteMsg::InitiateAgreement {
-                initiator_token: TokenInfo {
-                    address: Addr::unchecked(TOKEN_A),
-                    amount: 1000u128,
-                },
-                counterparty_token: TokenInfo {
-                    address: Addr::unchecked(TOKEN_B),
-                    amount: 2000u128,
-                },
-                counterparty: counterparty_addr.clone(),
+                initiator_token: Asset::native(TOKEN_A, 1000u128),
+                counterparty_token: Asset::native(TOKEN_B, 2000u128),
+                counterparty: Some(counterparty_addr.clone()),
+                expires: None,
+                arbiter: None,
+                arbiter_fee_bps: None,
+                milestones: None,
+                htlc: None,
+                cancel_policy: None,
+                cancel_penalty: None,
+                initiator_assets: None,
+                counterparty_assets: None,
             };
 
             let cosmos_msg = cw_template_contract
@@ -207,15 +236,18 @@ mod tests {
             });
 
             let initiate_msg = ExecuteMsg::InitiateAgreement {
-                initiator_token: TokenInfo {
-                    address: Addr::unchecked(TOKEN_A),
-                    amount: 1000u128,
-                },
-                counterparty_token: TokenInfo {
-                    address: Addr::unchecked(TOKEN_B),
-                    amount: 2000u128,
-                },
-                counterparty: counterparty_addr.clone(),
+                initiator_token: Asset::native(TOKEN_A, 1000u128),
+                counterparty_token: Asset::native(TOKEN_B, 2000u128),
+                counterparty: Some(counterparty_addr.clone()),
+                expires: None,
+                arbiter: None,
+                arbiter_fee_bps: None,
+                milestones: None,
+                htlc: None,
+                cancel_policy: None,
+                cancel_penalty: None,
+                initiator_assets: None,
+                counterparty_assets: None,
             };
 
             let cosmos_msg = cw_template_contract
@@ -232,7 +264,10 @@ mod tests {
 
             app.execute(counterparty_addr.clone(), cosmos_msg).unwrap();
 
-            let execute_msg = ExecuteMsg::ExecuteAgreement { id: 1 };
+            let execute_msg = ExecuteMsg::ExecuteAgreement {
+                id: 1,
+                preimage: None,
+            };
 
             let cosmos_msg = cw_template_contract.call(execute_msg, vec![]).unwrap();
 
@@ -287,6 +322,92 @@ mod tests {
             assert_eq!(contract_token_b_balance, 0);
         }
 
+        #[test]
+        fn execute_agreement_with_fees() {
+            let mut app = App::default();
+            let cw_template_id = app.store_code(contract_template());
+
+            let initiator_addr = app.api().addr_make(INITIATOR);
+            let counterparty_addr = app.api().addr_make(COUNTERPARTY);
+            let fee_recipient_addr = app.api().addr_make("fee_recipient");
+
+            app.init_modules(|router, _, storage| {
+                router
+                    .bank
+                    .init_balance(storage, &initiator_addr, coins(10000, TOKEN_A))
+                    .unwrap();
+                router
+                    .bank
+                    .init_balance(storage, &counterparty_addr, coins(10000, TOKEN_B))
+                    .unwrap();
+            });
+
+            let msg = InstantiateMsg {
+                owner: None,
+                fee_recipient: Some(fee_recipient_addr.clone()),
+                initiator_fee_bps: 200,
+                counterparty_fee_bps: 100,
+                guardian: None,
+            };
+            let contract_addr = app
+                .instantiate_contract(
+                    cw_template_id,
+                    app.api().addr_make(ADMIN),
+                    &msg,
+                    &[],
+                    "test",
+                    None,
+                )
+                .unwrap();
+            let cw_template_contract = CwTemplateContract(contract_addr);
+
+            let initiate_msg = ExecuteMsg::InitiateAgreement {
+                initiator_token: Asset::native(TOKEN_A, 1000u128),
+                counterparty_token: Asset::native(TOKEN_B, 2000u128),
+                counterparty: Some(counterparty_addr.clone()),
+                expires: None,
+                arbiter: None,
+                arbiter_fee_bps: None,
+                milestones: None,
+                htlc: None,
+                cancel_policy: None,
+                cancel_penalty: None,
+                initiator_assets: None,
+                counterparty_assets: None,
+            };
+            let cosmos_msg = cw_template_contract
+                .call(initiate_msg, coins(1000, TOKEN_A))
+                .unwrap();
+            app.execute(initiator_addr.clone(), cosmos_msg).unwrap();
+
+            let accept_msg = ExecuteMsg::AcceptAgreement { id: 1 };
+            let cosmos_msg = cw_template_contract
+                .call(accept_msg, coins(2000, TOKEN_B))
+                .unwrap();
+            app.execute(counterparty_addr.clone(), cosmos_msg).unwrap();
+
+            let execute_msg = ExecuteMsg::ExecuteAgreement {
+                id: 1,
+                preimage: None,
+            };
+            let cosmos_msg = cw_template_contract.call(execute_msg, vec![]).unwrap();
+            app.execute(counterparty_addr.clone(), cosmos_msg).unwrap();
+
+            let balance = |address: &Addr, denom: &str| {
+                app.wrap()
+                    .query_balance(address, denom)
+                    .unwrap()
+                    .amount
+                    .u128()
+            };
+            assert_eq!(balance(&initiator_addr, TOKEN_B), 1980);
+            assert_eq!(balance(&counterparty_addr, TOKEN_A), 980);
+            assert_eq!(balance(&fee_recipient_addr, TOKEN_A), 20);
+            assert_eq!(balance(&fee_recipient_addr, TOKEN_B), 20);
+            assert_eq!(balance(&cw_template_contract.addr(), TOKEN_A), 0);
+            assert_eq!(balance(&cw_template_contract.addr(), TOKEN_B), 0);
+        }
+
         #[test]
         fn cancel_accepted_agreement() {
             let (mut app, cw_template_contract) = proper_instantiate();
@@ -310,15 +431,18 @@ mod tests {
             });
 
             let initiate_msg = ExecuteMsg::InitiateAgreement {
-                initiator_token: TokenInfo {
-                    address: Addr::unchecked(TOKEN_A),
-                    amount: 1000u128,
-                },
-                counterparty_token: TokenInfo {
-                    address: Addr::unchecked(TOKEN_B),
-                    amount: 2000u128,
-                },
-                counterparty: counterparty_addr.clone(),
+                initiator_token: Asset::native(TOKEN_A, 1000u128),
+                counterparty_token: Asset::native(TOKEN_B, 2000u128),
+                counterparty: Some(counterparty_addr.clone()),
+                expires: None,
+                arbiter: None,
+                arbiter_fee_bps: None,
+                milestones: None,
+                htlc: None,
+                cancel_policy: None,
+                cancel_penalty: None,
+                initiator_assets: None,
+                counterparty_assets: None,
             };
 
             let cosmos_msg = cw_template_contract
@@ -397,15 +521,18 @@ mod tests {
             });
 
             let initiate_msg = ExecuteMsg::InitiateAgreement {
-                initiator_token: TokenInfo {
-                    address: Addr::unchecked(TOKEN_A),
-                    amount: 1000u128,
-                },
-                counterparty_token: TokenInfo {
-                    address: Addr::unchecked(TOKEN_B),
-                    amount: 2000u128,
-                },
-                counterparty: counterparty_addr.clone(),
+                initiator_token: Asset::native(TOKEN_A, 1000u128),
+                counterparty_token: Asset::native(TOKEN_B, 2000u128),
+                counterparty: Some(counterparty_addr.clone()),
+                expires: None,
+                arbiter: None,
+                arbiter_fee_bps: None,
+                milestones: None,
+                htlc: None,
+                cancel_policy: None,
+                cancel_penalty: None,
+                initiator_assets: None,
+                counterparty_assets: None,
             };
 
             let cosmos_msg = cw_template_contract
@@ -452,5 +579,859 @@ mod tests {
                 .u128();
             assert_eq!(contract_token_b_balance, 0);
         }
+
+        #[test]
+        fn claim_expired_agreement() {
+            let (mut app, cw_template_contract) = proper_instantiate();
+
+            let initiator_addr = app.api().addr_make(INITIATOR);
+
+            app.init_modules(|router, _, storage| {
+                router
+                    .bank
+                    .init_balance(storage, &initiator_addr.clone(), coins(10000, TOKEN_A))
+                    .unwrap();
+            });
+
+            let counterparty_addr = app.api().addr_make(COUNTERPARTY);
+
+            let expires = app.block_info().time.plus_seconds(3600);
+            let initiate_msg = ExecuteMsg::InitiateAgreement {
+                initiator_token: Asset::native(TOKEN_A, 1000u128),
+                counterparty_token: Asset::native(TOKEN_B, 2000u128),
+                counterparty: Some(counterparty_addr.clone()),
+                expires: Some(Expiration::AtTime(expires)),
+                arbiter: None,
+                arbiter_fee_bps: None,
+                milestones: None,
+                htlc: None,
+                cancel_policy: None,
+                cancel_penalty: None,
+                initiator_assets: None,
+                counterparty_assets: None,
+            };
+
+            let cosmos_msg = cw_template_contract
+                .call(initiate_msg, coins(1000, TOKEN_A))
+                .unwrap();
+
+            app.execute(initiator_addr.clone(), cosmos_msg).unwrap();
+
+            // Nobody can claim the deposits before the agreement expires
+            let claim_msg = ExecuteMsg::ClaimExpired { id: 1 };
+            let cosmos_msg = cw_template_contract.call(claim_msg, vec![]).unwrap();
+            let anyone = app.api().addr_make("anyone");
+            app.execute(anyone.clone(), cosmos_msg.clone()).unwrap_err();
+
+            app.update_block(|block| block.time = expires);
+
+            app.execute(anyone, cosmos_msg).unwrap();
+
+            let initiator_token_a_balance = app
+                .wrap()
+                .query_balance(initiator_addr.clone(), TOKEN_A)
+                .unwrap()
+                .amount
+                .u128();
+            assert_eq!(initiator_token_a_balance, 10000);
+
+            let contract_token_a_balance = app
+                .wrap()
+                .query_balance(cw_template_contract.addr().clone(), TOKEN_A)
+                .unwrap()
+                .amount
+                .u128();
+            assert_eq!(contract_token_a_balance, 0);
+        }
+
+        #[test]
+        fn resolve_disputed_agreement() {
+            let (mut app, cw_template_contract) = proper_instantiate();
+
+            let initiator_addr = app.api().addr_make(INITIATOR);
+            let counterparty_addr = app.api().addr_make(COUNTERPARTY);
+            let arbiter_addr = app.api().addr_make("arbiter");
+
+            app.init_modules(|router, _, storage| {
+                router
+                    .bank
+                    .init_balance(storage, &initiator_addr.clone(), coins(10000, TOKEN_A))
+                    .unwrap();
+                router
+                    .bank
+                    .init_balance(storage, &counterparty_addr.clone(), coins(10000, TOKEN_B))
+                    .unwrap();
+            });
+
+            let initiate_msg = ExecuteMsg::InitiateAgreement {
+                initiator_token: Asset::native(TOKEN_A, 1000u128),
+                counterparty_token: Asset::native(TOKEN_B, 2000u128),
+                counterparty: Some(counterparty_addr.clone()),
+                expires: None,
+                arbiter: Some(arbiter_addr.clone()),
+                arbiter_fee_bps: Some(500),
+                milestones: None,
+                htlc: None,
+                cancel_policy: None,
+                cancel_penalty: None,
+                initiator_assets: None,
+                counterparty_assets: None,
+            };
+
+            let cosmos_msg = cw_template_contract
+                .call(initiate_msg, coins(1000, TOKEN_A))
+                .unwrap();
+
+            app.execute(initiator_addr.clone(), cosmos_msg).unwrap();
+
+            let accept_msg = ExecuteMsg::AcceptAgreement { id: 1 };
+
+            let cosmos_msg = cw_template_contract
+                .call(accept_msg, coins(2000, TOKEN_B))
+                .unwrap();
+
+            app.execute(counterparty_addr.clone(), cosmos_msg).unwrap();
+
+            let dispute_msg = ExecuteMsg::RaiseDispute {
+                id: 1,
+                reason: "goods not delivered".to_string(),
+            };
+
+            let cosmos_msg = cw_template_contract.call(dispute_msg, vec![]).unwrap();
+
+            app.execute(initiator_addr.clone(), cosmos_msg).unwrap();
+
+            // The arbiter refunds both parties after keeping a 5% fee
+            let resolve_msg = ExecuteMsg::ResolveDispute {
+                id: 1,
+                payout: DisputePayout {
+                    initiator: vec![Asset::native(TOKEN_A, 950u128)],
+                    counterparty: vec![Asset::native(TOKEN_B, 1900u128)],
+                },
+            };
+
+            let cosmos_msg = cw_template_contract.call(resolve_msg, vec![]).unwrap();
+
+            app.execute(arbiter_addr.clone(), cosmos_msg).unwrap();
+
+            let balance = |app: &App, addr: &Addr, denom: &str| {
+                app.wrap()
+                    .query_balance(addr.clone(), denom)
+                    .unwrap()
+                    .amount
+                    .u128()
+            };
+            assert_eq!(balance(&app, &initiator_addr, TOKEN_A), 9950);
+            assert_eq!(balance(&app, &counterparty_addr, TOKEN_B), 9900);
+            assert_eq!(balance(&app, &arbiter_addr, TOKEN_A), 50);
+            assert_eq!(balance(&app, &arbiter_addr, TOKEN_B), 100);
+            assert_eq!(balance(&app, &cw_template_contract.addr(), TOKEN_A), 0);
+            assert_eq!(balance(&app, &cw_template_contract.addr(), TOKEN_B), 0);
+        }
+
+        #[test]
+        fn execute_basket_agreement() {
+            let (mut app, cw_template_contract) = proper_instantiate();
+
+            let initiator_addr = app.api().addr_make(INITIATOR);
+            let counterparty_addr = app.api().addr_make(COUNTERPARTY);
+
+            app.init_modules(|router, _, storage| {
+                router
+                    .bank
+                    .init_balance(
+                        storage,
+                        &initiator_addr.clone(),
+                        vec![Coin::new(10000u128, TOKEN_A), Coin::new(10000u128, TOKEN_C)],
+                    )
+                    .unwrap();
+                router
+                    .bank
+                    .init_balance(storage, &counterparty_addr.clone(), coins(10000, TOKEN_B))
+                    .unwrap();
+            });
+
+            // The initiator swaps a basket of two denoms for the counterparty token
+            let initiate_msg = ExecuteMsg::InitiateAgreement {
+                initiator_token: Asset::native(TOKEN_A, 1000u128),
+                counterparty_token: Asset::native(TOKEN_B, 2000u128),
+                counterparty: Some(counterparty_addr.clone()),
+                expires: None,
+                arbiter: None,
+                arbiter_fee_bps: None,
+                milestones: None,
+                htlc: None,
+                cancel_policy: None,
+                cancel_penalty: None,
+                initiator_assets: Some(vec![Asset::native(TOKEN_C, 500u128)]),
+                counterparty_assets: None,
+            };
+
+            let cosmos_msg = cw_template_contract
+                .call(
+                    initiate_msg,
+                    vec![Coin::new(1000u128, TOKEN_A), Coin::new(500u128, TOKEN_C)],
+                )
+                .unwrap();
+
+            app.execute(initiator_addr.clone(), cosmos_msg).unwrap();
+
+            let accept_msg = ExecuteMsg::AcceptAgreement { id: 1 };
+
+            let cosmos_msg = cw_template_contract
+                .call(accept_msg, coins(2000, TOKEN_B))
+                .unwrap();
+
+            app.execute(counterparty_addr.clone(), cosmos_msg).unwrap();
+
+            let execute_msg = ExecuteMsg::ExecuteAgreement {
+                id: 1,
+                preimage: None,
+            };
+
+            let cosmos_msg = cw_template_contract.call(execute_msg, vec![]).unwrap();
+
+            app.execute(initiator_addr.clone(), cosmos_msg).unwrap();
+
+            let balance = |app: &App, addr: &Addr, denom: &str| {
+                app.wrap()
+                    .query_balance(addr.clone(), denom)
+                    .unwrap()
+                    .amount
+                    .u128()
+            };
+            assert_eq!(balance(&app, &initiator_addr, TOKEN_A), 9000);
+            assert_eq!(balance(&app, &initiator_addr, TOKEN_C), 9500);
+            assert_eq!(balance(&app, &initiator_addr, TOKEN_B), 2000);
+            assert_eq!(balance(&app, &counterparty_addr, TOKEN_A), 1000);
+            assert_eq!(balance(&app, &counterparty_addr, TOKEN_C), 500);
+            assert_eq!(balance(&app, &counterparty_addr, TOKEN_B), 8000);
+            assert_eq!(balance(&app, &cw_template_contract.addr(), TOKEN_A), 0);
+            assert_eq!(balance(&app, &cw_template_contract.addr(), TOKEN_C), 0);
+        }
+
+        #[test]
+        fn execute_multi_party_agreement() {
+            let (mut app, cw_template_contract) = proper_instantiate();
+
+            let buyer_addr = app.api().addr_make("buyer");
+            let seller_addr = app.api().addr_make("seller");
+            let broker_addr = app.api().addr_make("broker");
+
+            app.init_modules(|router, _, storage| {
+                router
+                    .bank
+                    .init_balance(storage, &buyer_addr.clone(), coins(10000, TOKEN_A))
+                    .unwrap();
+                router
+                    .bank
+                    .init_balance(storage, &seller_addr.clone(), coins(10000, TOKEN_B))
+                    .unwrap();
+            });
+
+            // The broker sets up the deal and keeps a commission of the buyer's payment
+            let initiate_msg = ExecuteMsg::InitiateMultiPartyAgreement {
+                participants: vec![
+                    Participant {
+                        address: buyer_addr.clone(),
+                        deposit: vec![Asset::native(TOKEN_A, 1000u128)],
+                        payout: vec![Asset::native(TOKEN_B, 2000u128)],
+                    },
+                    Participant {
+                        address: seller_addr.clone(),
+                        deposit: vec![Asset::native(TOKEN_B, 2000u128)],
+                        payout: vec![Asset::native(TOKEN_A, 900u128)],
+                    },
+                    Participant {
+                        address: broker_addr.clone(),
+                        deposit: vec![],
+                        payout: vec![Asset::native(TOKEN_A, 100u128)],
+                    },
+                ],
+            };
+
+            let cosmos_msg = cw_template_contract.call(initiate_msg, vec![]).unwrap();
+
+            app.execute(broker_addr.clone(), cosmos_msg).unwrap();
+
+            let deposit_msg = ExecuteMsg::DepositMultiParty { id: 1 };
+
+            let cosmos_msg = cw_template_contract
+                .call(deposit_msg.clone(), coins(1000, TOKEN_A))
+                .unwrap();
+
+            app.execute(buyer_addr.clone(), cosmos_msg).unwrap();
+
+            let cosmos_msg = cw_template_contract
+                .call(deposit_msg, coins(2000, TOKEN_B))
+                .unwrap();
+
+            app.execute(seller_addr.clone(), cosmos_msg).unwrap();
+
+            let execute_msg = ExecuteMsg::ExecuteMultiPartyAgreement { id: 1 };
+
+            let cosmos_msg = cw_template_contract.call(execute_msg, vec![]).unwrap();
+
+            app.execute(buyer_addr.clone(), cosmos_msg).unwrap();
+
+            let balance = |app: &App, addr: &Addr, denom: &str| {
+                app.wrap()
+                    .query_balance(addr.clone(), denom)
+                    .unwrap()
+                    .amount
+                    .u128()
+            };
+            assert_eq!(balance(&app, &buyer_addr, TOKEN_A), 9000);
+            assert_eq!(balance(&app, &buyer_addr, TOKEN_B), 2000);
+            assert_eq!(balance(&app, &seller_addr, TOKEN_A), 900);
+            assert_eq!(balance(&app, &seller_addr, TOKEN_B), 8000);
+            assert_eq!(balance(&app, &broker_addr, TOKEN_A), 100);
+            assert_eq!(balance(&app, &cw_template_contract.addr(), TOKEN_A), 0);
+            assert_eq!(balance(&app, &cw_template_contract.addr(), TOKEN_B), 0);
+        }
+    }
+
+    mod cw20_agreement_tests {
+        use super::*;
+        use crate::msg::{ExecuteMsg, ReceiveMsg};
+        use cosmwasm_std::{coins, to_json_binary, Uint128};
+        use cw20::{BalanceResponse, Cw20Coin, Cw20ExecuteMsg, Cw20QueryMsg};
+        use cw_multi_test::error::AnyResult;
+        use cw_multi_test::AppResponse;
+
+        fn instantiate_cw20(app: &mut App, symbol: &str, holder: &Addr, amount: u128) -> Addr {
+            let cw20_id = app.store_code(contract_cw20());
+
+            let msg = cw20_base::msg::InstantiateMsg {
+                name: format!("{} token", symbol),
+                symbol: symbol.to_string(),
+                decimals: 6,
+                initial_balances: vec![Cw20Coin {
+                    address: holder.to_string(),
+                    amount: Uint128::new(amount),
+                }],
+                mint: None,
+                marketing: None,
+            };
+
+            app.instantiate_contract(cw20_id, app.api().addr_make(ADMIN), &msg, &[], symbol, None)
+                .unwrap()
+        }
+
+        fn cw20_balance(app: &App, token: &Addr, address: &Addr) -> u128 {
+            let response: BalanceResponse = app
+                .wrap()
+                .query_wasm_smart(
+                    token,
+                    &Cw20QueryMsg::Balance {
+                        address: address.to_string(),
+                    },
+                )
+                .unwrap();
+            response.balance.u128()
+        }
+
+        fn send_cw20(
+            app: &mut App,
+            sender: &Addr,
+            token: &Addr,
+            contract: &Addr,
+            amount: u128,
+            msg: &ReceiveMsg,
+        ) -> AnyResult<AppResponse> {
+            app.execute_contract(
+                sender.clone(),
+                token.clone(),
+                &Cw20ExecuteMsg::Send {
+                    contract: contract.to_string(),
+                    amount: Uint128::new(amount),
+                    msg: to_json_binary(msg).unwrap(),
+                },
+                &[],
+            )
+        }
+
+        #[test]
+        fn cw20_for_native_agreement() {
+            let (mut app, cw_template_contract) = proper_instantiate();
+
+            let initiator_addr = app.api().addr_make(INITIATOR);
+            let counterparty_addr = app.api().addr_make(COUNTERPARTY);
+
+            let cw20_token = instantiate_cw20(&mut app, "CWA", &initiator_addr, 10000);
+
+            app.init_modules(|router, _, storage| {
+                router
+                    .bank
+                    .init_balance(storage, &counterparty_addr.clone(), coins(10000, TOKEN_B))
+                    .unwrap();
+            });
+
+            let initiate_msg = ReceiveMsg::InitiateAgreement {
+                initiator_token: Asset::cw20(cw20_token.clone(), 1000u128),
+                counterparty_token: Asset::native(TOKEN_B, 2000u128),
+                counterparty: Some(counterparty_addr.clone()),
+                expires: None,
+                arbiter: None,
+                arbiter_fee_bps: None,
+                milestones: None,
+                htlc: None,
+                cancel_policy: None,
+                cancel_penalty: None,
+                initiator_assets: None,
+                counterparty_assets: None,
+            };
+
+            send_cw20(
+                &mut app,
+                &initiator_addr,
+                &cw20_token,
+                &cw_template_contract.addr(),
+                1000,
+                &initiate_msg,
+            )
+            .unwrap();
+
+            assert_eq!(cw20_balance(&app, &cw20_token, &initiator_addr), 9000);
+            assert_eq!(
+                cw20_balance(&app, &cw20_token, &cw_template_contract.addr()),
+                1000
+            );
+
+            let accept_msg = ExecuteMsg::AcceptAgreement { id: 1 };
+
+            let cosmos_msg = cw_template_contract
+                .call(accept_msg, coins(2000, TOKEN_B))
+                .unwrap();
+
+            app.execute(counterparty_addr.clone(), cosmos_msg).unwrap();
+
+            let execute_msg = ExecuteMsg::ExecuteAgreement {
+                id: 1,
+                preimage: None,
+            };
+
+            let cosmos_msg = cw_template_contract.call(execute_msg, vec![]).unwrap();
+
+            app.execute(initiator_addr.clone(), cosmos_msg).unwrap();
+
+            assert_eq!(cw20_balance(&app, &cw20_token, &counterparty_addr), 1000);
+            assert_eq!(
+                cw20_balance(&app, &cw20_token, &cw_template_contract.addr()),
+                0
+            );
+
+            let initiator_token_b_balance = app
+                .wrap()
+                .query_balance(initiator_addr.clone(), TOKEN_B)
+                .unwrap()
+                .amount
+                .u128();
+            assert_eq!(initiator_token_b_balance, 2000);
+        }
+
+        #[test]
+        fn cw20_for_cw20_agreement_canceled() {
+            let (mut app, cw_template_contract) = proper_instantiate();
+
+            let initiator_addr = app.api().addr_make(INITIATOR);
+            let counterparty_addr = app.api().addr_make(COUNTERPARTY);
+
+            let cw20_token_a = instantiate_cw20(&mut app, "CWA", &initiator_addr, 10000);
+            let cw20_token_b = instantiate_cw20(&mut app, "CWB", &counterparty_addr, 10000);
+
+            let initiate_msg = ReceiveMsg::InitiateAgreement {
+                initiator_token: Asset::cw20(cw20_token_a.clone(), 1000u128),
+                counterparty_token: Asset::cw20(cw20_token_b.clone(), 2000u128),
+                counterparty: Some(counterparty_addr.clone()),
+                expires: None,
+                arbiter: None,
+                arbiter_fee_bps: None,
+                milestones: None,
+                htlc: None,
+                cancel_policy: None,
+                cancel_penalty: None,
+                initiator_assets: None,
+                counterparty_assets: None,
+            };
+
+            send_cw20(
+                &mut app,
+                &initiator_addr,
+                &cw20_token_a,
+                &cw_template_contract.addr(),
+                1000,
+                &initiate_msg,
+            )
+            .unwrap();
+
+            send_cw20(
+                &mut app,
+                &counterparty_addr,
+                &cw20_token_b,
+                &cw_template_contract.addr(),
+                2000,
+                &ReceiveMsg::AcceptAgreement { id: 1 },
+            )
+            .unwrap();
+
+            assert_eq!(
+                cw20_balance(&app, &cw20_token_b, &cw_template_contract.addr()),
+                2000
+            );
+
+            let cancel_msg = ExecuteMsg::CancelAgreement { id: 1 };
+
+            let cosmos_msg = cw_template_contract.call(cancel_msg, vec![]).unwrap();
+
+            app.execute(initiator_addr.clone(), cosmos_msg).unwrap();
+
+            assert_eq!(cw20_balance(&app, &cw20_token_a, &initiator_addr), 10000);
+            assert_eq!(cw20_balance(&app, &cw20_token_b, &counterparty_addr), 10000);
+            assert_eq!(
+                cw20_balance(&app, &cw20_token_a, &cw_template_contract.addr()),
+                0
+            );
+            assert_eq!(
+                cw20_balance(&app, &cw20_token_b, &cw_template_contract.addr()),
+                0
+            );
+        }
+
+        #[test]
+        fn cw20_deposit_from_wrong_token_contract() {
+            let (mut app, cw_template_contract) = proper_instantiate();
+
+            let initiator_addr = app.api().addr_make(INITIATOR);
+            let counterparty_addr = app.api().addr_make(COUNTERPARTY);
+
+            let cw20_token_a = instantiate_cw20(&mut app, "CWA", &initiator_addr, 10000);
+            let cw20_token_b = instantiate_cw20(&mut app, "CWB", &initiator_addr, 10000);
+
+            let initiate_msg = ReceiveMsg::InitiateAgreement {
+                initiator_token: Asset::cw20(cw20_token_a.clone(), 1000u128),
+                counterparty_token: Asset::native(TOKEN_B, 2000u128),
+                counterparty: Some(counterparty_addr.clone()),
+                expires: None,
+                arbiter: None,
+                arbiter_fee_bps: None,
+                milestones: None,
+                htlc: None,
+                cancel_policy: None,
+                cancel_penalty: None,
+                initiator_assets: None,
+                counterparty_assets: None,
+            };
+
+            let err = send_cw20(
+                &mut app,
+                &initiator_addr,
+                &cw20_token_b,
+                &cw_template_contract.addr(),
+                1000,
+                &initiate_msg,
+            )
+            .unwrap_err();
+
+            assert_eq!(
+                err.root_cause().to_string(),
+                format!(
+                    "Unexpected funds found: expected `{}`, but found `{}`",
+                    cw20_token_a, cw20_token_b
+                )
+            );
+            assert_eq!(cw20_balance(&app, &cw20_token_b, &initiator_addr), 10000);
+        }
+    }
+
+    mod cw721_agreement_tests {
+        use super::*;
+        use crate::msg::{ExecuteMsg, ReceiveMsg};
+        use cosmwasm_std::{coins, to_json_binary};
+        use cw721::msg::OwnerOfResponse;
+        use cw_multi_test::error::AnyResult;
+        use cw_multi_test::AppResponse;
+
+        fn instantiate_cw721(app: &mut App, symbol: &str, mints: &[(&Addr, &str)]) -> Addr {
+            let cw721_id = app.store_code(contract_cw721());
+            let minter = app.api().addr_make(ADMIN);
+
+            let msg = cw721_base::msg::InstantiateMsg {
+                name: format!("{} collection", symbol),
+                symbol: symbol.to_string(),
+                collection_info_extension: None,
+                minter: Some(minter.to_string()),
+                creator: None,
+                withdraw_address: None,
+            };
+
+            let collection = app
+                .instantiate_contract(cw721_id, minter.clone(), &msg, &[], symbol, None)
+                .unwrap();
+
+            for (owner, token_id) in mints {
+                app.execute_contract(
+                    minter.clone(),
+                    collection.clone(),
+                    &cw721_base::msg::ExecuteMsg::Mint {
+                        token_id: token_id.to_string(),
+                        owner: owner.to_string(),
+                        token_uri: None,
+                        extension: None,
+                    },
+                    &[],
+                )
+                .unwrap();
+            }
+
+            collection
+        }
+
+        fn nft_owner(app: &App, collection: &Addr, token_id: &str) -> Addr {
+            let response: OwnerOfResponse = app
+                .wrap()
+                .query_wasm_smart(
+                    collection,
+                    &cw721_base::msg::QueryMsg::OwnerOf {
+                        token_id: token_id.to_string(),
+                        include_expired: None,
+                    },
+                )
+                .unwrap();
+            Addr::unchecked(response.owner)
+        }
+
+        fn send_nft(
+            app: &mut App,
+            sender: &Addr,
+            collection: &Addr,
+            contract: &Addr,
+            token_id: &str,
+            msg: &ReceiveMsg,
+        ) -> AnyResult<AppResponse> {
+            app.execute_contract(
+                sender.clone(),
+                collection.clone(),
+                &cw721_base::msg::ExecuteMsg::SendNft {
+                    contract: contract.to_string(),
+                    token_id: token_id.to_string(),
+                    msg: to_json_binary(msg).unwrap(),
+                },
+                &[],
+            )
+        }
+
+        #[test]
+        fn nft_for_native_agreement() {
+            let (mut app, cw_template_contract) = proper_instantiate();
+
+            let initiator_addr = app.api().addr_make(INITIATOR);
+            let counterparty_addr = app.api().addr_make(COUNTERPARTY);
+
+            let collection = instantiate_cw721(&mut app, "NFTA", &[(&initiator_addr, "1")]);
+
+            app.init_modules(|router, _, storage| {
+                router
+                    .bank
+                    .init_balance(storage, &counterparty_addr.clone(), coins(10000, TOKEN_B))
+                    .unwrap();
+            });
+
+            let initiate_msg = ReceiveMsg::InitiateAgreement {
+                initiator_token: Asset::cw721(collection.clone(), &["1"]),
+                counterparty_token: Asset::native(TOKEN_B, 2000u128),
+                counterparty: Some(counterparty_addr.clone()),
+                expires: None,
+                arbiter: None,
+                arbiter_fee_bps: None,
+                milestones: None,
+                htlc: None,
+                cancel_policy: None,
+                cancel_penalty: None,
+                initiator_assets: None,
+                counterparty_assets: None,
+            };
+
+            send_nft(
+                &mut app,
+                &initiator_addr,
+                &collection,
+                &cw_template_contract.addr(),
+                "1",
+                &initiate_msg,
+            )
+            .unwrap();
+
+            assert_eq!(
+                nft_owner(&app, &collection, "1"),
+                cw_template_contract.addr()
+            );
+
+            let accept_msg = ExecuteMsg::AcceptAgreement { id: 1 };
+
+            let cosmos_msg = cw_template_contract
+                .call(accept_msg, coins(2000, TOKEN_B))
+                .unwrap();
+
+            app.execute(counterparty_addr.clone(), cosmos_msg).unwrap();
+
+            let execute_msg = ExecuteMsg::ExecuteAgreement {
+                id: 1,
+                preimage: None,
+            };
+
+            let cosmos_msg = cw_template_contract.call(execute_msg, vec![]).unwrap();
+
+            app.execute(counterparty_addr.clone(), cosmos_msg).unwrap();
+
+            assert_eq!(nft_owner(&app, &collection, "1"), counterparty_addr);
+
+            let initiator_token_b_balance = app
+                .wrap()
+                .query_balance(initiator_addr.clone(), TOKEN_B)
+                .unwrap()
+                .amount
+                .u128();
+            assert_eq!(initiator_token_b_balance, 2000);
+        }
+
+        #[test]
+        fn nfts_for_nft_agreement() {
+            let (mut app, cw_template_contract) = proper_instantiate();
+
+            let initiator_addr = app.api().addr_make(INITIATOR);
+            let counterparty_addr = app.api().addr_make(COUNTERPARTY);
+
+            let collection_a = instantiate_cw721(
+                &mut app,
+                "NFTA",
+                &[(&initiator_addr, "1"), (&initiator_addr, "2")],
+            );
+            let collection_b = instantiate_cw721(&mut app, "NFTB", &[(&counterparty_addr, "7")]);
+
+            let initiate_msg = ReceiveMsg::InitiateAgreement {
+                initiator_token: Asset::cw721(collection_a.clone(), &["1", "2"]),
+                counterparty_token: Asset::cw721(collection_b.clone(), &["7"]),
+                counterparty: Some(counterparty_addr.clone()),
+                expires: None,
+                arbiter: None,
+                arbiter_fee_bps: None,
+                milestones: None,
+                htlc: None,
+                cancel_policy: None,
+                cancel_penalty: None,
+                initiator_assets: None,
+                counterparty_assets: None,
+            };
+
+            send_nft(
+                &mut app,
+                &initiator_addr,
+                &collection_a,
+                &cw_template_contract.addr(),
+                "1",
+                &initiate_msg,
+            )
+            .unwrap();
+
+            // The counterparty cannot accept before every NFT of the initiator is escrowed
+            let err = send_nft(
+                &mut app,
+                &counterparty_addr,
+                &collection_b,
+                &cw_template_contract.addr(),
+                "7",
+                &ReceiveMsg::AcceptAgreement { id: 1 },
+            )
+            .unwrap_err();
+            assert_eq!(
+                err.root_cause().to_string(),
+                format!(
+                    "Incomplete deposit: `cw721:{}:1,2` has not been fully escrowed",
+                    collection_a
+                )
+            );
+
+            send_nft(
+                &mut app,
+                &initiator_addr,
+                &collection_a,
+                &cw_template_contract.addr(),
+                "2",
+                &ReceiveMsg::DepositNft { id: 1 },
+            )
+            .unwrap();
+
+            send_nft(
+                &mut app,
+                &counterparty_addr,
+                &collection_b,
+                &cw_template_contract.addr(),
+                "7",
+                &ReceiveMsg::AcceptAgreement { id: 1 },
+            )
+            .unwrap();
+
+            let execute_msg = ExecuteMsg::ExecuteAgreement {
+                id: 1,
+                preimage: None,
+            };
+
+            let cosmos_msg = cw_template_contract.call(execute_msg, vec![]).unwrap();
+
+            app.execute(initiator_addr.clone(), cosmos_msg).unwrap();
+
+            assert_eq!(nft_owner(&app, &collection_a, "1"), counterparty_addr);
+            assert_eq!(nft_owner(&app, &collection_a, "2"), counterparty_addr);
+            assert_eq!(nft_owner(&app, &collection_b, "7"), initiator_addr);
+        }
+
+        #[test]
+        fn cancel_partially_deposited_nft_agreement() {
+            let (mut app, cw_template_contract) = proper_instantiate();
+
+            let initiator_addr = app.api().addr_make(INITIATOR);
+            let counterparty_addr = app.api().addr_make(COUNTERPARTY);
+
+            let collection = instantiate_cw721(
+                &mut app,
+                "NFTA",
+                &[(&initiator_addr, "1"), (&initiator_addr, "2")],
+            );
+
+            let initiate_msg = ReceiveMsg::InitiateAgreement {
+                initiator_token: Asset::cw721(collection.clone(), &["1", "2"]),
+                counterparty_token: Asset::native(TOKEN_B, 2000u128),
+                counterparty: Some(counterparty_addr.clone()),
+                expires: None,
+                arbiter: None,
+                arbiter_fee_bps: None,
+                milestones: None,
+                htlc: None,
+                cancel_policy: None,
+                cancel_penalty: None,
+                initiator_assets: None,
+                counterparty_assets: None,
+            };
+
+            send_nft(
+                &mut app,
+                &initiator_addr,
+                &collection,
+                &cw_template_contract.addr(),
+                "1",
+                &initiate_msg,
+            )
+            .unwrap();
+
+            let cancel_msg = ExecuteMsg::CancelAgreement { id: 1 };
+
+            let cosmos_msg = cw_template_contract.call(cancel_msg, vec![]).unwrap();
+
+            app.execute(initiator_addr.clone(), cosmos_msg).unwrap();
+
+            assert_eq!(nft_owner(&app, &collection, "1"), initiator_addr);
+            assert_eq!(nft_owner(&app, &collection, "2"), initiator_addr);
+        }
     }
 }
@@ -1,28 +1,305 @@
-use crate::state::Agreement;
-use cosmwasm_std::Addr;
+use crate::asset::Asset;
+use crate::state::{
+    Agreement, AgreementStatus, AssetListMode, AssetRule, CancelPenalty, CancelPolicy, Config,
+    CounterOfferStatus, Htlc, Milestone, MultiPartyAgreement, Participant, PauseFlags,
+};
+use cosmwasm_std::{Addr, Binary, HexBinary, Uint128};
+use cw20::Cw20ReceiveMsg;
+use cw721::receiver::Cw721ReceiveMsg;
+use cw_utils::Expiration;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
-pub struct InstantiateMsg {}
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq, JsonSchema)]
+pub struct InstantiateMsg {
+    /// Defaults to the sender of the instantiation.
+    pub owner: Option<Addr>,
+    /// Defaults to the owner.
+    pub fee_recipient: Option<Addr>,
+    /// Share of the initiator token taken as fee when an agreement executes.
+    #[serde(default)]
+    pub initiator_fee_bps: u16,
+    /// Share of the counterparty token taken as fee when an agreement executes.
+    #[serde(default)]
+    pub counterparty_fee_bps: u16,
+    /// Address allowed to pause and unpause the contract besides the owner.
+    pub guardian: Option<Addr>,
+}
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq, JsonSchema)]
+pub struct MigrateMsg {
+    /// Owner set when migrating from a version without an owner.
+    pub owner: Option<Addr>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
+#[allow(clippy::large_enum_variant)]
 pub enum ExecuteMsg {
     InitiateAgreement {
-        initiator_token: TokenInfo,
-        counterparty_token: TokenInfo,
-        counterparty: Addr,
+        initiator_token: Asset,
+        counterparty_token: Asset,
+        /// Leave unset to post an open offer taken by the first valid `AcceptAgreement`.
+        counterparty: Option<Addr>,
+        expires: Option<Expiration>,
+        arbiter: Option<Addr>,
+        arbiter_fee_bps: Option<u16>,
+        milestones: Option<Vec<Uint128>>,
+        /// Locks execution behind the preimage of a sha256 hashlock until a timelock passes.
+        htlc: Option<Htlc>,
+        /// Who has to agree to cancel the agreement once accepted, either party by default.
+        cancel_policy: Option<CancelPolicy>,
+        /// Paid out of the deposit of a party canceling the accepted agreement to the other party.
+        cancel_penalty: Option<CancelPenalty>,
+        /// Further bank assets deposited together with the initiator token.
+        initiator_assets: Option<Vec<Asset>>,
+        /// Further bank assets the counterparty deposits together with the counterparty token.
+        counterparty_assets: Option<Vec<Asset>>,
     },
     AcceptAgreement {
         id: u64,
     },
     ExecuteAgreement {
         id: u64,
+        /// Preimage of the hashlock, required to execute HTLC agreements.
+        preimage: Option<HexBinary>,
     },
     CancelAgreement {
         id: u64,
     },
+    /// Refunds the deposits of an initiated agreement past its expiration, callable by anyone.
+    ClaimExpired {
+        id: u64,
+    },
+    /// Refunds the deposits of an HTLC agreement past its timelock, callable by anyone.
+    RefundHtlc {
+        id: u64,
+    },
+    /// Asks the other party to cancel an accepted agreement that requires mutual consent.
+    RequestCancel {
+        id: u64,
+    },
+    /// Agrees to the other party's request, canceling the agreement and refunding both parties.
+    ConfirmCancel {
+        id: u64,
+    },
+    /// Declines an initiated agreement, refunding the initiator. Callable by the counterparty.
+    RejectAgreement {
+        id: u64,
+        reason: String,
+    },
+    /// Freezes an accepted agreement with an arbiter until the arbiter resolves it. Hash time
+    /// locked agreements cannot be disputed.
+    RaiseDispute {
+        id: u64,
+        reason: String,
+    },
+    /// Splits the escrow of a disputed agreement between the parties, callable by the arbiter.
+    ResolveDispute {
+        id: u64,
+        payout: DisputePayout,
+    },
+    /// Releases the milestone at `index` to the initiator, callable by the initiator in order.
+    ApproveMilestone {
+        id: u64,
+        index: u32,
+    },
+    /// Pays part of the counterparty token of an initiated agreement for a proportional share
+    /// of the initiator token, executing the agreement once it is completely filled.
+    FillAgreement {
+        id: u64,
+    },
+    /// Replaces the counterparty and tokens of an initiated agreement, callable by the initiator.
+    /// Any increase of the initiator token is attached, a decrease is refunded.
+    AmendAgreement {
+        id: u64,
+        /// Leave unset to turn the agreement into an open offer.
+        counterparty: Option<Addr>,
+        counterparty_token: Asset,
+        initiator_token: Asset,
+    },
+    /// Proposes new terms for an initiated agreement, superseding the pending proposal if any.
+    /// The initiator attaches any increase of the initiator token it proposes.
+    ProposeCounterOffer {
+        id: u64,
+        initiator_token: Asset,
+        counterparty_token: Asset,
+    },
+    /// Accepts the pending proposal of the other party, refunding or topping up the initiator's
+    /// deposit to the new initiator token.
+    AcceptCounterOffer {
+        id: u64,
+    },
+    /// Proposes a new owner, who takes over once it accepts. Callable by the owner.
+    TransferOwnership {
+        new_owner: Addr,
+    },
+    /// Completes an ownership transfer, callable by the proposed owner.
+    AcceptOwnership {},
+    /// Leaves the contract without an owner for good, callable by the owner.
+    RenounceOwnership {},
+    /// Updates the fee config, callable by the owner. Unset fields are left unchanged.
+    UpdateConfig {
+        fee_recipient: Option<Addr>,
+        initiator_fee_bps: Option<u16>,
+        counterparty_fee_bps: Option<u16>,
+    },
+    /// Sets or removes the guardian, callable by the owner.
+    SetGuardian {
+        guardian: Option<Addr>,
+    },
+    /// Pauses the operations set to true, callable by the owner or guardian.
+    Pause {
+        #[serde(default)]
+        initiate: bool,
+        #[serde(default)]
+        accept: bool,
+        #[serde(default)]
+        execute: bool,
+    },
+    /// Unpauses the operations set to true, callable by the owner or guardian.
+    Unpause {
+        #[serde(default)]
+        initiate: bool,
+        #[serde(default)]
+        accept: bool,
+        #[serde(default)]
+        execute: bool,
+    },
+    /// Adds or replaces the rule of a bank denom or token contract address, callable by the owner.
+    AddAssetRule {
+        token: String,
+        allowed: bool,
+        min_amount: Option<Uint128>,
+        max_amount: Option<Uint128>,
+    },
+    /// Removes the rule of a bank denom or token contract address, callable by the owner.
+    RemoveAssetRule {
+        token: String,
+    },
+    /// Sets whether assets without a rule can be escrowed, callable by the owner.
+    SetAssetListMode {
+        mode: AssetListMode,
+    },
+    /// Credits the attached funds to the sender's balance, used to settle signed offers.
+    Deposit {},
+    /// Withdraws an asset from the sender's balance.
+    Withdraw {
+        asset: Asset,
+    },
+    /// Settles an offer signed off-chain by its initiator, paying the initiator token out of the
+    /// initiator's balance against the counterparty token attached by the sender.
+    AcceptSignedOffer {
+        offer: SignedOffer,
+        /// 64 byte secp256k1 signature over the digest returned by `GetSignedOffer`.
+        signature: Binary,
+        /// 33 byte compressed secp256k1 public key of the initiator's account.
+        pubkey: Binary,
+    },
+    /// Revokes an offer signed off-chain by the sender, so that it can no longer be accepted.
+    RevokeSignedOffer {
+        offer: SignedOffer,
+    },
+    /// Creates an agreement between several participants, each owing a deposit and receiving a
+    /// payout. Together the payouts must add up to the deposits.
+    InitiateMultiPartyAgreement {
+        participants: Vec<Participant>,
+    },
+    /// Deposits the attached funds towards the sender's share of a multi-party agreement.
+    DepositMultiParty {
+        id: u64,
+    },
+    /// Pays out a multi-party agreement once every participant has deposited in full.
+    ExecuteMultiPartyAgreement {
+        id: u64,
+    },
+    /// Cancels a multi-party agreement, refunding what each participant actually deposited.
+    CancelMultiPartyAgreement {
+        id: u64,
+    },
+    Receive(Cw20ReceiveMsg),
+    ReceiveNft(Cw721ReceiveMsg),
+}
+
+/// Terms of an offer signed off-chain by the initiator, each offer can be accepted once.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SignedOffer {
+    pub initiator: Addr,
+    pub initiator_token: Asset,
+    pub counterparty_token: Asset,
+    /// Leave unset to let anyone accept the offer.
+    pub counterparty: Option<Addr>,
+    /// Distinguishes otherwise identical offers of the same initiator.
+    pub nonce: u64,
+    /// Offers that never expire are rejected, revoke an offer to withdraw it before it expires.
+    pub expires: Expiration,
+}
+
+/// Assets an arbiter awards to each party, together they must add up to the escrow minus the fee.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct DisputePayout {
+    pub initiator: Vec<Asset>,
+    pub counterparty: Vec<Asset>,
+}
+
+/// Hook messages carried in the `msg` field of a CW20 `Send` or a CW721 `SendNft`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+#[allow(clippy::large_enum_variant)]
+pub enum ReceiveMsg {
+    InitiateAgreement {
+        initiator_token: Asset,
+        counterparty_token: Asset,
+        /// Leave unset to post an open offer taken by the first valid `AcceptAgreement`.
+        counterparty: Option<Addr>,
+        expires: Option<Expiration>,
+        arbiter: Option<Addr>,
+        arbiter_fee_bps: Option<u16>,
+        milestones: Option<Vec<Uint128>>,
+        /// Locks execution behind the preimage of a sha256 hashlock until a timelock passes.
+        htlc: Option<Htlc>,
+        /// Who has to agree to cancel the agreement once accepted, either party by default.
+        cancel_policy: Option<CancelPolicy>,
+        /// Paid out of the deposit of a party canceling the accepted agreement to the other party.
+        cancel_penalty: Option<CancelPenalty>,
+        /// Further bank assets deposited together with the initiator token.
+        initiator_assets: Option<Vec<Asset>>,
+        /// Further bank assets the counterparty deposits together with the counterparty token.
+        counterparty_assets: Option<Vec<Asset>>,
+    },
+    AcceptAgreement {
+        id: u64,
+    },
+    /// Escrows one more of the initiator's NFTs when the initiator token holds several token ids.
+    DepositNft {
+        id: u64,
+    },
+    FillAgreement {
+        id: u64,
+    },
+    AmendAgreement {
+        id: u64,
+        counterparty: Option<Addr>,
+        counterparty_token: Asset,
+        initiator_token: Asset,
+    },
+    ProposeCounterOffer {
+        id: u64,
+        initiator_token: Asset,
+        counterparty_token: Asset,
+    },
+    AcceptCounterOffer {
+        id: u64,
+    },
+    Deposit {},
+    AcceptSignedOffer {
+        offer: SignedOffer,
+        signature: Binary,
+        pubkey: Binary,
+    },
+    DepositMultiParty {
+        id: u64,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -36,37 +313,80 @@ pub enum QueryMsg {
     GetAcceptedAgreementCount {},
     GetExecutedAgreementCount {},
     GetCanceledAgreementCount {},
+    GetExpiredAgreementCount {},
+    GetDisputedAgreementCount {},
+    GetResolvedAgreementCount {},
+    GetRejectedAgreementCount {},
     GetAgreementsByInitiator {
         initiator: Addr,
-        page: u64,
-        page_size: u64,
+        start_after: Option<u64>,
+        limit: Option<u32>,
     },
     GetAgreementsByCounterparty {
         counterparty: Addr,
-        page: u64,
-        page_size: u64,
+        start_after: Option<u64>,
+        limit: Option<u32>,
     },
     GetAgreementsByStatus {
-        status: String,
-        page: u64,
-        page_size: u64,
+        status: AgreementStatus,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    GetOpenOffers {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    GetMilestones {
+        id: u64,
+    },
+    /// The party asking to cancel an accepted agreement, awaiting the other party's consent.
+    GetCancelRequest {
+        id: u64,
+    },
+    GetAgreementDeposits {
+        id: u64,
+    },
+    /// A multi-party agreement, the deposits made so far and the participants yet to deposit.
+    GetMultiPartyAgreement {
+        id: u64,
+    },
+    GetFills {
+        id: u64,
+        start_after: Option<u32>,
+        limit: Option<u32>,
+    },
+    /// Lists the negotiation rounds of an agreement in the order they were proposed.
+    GetCounterOffers {
+        id: u64,
+        start_after: Option<u32>,
+        limit: Option<u32>,
+    },
+    GetOwnership {},
+    GetConfig {},
+    GetPaused {},
+    GetAssetRule {
+        token: String,
+    },
+    GetAssetRules {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Total fees collected in a bank denom or CW20 contract address.
+    GetAccruedFees {
+        denom: String,
+    },
+    /// Assets an address has deposited to settle its signed offers.
+    GetBalances {
+        owner: Addr,
+    },
+    /// Digest an initiator signs for an offer, the agreement it settled if already accepted, and
+    /// whether its initiator revoked it.
+    GetSignedOffer {
+        offer: SignedOffer,
     },
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
-pub struct TokenInfo {
-    pub address: Addr,
-    pub amount: u128,
-}
-
-impl TokenInfo {
-    #[inline]
-    pub fn into_string(self) -> String {
-        [self.amount.to_string(), self.address.to_string()].join("")
-    }
-}
-
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct AgreementResponse {
     pub agreement: Agreement,
 }
@@ -97,6 +417,144 @@ pub struct CanceledAgreementCountResponse {
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct ExpiredAgreementCountResponse {
+    pub expired_agreement_count: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct DisputedAgreementCountResponse {
+    pub disputed_agreement_count: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct ResolvedAgreementCountResponse {
+    pub resolved_agreement_count: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct RejectedAgreementCountResponse {
+    pub rejected_agreement_count: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct AgreementsResponse {
     pub agreements: Vec<Agreement>,
+    /// Cursor to pass as `start_after` for the next page, unset once all agreements were listed.
+    pub next_start_after: Option<u64>,
+}
+
+/// Assets held in escrow for an agreement on behalf of one depositor.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct AgreementDeposit {
+    pub depositor: Addr,
+    pub assets: Vec<Asset>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct AgreementDepositsResponse {
+    pub deposits: Vec<AgreementDeposit>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct MultiPartyAgreementResponse {
+    pub agreement: MultiPartyAgreement,
+    pub deposits: Vec<AgreementDeposit>,
+    pub pending: Vec<Addr>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct CancelRequestResponse {
+    pub cancel_policy: CancelPolicy,
+    pub requested_by: Option<Addr>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct MilestonesResponse {
+    pub milestones: Vec<Milestone>,
+    pub released: u32,
+    pub released_amount: Uint128,
+}
+
+/// A partial fill of an agreement, `payout` is the initiator token received for `amount`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct AgreementFill {
+    pub index: u32,
+    pub filler: Addr,
+    pub amount: Uint128,
+    pub payout: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct FillsResponse {
+    pub fills: Vec<AgreementFill>,
+    pub filled: Uint128,
+    pub remaining: Uint128,
+    /// Cursor to pass as `start_after` for the next page, unset once all fills were listed.
+    pub next_start_after: Option<u32>,
+}
+
+/// A negotiation round of an agreement, `round` counts from zero.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct AgreementCounterOffer {
+    pub round: u32,
+    pub proposer: Addr,
+    pub initiator_token: Asset,
+    pub counterparty_token: Asset,
+    pub status: CounterOfferStatus,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct CounterOffersResponse {
+    pub counter_offers: Vec<AgreementCounterOffer>,
+    /// Cursor to pass as `start_after` for the next page, unset once all rounds were listed.
+    pub next_start_after: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct OwnershipResponse {
+    pub owner: Option<Addr>,
+    pub pending_owner: Option<Addr>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct ConfigResponse {
+    pub config: Config,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct PausedResponse {
+    pub paused: PauseFlags,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct AssetRuleResponse {
+    pub token: String,
+    pub rule: Option<AssetRule>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct AssetRulesResponse {
+    pub mode: AssetListMode,
+    pub rules: Vec<AssetRuleResponse>,
+    /// Cursor to pass as `start_after` for the next page, unset once all rules were listed.
+    pub next_start_after: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct AccruedFeesResponse {
+    pub denom: String,
+    pub amount: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct BalancesResponse {
+    pub owner: Addr,
+    pub balances: Vec<Asset>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct SignedOfferResponse {
+    pub digest: HexBinary,
+    pub agreement_id: Option<u64>,
+    pub revoked: bool,
 }
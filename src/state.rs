@@ -1,30 +1,532 @@
+use crate::asset::Asset;
+use crate::contract::BPS_DENOMINATOR;
 use crate::msg::{
-    AcceptedAgreementCountResponse, AgreementResponse, AgreementsResponse,
-    CanceledAgreementCountResponse, ExecutedAgreementCountResponse,
-    InitiatedAgreementCountResponse, TokenInfo, TotalAgreementCountResponse,
+    AcceptedAgreementCountResponse, AccruedFeesResponse, AgreementCounterOffer, AgreementDeposit,
+    AgreementDepositsResponse, AgreementFill, AgreementResponse, AgreementsResponse,
+    AssetRuleResponse, AssetRulesResponse, BalancesResponse, CancelRequestResponse,
+    CanceledAgreementCountResponse, ConfigResponse, CounterOffersResponse,
+    DisputedAgreementCountResponse, ExecutedAgreementCountResponse, ExpiredAgreementCountResponse,
+    FillsResponse, InitiatedAgreementCountResponse, MilestonesResponse,
+    MultiPartyAgreementResponse, OwnershipResponse, PausedResponse, RejectedAgreementCountResponse,
+    ResolvedAgreementCountResponse, SignedOffer, SignedOfferResponse, TotalAgreementCountResponse,
 };
-use cosmwasm_std::{Addr, Deps, Order, StdResult};
-use cw_storage_plus::{Bounder, Item, Map};
+use crate::utils::{fill_payout, outstanding_assets, signed_offer_digest};
+use crate::ContractError;
+use cosmwasm_std::{Addr, Deps, Env, HexBinary, Order, StdResult, Uint128};
+use cw_storage_plus::{Bound, Index, IndexList, IndexedMap, Item, Map, MultiIndex};
+use cw_utils::Expiration;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
 
+/// Protocol fees of the contract, paid to the fee recipient when agreements execute, and the
+/// guardian allowed to pause the contract besides the owner.
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct Config {
+    pub fee_recipient: Addr,
+    pub initiator_fee_bps: u16,
+    pub counterparty_fee_bps: u16,
+    #[serde(default)]
+    pub guardian: Option<Addr>,
+}
+
+/// The owner a config held before ownership moved to `OWNERSHIP`, read by `migrate`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct LegacyConfig {
+    #[serde(default)]
+    pub owner: Option<Addr>,
+}
+
+/// Operations currently paused, canceling agreements is never paused so deposits can be refunded.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq, JsonSchema)]
+pub struct PauseFlags {
+    pub initiate: bool,
+    pub accept: bool,
+    pub execute: bool,
+}
+
+/// Whether assets without a rule can be escrowed (`denylist`) or not (`allowlist`).
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AssetListMode {
+    #[default]
+    Denylist,
+    Allowlist,
+}
+
+/// Owner-managed rule for a bank denom or token contract, limiting the amount per agreement.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct AssetRule {
+    pub allowed: bool,
+    pub min_amount: Option<Uint128>,
+    pub max_amount: Option<Uint128>,
+}
+
+/// Owner of the contract and the address proposed to take over ownership, if any.
+/// A renounced contract has no owner and no admin operations can be performed anymore.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq, JsonSchema)]
+pub struct Ownership {
+    pub owner: Option<Addr>,
+    pub pending_owner: Option<Addr>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Agreement {
     pub id: u64,
     pub initiator: Addr,
-    pub initiator_token: TokenInfo,
+    pub initiator_token: Asset,
+    pub counterparty: Option<Addr>,
+    pub counterparty_token: Asset,
+    pub status: AgreementStatus,
+    pub expires: Option<Expiration>,
+    pub arbiter: Option<Addr>,
+    #[serde(default)]
+    pub arbiter_fee_bps: u16,
+    pub dispute: Option<Dispute>,
+    #[serde(default)]
+    pub milestones: Vec<Milestone>,
+    /// Amount of the counterparty token paid by partial fills so far.
+    #[serde(default)]
+    pub filled: Uint128,
+    #[serde(default)]
+    pub htlc: Option<Htlc>,
+    #[serde(default)]
+    pub rejection: Option<Rejection>,
+    #[serde(default)]
+    pub cancel_policy: CancelPolicy,
+    #[serde(default)]
+    pub cancel_penalty: Option<CancelPenalty>,
+    /// Further bank assets swapped together with the initiator token.
+    #[serde(default)]
+    pub initiator_assets: Vec<Asset>,
+    /// Further bank assets swapped together with the counterparty token.
+    #[serde(default)]
+    pub counterparty_assets: Vec<Asset>,
+}
+
+impl Agreement {
+    /// Returns the initiator and, once known, the counterparty of the agreement.
+    pub fn parties(&self) -> Vec<&Addr> {
+        [Some(&self.initiator), self.counterparty.as_ref()]
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+
+    /// Returns the counterparty of an agreement that has been taken by one.
+    pub fn accepted_counterparty(&self) -> Result<&Addr, ContractError> {
+        self.counterparty
+            .as_ref()
+            .ok_or(ContractError::OpenOffer { id: self.id })
+    }
+
+    /// Returns the counterparty for event attributes, `open` for an offer anyone may take.
+    pub fn counterparty_label(&self) -> String {
+        self.counterparty
+            .as_ref()
+            .map(|counterparty| counterparty.to_string())
+            .unwrap_or_else(|| OPEN_COUNTERPARTY.to_string())
+    }
+
+    /// Returns every asset the initiator escrows, starting with the initiator token.
+    pub fn initiator_basket(&self) -> Vec<Asset> {
+        std::iter::once(&self.initiator_token)
+            .chain(&self.initiator_assets)
+            .cloned()
+            .collect()
+    }
+
+    /// Returns every asset the counterparty escrows, starting with the counterparty token.
+    pub fn counterparty_basket(&self) -> Vec<Asset> {
+        std::iter::once(&self.counterparty_token)
+            .chain(&self.counterparty_assets)
+            .cloned()
+            .collect()
+    }
+
+    /// Returns true if either party swaps further assets together with its token.
+    pub fn is_basket(&self) -> bool {
+        !self.initiator_assets.is_empty() || !self.counterparty_assets.is_empty()
+    }
+
+    /// Returns the amount of the initiator token not yet paid out to partial fills.
+    pub fn remaining(&self) -> Uint128 {
+        self.initiator_token.amount()
+            - fill_payout(
+                self.initiator_token.amount(),
+                self.counterparty_token.amount(),
+                Uint128::zero(),
+                self.filled,
+            )
+    }
+
+    /// Returns the penalty the canceling party pays out of its deposit, capped at the deposit.
+    /// NFT deposits are never penalized.
+    pub fn cancel_penalty(&self, canceler: &Addr) -> Option<Asset> {
+        let is_initiator = *canceler == self.initiator;
+        let deposit = if is_initiator {
+            &self.initiator_token
+        } else {
+            &self.counterparty_token
+        };
+        let amount = match self.cancel_penalty.as_ref()? {
+            CancelPenalty::Bps { bps } => deposit.amount().multiply_ratio(*bps, BPS_DENOMINATOR),
+            CancelPenalty::Fixed {
+                initiator,
+                counterparty,
+            } => {
+                let amount = if is_initiator {
+                    initiator
+                } else {
+                    counterparty
+                };
+                (*amount).min(deposit.amount())
+            }
+        };
+        (!deposit.is_nft() && !amount.is_zero()).then(|| deposit.with_amount(amount))
+    }
+}
+
+/// A participant of a multi-party agreement, the assets it deposits and the assets paid out to
+/// it once the agreement executes.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct Participant {
+    pub address: Addr,
+    pub deposit: Vec<Asset>,
+    pub payout: Vec<Asset>,
+}
+
+/// An agreement between any number of participants, which can be executed once every participant
+/// has deposited in full. The payouts redistribute exactly the deposited assets.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct MultiPartyAgreement {
+    pub id: u64,
+    pub creator: Addr,
+    pub participants: Vec<Participant>,
+    pub status: AgreementStatus,
+}
+
+impl MultiPartyAgreement {
+    /// Returns the addresses of all participants.
+    pub fn addresses(&self) -> Vec<&Addr> {
+        self.participants
+            .iter()
+            .map(|participant| &participant.address)
+            .collect()
+    }
+
+    /// Returns the participant with the given address.
+    pub fn participant(&self, address: &Addr) -> Option<&Participant> {
+        self.participants
+            .iter()
+            .find(|participant| participant.address == *address)
+    }
+}
+
+/// The stage of its lifecycle an agreement is in.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AgreementStatus {
+    Initiated,
+    Accepted,
+    Executed,
+    Canceled,
+    Expired,
+    Disputed,
+    Resolved,
+    Rejected,
+}
+
+impl AgreementStatus {
+    /// Returns the snake case name the status is serialized as.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AgreementStatus::Initiated => "initiated",
+            AgreementStatus::Accepted => "accepted",
+            AgreementStatus::Executed => "executed",
+            AgreementStatus::Canceled => "canceled",
+            AgreementStatus::Expired => "expired",
+            AgreementStatus::Disputed => "disputed",
+            AgreementStatus::Resolved => "resolved",
+            AgreementStatus::Rejected => "rejected",
+        }
+    }
+}
+
+impl fmt::Display for AgreementStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for AgreementStatus {
+    type Err = ContractError;
+
+    fn from_str(status: &str) -> Result<Self, Self::Err> {
+        match status {
+            "initiated" => Ok(AgreementStatus::Initiated),
+            "accepted" => Ok(AgreementStatus::Accepted),
+            "executed" => Ok(AgreementStatus::Executed),
+            "canceled" => Ok(AgreementStatus::Canceled),
+            "expired" => Ok(AgreementStatus::Expired),
+            "disputed" => Ok(AgreementStatus::Disputed),
+            "resolved" => Ok(AgreementStatus::Resolved),
+            "rejected" => Ok(AgreementStatus::Rejected),
+            _ => Err(ContractError::UnknownAgreementStatus {
+                status: status.to_string(),
+            }),
+        }
+    }
+}
+
+/// An agreement as stored by the first version, with string statuses and tokens, read by
+/// `migrate`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LegacyAgreement {
+    pub id: u64,
+    pub initiator: Addr,
+    pub initiator_token: LegacyTokenInfo,
     pub counterparty: Addr,
-    pub counterparty_token: TokenInfo,
+    pub counterparty_token: LegacyTokenInfo,
     pub status: String,
 }
 
+/// A token as stored by the first version: an amount of the bank denom in `address`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct LegacyTokenInfo {
+    pub address: Addr,
+    pub amount: u128,
+}
+
+impl LegacyTokenInfo {
+    /// Converts the token to the native asset of its denom.
+    pub fn into_asset(self) -> Asset {
+        Asset::native(self.address, self.amount)
+    }
+}
+
+impl LegacyAgreement {
+    /// Converts the agreement to the current format, parsing its status string.
+    pub fn into_agreement(self) -> Result<Agreement, ContractError> {
+        Ok(Agreement {
+            id: self.id,
+            initiator: self.initiator,
+            initiator_token: self.initiator_token.into_asset(),
+            counterparty: Some(self.counterparty),
+            counterparty_token: self.counterparty_token.into_asset(),
+            status: self.status.parse()?,
+            expires: None,
+            arbiter: None,
+            arbiter_fee_bps: 0,
+            dispute: None,
+            milestones: vec![],
+            filled: Uint128::zero(),
+            htlc: None,
+            rejection: None,
+            cancel_policy: CancelPolicy::default(),
+            cancel_penalty: None,
+            initiator_assets: vec![],
+            counterparty_assets: vec![],
+        })
+    }
+}
+
+/// A part of the counterparty token released to the initiator once the initiator approves it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct Milestone {
+    pub amount: Uint128,
+    pub released: bool,
+}
+
+/// A partial fill of an agreement, paying part of the counterparty token for its share of the
+/// initiator token.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct Fill {
+    pub filler: Addr,
+    pub amount: Uint128,
+    pub payout: Uint128,
+}
+
+/// A proposal of new terms for an initiated agreement by one of its parties.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct CounterOffer {
+    pub proposer: Addr,
+    pub initiator_token: Asset,
+    pub counterparty_token: Asset,
+    pub status: CounterOfferStatus,
+}
+
+/// Whether a counter-offer awaits the other party, was accepted, or was replaced by a later one.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CounterOfferStatus {
+    Pending,
+    Accepted,
+    Superseded,
+}
+
+/// Hash time lock of an agreement: it is accepted, and executed only with the preimage of the
+/// sha256 `hashlock`, before `timelock` passes, after which the deposits can be refunded.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Htlc {
+    pub hashlock: HexBinary,
+    pub timelock: Expiration,
+}
+
+/// A dispute raised by one of the parties, awaiting resolution by the arbiter.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct Dispute {
+    pub raised_by: Addr,
+    pub reason: String,
+}
+
+/// Who can cancel an agreement once it has been accepted.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CancelPolicy {
+    /// Either party alone, refunding both.
+    #[default]
+    Unilateral,
+    /// Both parties, one requesting and the other confirming the cancellation.
+    MutualConsent,
+}
+
+/// Penalty a party pays to the other party for canceling an accepted agreement alone.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CancelPenalty {
+    /// Share of the canceling party's deposit.
+    Bps { bps: u16 },
+    /// Amount of its own token each party pays when it cancels.
+    Fixed {
+        initiator: Uint128,
+        counterparty: Uint128,
+    },
+}
+
+/// The counterparty's refusal of an initiated agreement.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct Rejection {
+    pub rejected_by: Addr,
+    pub reason: String,
+}
+
+/// Secondary indexes of the agreements by initiator, counterparty and status.
+/// Open offers are indexed under an empty counterparty, and in `open_offers` by their status.
+pub struct AgreementIndexes<'a> {
+    pub initiator: MultiIndex<'a, Addr, Agreement, u64>,
+    pub counterparty: MultiIndex<'a, String, Agreement, u64>,
+    pub status: MultiIndex<'a, String, Agreement, u64>,
+    pub open_offers: MultiIndex<'a, String, Agreement, u64>,
+}
+
+impl IndexList<Agreement> for AgreementIndexes<'_> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Agreement>> + '_> {
+        let v: Vec<&dyn Index<Agreement>> = vec![
+            &self.initiator,
+            &self.counterparty,
+            &self.status,
+            &self.open_offers,
+        ];
+        Box::new(v.into_iter())
+    }
+}
+
+// Storage for the contract ownership and config
+pub const OWNERSHIP: Item<Ownership> = Item::new("ownership");
+pub const CONFIG: Item<Config> = Item::new("config");
+pub const LEGACY_CONFIG: Item<LegacyConfig> = Item::new("config");
+pub const PAUSED: Item<PauseFlags> = Item::new("paused");
+
+// Asset rules keyed by bank denom or token contract address, and how unlisted assets are treated
+pub const ASSET_RULES: Map<&str, AssetRule> = Map::new("asset_rules");
+pub const ASSET_LIST_MODE: Item<AssetListMode> = Item::new("asset_list_mode");
+
+// Total fees collected per bank denom or CW20 contract address
+pub const ACCRUED_FEES: Map<&str, Uint128> = Map::new("accrued_fees");
+
 // Storage for agreements and agreement count
-pub const AGREEMENTS: Map<u64, Agreement> = Map::new("agreements");
+pub const AGREEMENTS: IndexedMap<u64, Agreement, AgreementIndexes> = IndexedMap::new(
+    "agreements",
+    AgreementIndexes {
+        initiator: MultiIndex::new(
+            |_, agreement| agreement.initiator.clone(),
+            "agreements",
+            "agreements__initiator",
+        ),
+        counterparty: MultiIndex::new(
+            |_, agreement| {
+                agreement
+                    .counterparty
+                    .as_ref()
+                    .map(|counterparty| counterparty.to_string())
+                    .unwrap_or_default()
+            },
+            "agreements",
+            "agreements__counterparty",
+        ),
+        status: MultiIndex::new(
+            |_, agreement| agreement.status.to_string(),
+            "agreements",
+            "agreements__status",
+        ),
+        open_offers: MultiIndex::new(
+            |_, agreement| match agreement.counterparty {
+                Some(_) => String::new(),
+                None => agreement.status.to_string(),
+            },
+            "agreements",
+            "agreements__open_offers",
+        ),
+    },
+);
+
+// Counterparty shown in events for offers anyone may take
+pub const OPEN_COUNTERPARTY: &str = "open";
+pub const LEGACY_AGREEMENTS: Map<u64, LegacyAgreement> = Map::new("agreements");
 pub const TOTAL_AGREEMENT_COUNT: Item<u64> = Item::new("total_agreement_count");
 pub const INITIATED_AGREEMENT_COUNT: Item<u64> = Item::new("initiated_agreement_count");
 pub const ACCEPTED_AGREEMENT_COUNT: Item<u64> = Item::new("accepted_agreement_count");
 pub const EXECUTED_AGREEMENT_COUNT: Item<u64> = Item::new("executed_agreement_count");
 pub const CANCELED_AGREEMENT_COUNT: Item<u64> = Item::new("canceled_agreement_count");
+pub const EXPIRED_AGREEMENT_COUNT: Item<u64> = Item::new("expired_agreement_count");
+pub const DISPUTED_AGREEMENT_COUNT: Item<u64> = Item::new("disputed_agreement_count");
+pub const RESOLVED_AGREEMENT_COUNT: Item<u64> = Item::new("resolved_agreement_count");
+pub const REJECTED_AGREEMENT_COUNT: Item<u64> = Item::new("rejected_agreement_count");
+
+// Escrow ledger of the assets deposited per agreement, keyed by agreement ID and depositor
+pub const DEPOSITS: Map<(u64, &Addr), Vec<Asset>> = Map::new("deposits");
+
+// Partial fills of an agreement, keyed by agreement ID and the index of the fill
+pub const FILLS: Map<(u64, u32), Fill> = Map::new("fills");
+
+// Pending requests to cancel accepted agreements, keyed by agreement ID to the requesting party
+pub const CANCEL_REQUESTS: Map<u64, Addr> = Map::new("cancel_requests");
+
+// Negotiation history of an agreement, keyed by agreement ID and round
+pub const COUNTER_OFFERS: Map<(u64, u32), CounterOffer> = Map::new("counter_offers");
+
+// Withdrawable balances backing signed offers, keyed by owner and bank denom or token address
+pub const BALANCES: Map<(&Addr, &str), Asset> = Map::new("balances");
+
+// Digests of accepted signed offers and the agreements they settled, to prevent replays
+pub const USED_OFFERS: Map<&[u8], u64> = Map::new("used_offers");
+
+// Agreement ID recorded in USED_OFFERS for revoked offers, agreements are numbered from 1
+pub const REVOKED_OFFER_ID: u64 = 0;
+
+// Agreements between several participants, numbered separately from two-party agreements
+pub const MULTI_PARTY_AGREEMENTS: Map<u64, MultiPartyAgreement> =
+    Map::new("multi_party_agreements");
+pub const MULTI_PARTY_AGREEMENT_COUNT: Item<u64> = Item::new("multi_party_agreement_count");
+
+// Escrow ledger of multi-party agreements, keyed by agreement ID and participant
+pub const MULTI_PARTY_DEPOSITS: Map<(u64, &Addr), Vec<Asset>> = Map::new("multi_party_deposits");
+
+// Number of agreements returned by list queries without a limit, and the maximum limit
+pub const DEFAULT_LIMIT: u32 = 10;
+pub const MAX_LIMIT: u32 = 30;
 
 // Query functions for agreements
 
@@ -34,6 +536,253 @@ pub fn query_agreement(deps: Deps, id: u64) -> StdResult<AgreementResponse> {
     Ok(AgreementResponse { agreement })
 }
 
+/// Queries the owner of the contract and the pending owner of an ownership transfer.
+pub fn query_ownership(deps: Deps) -> StdResult<OwnershipResponse> {
+    let ownership = OWNERSHIP.load(deps.storage)?;
+    Ok(OwnershipResponse {
+        owner: ownership.owner,
+        pending_owner: ownership.pending_owner,
+    })
+}
+
+/// Queries the contract config.
+pub fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    Ok(ConfigResponse { config })
+}
+
+/// Queries the operations that are currently paused.
+pub fn query_paused(deps: Deps) -> StdResult<PausedResponse> {
+    let paused = PAUSED.may_load(deps.storage)?.unwrap_or_default();
+    Ok(PausedResponse { paused })
+}
+
+/// Queries the rule of a bank denom or token contract address, if it has one.
+pub fn query_asset_rule(deps: Deps, token: String) -> StdResult<AssetRuleResponse> {
+    let rule = ASSET_RULES.may_load(deps.storage, &token)?;
+    Ok(AssetRuleResponse { token, rule })
+}
+
+/// Queries a page of the asset rules ordered by token, together with the asset list mode.
+pub fn query_asset_rules(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<AssetRulesResponse> {
+    let mode = ASSET_LIST_MODE.may_load(deps.storage)?.unwrap_or_default();
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let rules = ASSET_RULES
+        .range(
+            deps.storage,
+            start_after.as_deref().map(Bound::exclusive),
+            None,
+            Order::Ascending,
+        )
+        .take(limit)
+        .map(|item| {
+            item.map(|(token, rule)| AssetRuleResponse {
+                token,
+                rule: Some(rule),
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+    let next_start_after = if rules.len() == limit {
+        rules.last().map(|rule| rule.token.clone())
+    } else {
+        None
+    };
+    Ok(AssetRulesResponse {
+        mode,
+        rules,
+        next_start_after,
+    })
+}
+
+/// Queries the total fees collected in a bank denom or CW20 contract address.
+pub fn query_accrued_fees(deps: Deps, denom: String) -> StdResult<AccruedFeesResponse> {
+    let amount = ACCRUED_FEES
+        .may_load(deps.storage, &denom)?
+        .unwrap_or_default();
+    Ok(AccruedFeesResponse { denom, amount })
+}
+
+/// Queries the withdrawable balances of an address.
+pub fn query_balances(deps: Deps, owner: Addr) -> StdResult<BalancesResponse> {
+    let balances = BALANCES
+        .prefix(&owner)
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, asset)| asset))
+        .collect::<StdResult<_>>()?;
+    Ok(BalancesResponse { owner, balances })
+}
+
+/// Queries the digest to sign for an offer and whether it has been accepted.
+pub fn query_signed_offer(
+    deps: Deps,
+    env: Env,
+    offer: SignedOffer,
+) -> StdResult<SignedOfferResponse> {
+    let digest = signed_offer_digest(&env, &offer)?;
+    let used = USED_OFFERS.may_load(deps.storage, &digest)?;
+    Ok(SignedOfferResponse {
+        digest: HexBinary::from(digest),
+        agreement_id: used.filter(|id| *id != REVOKED_OFFER_ID),
+        revoked: used == Some(REVOKED_OFFER_ID),
+    })
+}
+
+/// Queries the assets escrowed for an agreement, grouped by depositor.
+pub fn query_agreement_deposits(deps: Deps, id: u64) -> StdResult<AgreementDepositsResponse> {
+    let deposits = DEPOSITS
+        .prefix(id)
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(depositor, assets)| AgreementDeposit { depositor, assets }))
+        .collect::<StdResult<Vec<_>>>()?;
+    Ok(AgreementDepositsResponse { deposits })
+}
+
+/// Queries a multi-party agreement together with the assets each participant has deposited and
+/// the participants whose deposit is still incomplete.
+pub fn query_multi_party_agreement(deps: Deps, id: u64) -> StdResult<MultiPartyAgreementResponse> {
+    let agreement = MULTI_PARTY_AGREEMENTS.load(deps.storage, id)?;
+    let deposits = MULTI_PARTY_DEPOSITS
+        .prefix(id)
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(depositor, assets)| AgreementDeposit { depositor, assets }))
+        .collect::<StdResult<Vec<_>>>()?;
+    let pending = agreement
+        .participants
+        .iter()
+        .filter(|participant| {
+            let escrowed = deposits
+                .iter()
+                .find(|deposit| deposit.depositor == participant.address)
+                .map(|deposit| deposit.assets.as_slice())
+                .unwrap_or_default();
+            !outstanding_assets(escrowed, &participant.deposit).is_empty()
+        })
+        .map(|participant| participant.address.clone())
+        .collect();
+    Ok(MultiPartyAgreementResponse {
+        agreement,
+        deposits,
+        pending,
+    })
+}
+
+/// Queries the cancellation policy of an agreement and the party requesting to cancel it while
+/// it is accepted.
+pub fn query_cancel_request(deps: Deps, id: u64) -> StdResult<CancelRequestResponse> {
+    let agreement = AGREEMENTS.load(deps.storage, id)?;
+    let requested_by = match agreement.status {
+        AgreementStatus::Accepted => CANCEL_REQUESTS.may_load(deps.storage, id)?,
+        _ => None,
+    };
+    Ok(CancelRequestResponse {
+        cancel_policy: agreement.cancel_policy,
+        requested_by,
+    })
+}
+
+/// Queries the milestones of an agreement and how much of them has been released.
+pub fn query_milestones(deps: Deps, id: u64) -> StdResult<MilestonesResponse> {
+    let agreement = AGREEMENTS.load(deps.storage, id)?;
+    let released = agreement
+        .milestones
+        .iter()
+        .filter(|milestone| milestone.released)
+        .count() as u32;
+    let released_amount = agreement
+        .milestones
+        .iter()
+        .filter(|milestone| milestone.released)
+        .map(|milestone| milestone.amount)
+        .sum();
+    Ok(MilestonesResponse {
+        milestones: agreement.milestones,
+        released,
+        released_amount,
+    })
+}
+
+/// Queries a page of the negotiation rounds of an agreement.
+pub fn query_counter_offers(
+    deps: Deps,
+    id: u64,
+    start_after: Option<u32>,
+    limit: Option<u32>,
+) -> StdResult<CounterOffersResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let counter_offers = COUNTER_OFFERS
+        .prefix(id)
+        .range(
+            deps.storage,
+            start_after.map(Bound::exclusive),
+            None,
+            Order::Ascending,
+        )
+        .take(limit)
+        .map(|item| {
+            item.map(|(round, offer)| AgreementCounterOffer {
+                round,
+                proposer: offer.proposer,
+                initiator_token: offer.initiator_token,
+                counterparty_token: offer.counterparty_token,
+                status: offer.status,
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+    let next_start_after = if counter_offers.len() == limit {
+        counter_offers.last().map(|offer| offer.round)
+    } else {
+        None
+    };
+    Ok(CounterOffersResponse {
+        counter_offers,
+        next_start_after,
+    })
+}
+
+/// Queries a page of the partial fills of an agreement and the initiator token left to fill.
+pub fn query_fills(
+    deps: Deps,
+    id: u64,
+    start_after: Option<u32>,
+    limit: Option<u32>,
+) -> StdResult<FillsResponse> {
+    let agreement = AGREEMENTS.load(deps.storage, id)?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let fills = FILLS
+        .prefix(id)
+        .range(
+            deps.storage,
+            start_after.map(Bound::exclusive),
+            None,
+            Order::Ascending,
+        )
+        .take(limit)
+        .map(|item| {
+            item.map(|(index, fill)| AgreementFill {
+                index,
+                filler: fill.filler,
+                amount: fill.amount,
+                payout: fill.payout,
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+    let next_start_after = if fills.len() == limit {
+        fills.last().map(|fill| fill.index)
+    } else {
+        None
+    };
+    Ok(FillsResponse {
+        fills,
+        filled: agreement.filled,
+        remaining: agreement.remaining(),
+        next_start_after,
+    })
+}
+
 /// Queries total agreement count.
 pub fn query_total_agreement_count(deps: Deps) -> StdResult<TotalAgreementCountResponse> {
     let total_agreement_count = TOTAL_AGREEMENT_COUNT.load(deps.storage)?;
@@ -74,71 +823,131 @@ pub fn query_canceled_agreement_count(deps: Deps) -> StdResult<CanceledAgreement
     })
 }
 
-/// Queries agreements initiated by a specific address within a given range.
+/// Queries expired agreement count.
+pub fn query_expired_agreement_count(deps: Deps) -> StdResult<ExpiredAgreementCountResponse> {
+    let expired_agreement_count = EXPIRED_AGREEMENT_COUNT.load(deps.storage)?;
+    Ok(ExpiredAgreementCountResponse {
+        expired_agreement_count,
+    })
+}
+
+/// Queries disputed agreement count.
+pub fn query_disputed_agreement_count(deps: Deps) -> StdResult<DisputedAgreementCountResponse> {
+    let disputed_agreement_count = DISPUTED_AGREEMENT_COUNT.load(deps.storage)?;
+    Ok(DisputedAgreementCountResponse {
+        disputed_agreement_count,
+    })
+}
+
+/// Queries resolved agreement count.
+pub fn query_resolved_agreement_count(deps: Deps) -> StdResult<ResolvedAgreementCountResponse> {
+    let resolved_agreement_count = RESOLVED_AGREEMENT_COUNT.load(deps.storage)?;
+    Ok(ResolvedAgreementCountResponse {
+        resolved_agreement_count,
+    })
+}
+
+/// Queries rejected agreement count.
+pub fn query_rejected_agreement_count(deps: Deps) -> StdResult<RejectedAgreementCountResponse> {
+    let rejected_agreement_count = REJECTED_AGREEMENT_COUNT.load(deps.storage)?;
+    Ok(RejectedAgreementCountResponse {
+        rejected_agreement_count,
+    })
+}
+
+/// Collects up to `limit` agreements, returning the cursor of the next page if it may hold more.
+fn page_agreements(
+    agreements: impl Iterator<Item = StdResult<(u64, Agreement)>>,
+    limit: Option<u32>,
+) -> StdResult<AgreementsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let agreements = agreements
+        .take(limit)
+        .map(|item| item.map(|(_, agreement)| agreement))
+        .collect::<StdResult<Vec<_>>>()?;
+    let next_start_after = if agreements.len() == limit {
+        agreements.last().map(|agreement| agreement.id)
+    } else {
+        None
+    };
+
+    Ok(AgreementsResponse {
+        agreements,
+        next_start_after,
+    })
+}
+
+/// Queries a page of the agreements initiated by a specific address.
 pub fn query_agreements_by_initiator(
     deps: Deps,
     initiator: Addr,
-    start_after: u64,
-    end_before: u64,
+    start_after: Option<u64>,
+    limit: Option<u32>,
 ) -> StdResult<AgreementsResponse> {
-    let agreements: Vec<Agreement> = AGREEMENTS
-        .range(
-            deps.storage,
-            start_after.inclusive_bound(),
-            end_before.inclusive_bound(),
-            Order::Ascending,
-        )
-        .filter_map(|item| match item {
-            Ok((_, agreement)) if agreement.initiator == initiator => Some(agreement),
-            _ => None,
-        })
-        .collect();
+    let agreements = AGREEMENTS.idx.initiator.prefix(initiator).range(
+        deps.storage,
+        start_after.map(Bound::exclusive),
+        None,
+        Order::Ascending,
+    );
 
-    Ok(AgreementsResponse { agreements })
+    page_agreements(agreements, limit)
 }
 
-/// Queries agreements involving a specific counterparty address within a given range.
+/// Queries a page of the agreements involving a specific counterparty address.
 pub fn query_agreements_by_counterparty(
     deps: Deps,
     counterparty: Addr,
-    start_after: u64,
-    end_before: u64,
+    start_after: Option<u64>,
+    limit: Option<u32>,
 ) -> StdResult<AgreementsResponse> {
-    let agreements: Vec<Agreement> = AGREEMENTS
+    let agreements = AGREEMENTS
+        .idx
+        .counterparty
+        .prefix(counterparty.to_string())
         .range(
             deps.storage,
-            start_after.inclusive_bound(),
-            end_before.inclusive_bound(),
+            start_after.map(Bound::exclusive),
+            None,
             Order::Ascending,
-        )
-        .filter_map(|item| match item {
-            Ok((_, agreement)) if agreement.counterparty == counterparty => Some(agreement),
-            _ => None,
-        })
-        .collect();
+        );
 
-    Ok(AgreementsResponse { agreements })
+    page_agreements(agreements, limit)
 }
 
-/// Queries agreements with a specific status within a given range.
+/// Queries a page of the agreements with a specific status.
 pub fn query_agreements_by_status(
     deps: Deps,
-    status: String,
-    start_after: u64,
-    end_before: u64,
+    status: AgreementStatus,
+    start_after: Option<u64>,
+    limit: Option<u32>,
 ) -> StdResult<AgreementsResponse> {
-    let agreements: Vec<Agreement> = AGREEMENTS
+    let agreements = AGREEMENTS.idx.status.prefix(status.to_string()).range(
+        deps.storage,
+        start_after.map(Bound::exclusive),
+        None,
+        Order::Ascending,
+    );
+
+    page_agreements(agreements, limit)
+}
+
+/// Queries a page of the open offers that anyone may still accept.
+pub fn query_open_offers(
+    deps: Deps,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<AgreementsResponse> {
+    let agreements = AGREEMENTS
+        .idx
+        .open_offers
+        .prefix(AgreementStatus::Initiated.to_string())
         .range(
             deps.storage,
-            start_after.inclusive_bound(),
-            end_before.inclusive_bound(),
+            start_after.map(Bound::exclusive),
+            None,
             Order::Ascending,
-        )
-        .filter_map(|item| match item {
-            Ok((_, agreement)) if agreement.status == status => Some(agreement),
-            _ => None,
-        })
-        .collect();
+        );
 
-    Ok(AgreementsResponse { agreements })
+    page_agreements(agreements, limit)
 }
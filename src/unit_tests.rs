@@ -1,16 +1,45 @@
 #[cfg(test)]
 mod tests {
-    use crate::contract::{
-        execute, instantiate, query, STATUS_ACCEPTED, STATUS_CANCELED, STATUS_EXECUTED,
-        STATUS_INITIATED,
+    use crate::asset::{nft_transfer_msg, Asset};
+    use crate::contract::{execute, instantiate, migrate, query};
+    use crate::helpers::{
+        addr, check_agreement_counts, initialize_contract, initiate, initiate_new_agreement,
+        InitiateAgreement,
     };
-    use crate::helpers::{check_agreement_counts, initialize_contract, initiate_new_agreement};
     use crate::msg::{
-        AgreementResponse, AgreementsResponse, ExecuteMsg, InstantiateMsg, QueryMsg, TokenInfo,
+        AccruedFeesResponse, AgreementDeposit, AgreementDepositsResponse, AgreementFill,
+        AgreementResponse, AgreementsResponse, AssetRuleResponse, AssetRulesResponse,
+        BalancesResponse, CancelRequestResponse, ConfigResponse, CounterOffersResponse,
+        DisputePayout, ExecuteMsg, ExpiredAgreementCountResponse, FillsResponse, InstantiateMsg,
+        MigrateMsg, MilestonesResponse, MultiPartyAgreementResponse, OwnershipResponse,
+        PausedResponse, QueryMsg, ReceiveMsg, RejectedAgreementCountResponse,
+        ResolvedAgreementCountResponse, SignedOffer, SignedOfferResponse,
     };
+    use crate::state::{
+        AgreementStatus, AssetListMode, AssetRule, CancelPenalty, CancelPolicy, Config,
+        CounterOfferStatus, Dispute, Htlc, Participant, PauseFlags, Rejection,
+        ACCEPTED_AGREEMENT_COUNT, AGREEMENTS, CANCEL_REQUESTS, CONFIG, DEFAULT_LIMIT, DEPOSITS,
+        EXPIRED_AGREEMENT_COUNT, INITIATED_AGREEMENT_COUNT, MAX_LIMIT, OWNERSHIP,
+        TOTAL_AGREEMENT_COUNT,
+    };
+    use crate::utils::{fill_payout, signed_offer_digest};
     use crate::ContractError;
-    use cosmwasm_std::testing::{message_info, mock_dependencies_with_balances, mock_env};
-    use cosmwasm_std::{coin, coins, from_json, Addr, Attribute};
+    use cosmwasm_std::testing::{
+        message_info, mock_dependencies_with_balances, mock_env, MockApi, MockQuerier, MockStorage,
+    };
+    use cosmwasm_std::{
+        coin, coins, from_json, to_json_binary, Addr, Api, Attribute, BankMsg, Binary,
+        CanonicalAddr, Coin, Env, HexBinary, Order, OwnedDeps, Response, StdResult, Storage,
+        SubMsg, Uint128,
+    };
+    use cw20::Cw20ReceiveMsg;
+    use cw721::receiver::Cw721ReceiveMsg;
+    use cw_utils::Expiration;
+    use k256::ecdsa::signature::hazmat::PrehashSigner;
+    use k256::ecdsa::{Signature, SigningKey};
+    use proptest::prelude::*;
+    use ripemd::Ripemd160;
+    use sha2::{Digest, Sha256};
 
     #[test]
     fn contract_initialization() {
@@ -30,13 +59,10 @@ mod tests {
         let value: AgreementResponse = from_json(&res).unwrap();
         assert_eq!(value.agreement.id, 1);
         assert_eq!(value.agreement.initiator, Addr::unchecked("initiator"));
-        assert_eq!(
-            value.agreement.counterparty,
-            Addr::unchecked("counterparty")
-        );
+        assert_eq!(value.agreement.counterparty, Some(addr("counterparty")));
         assert_eq!(value.agreement.initiator_token, initiator_token);
         assert_eq!(value.agreement.counterparty_token, counterparty_token);
-        assert_eq!(value.agreement.status, STATUS_INITIATED);
+        assert_eq!(value.agreement.status, AgreementStatus::Initiated);
     }
 
     #[test]
@@ -47,7 +73,7 @@ mod tests {
             initiate_new_agreement(&mut deps, "initiator", 1000, "counterparty", 2000);
 
         let msg = ExecuteMsg::AcceptAgreement { id: 1 };
-        let info = message_info(&Addr::unchecked("counterparty"), &coins(2000, "tokenB"));
+        let info = message_info(&addr("counterparty"), &coins(2000, "tokenB"));
         let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
 
         assert_eq!(
@@ -63,7 +89,7 @@ mod tests {
                 },
                 Attribute {
                     key: "status".to_string(),
-                    value: STATUS_ACCEPTED.to_string()
+                    value: AgreementStatus::Accepted.to_string()
                 },
                 Attribute {
                     key: "initiator".to_string(),
@@ -71,15 +97,15 @@ mod tests {
                 },
                 Attribute {
                     key: "counterparty".to_string(),
-                    value: "counterparty".to_string()
+                    value: addr("counterparty").to_string()
                 },
                 Attribute {
                     key: "initiator_token".to_string(),
-                    value: "1000tokenA".to_string()
+                    value: "native:tokenA:1000".to_string()
                 },
                 Attribute {
                     key: "counterparty_token".to_string(),
-                    value: "2000tokenB".to_string()
+                    value: "native:tokenB:2000".to_string()
                 }
             ]
         );
@@ -91,33 +117,33 @@ mod tests {
         let value: AgreementResponse = from_json(&res).unwrap();
         assert_eq!(value.agreement.id, 1);
         assert_eq!(value.agreement.initiator, Addr::unchecked("initiator"));
-        assert_eq!(
-            value.agreement.counterparty,
-            Addr::unchecked("counterparty")
-        );
+        assert_eq!(value.agreement.counterparty, Some(addr("counterparty")));
         assert_eq!(value.agreement.initiator_token, initiator_token);
         assert_eq!(value.agreement.counterparty_token, counterparty_token);
-        assert_eq!(value.agreement.status, STATUS_ACCEPTED);
+        assert_eq!(value.agreement.status, AgreementStatus::Accepted);
     }
 
     #[test]
     fn incorrect_funds_initiate_agreement() {
         let mut deps = initialize_contract();
 
-        let initiator_token = TokenInfo {
-            address: Addr::unchecked("tokenA"),
-            amount: 1000u128,
-        };
-        let counterparty_token = TokenInfo {
-            address: Addr::unchecked("tokenB"),
-            amount: 2000u128,
-        };
-        let counterparty = Addr::unchecked("counterparty");
+        let initiator_token = Asset::native("tokenA", 1000u128);
+        let counterparty_token = Asset::native("tokenB", 2000u128);
+        let counterparty = addr("counterparty");
 
         let msg = ExecuteMsg::InitiateAgreement {
             initiator_token: initiator_token.clone(),
             counterparty_token: counterparty_token.clone(),
-            counterparty: counterparty.clone(),
+            counterparty: Some(counterparty.clone()),
+            expires: None,
+            arbiter: None,
+            arbiter_fee_bps: None,
+            milestones: None,
+            htlc: None,
+            cancel_policy: None,
+            cancel_penalty: None,
+            initiator_assets: None,
+            counterparty_assets: None,
         };
         let info = message_info(&Addr::unchecked("initiator"), &coins(500, "tokenA")); // Insufficient funds
         let res = execute(deps.as_mut(), mock_env(), info.clone(), msg);
@@ -136,27 +162,30 @@ mod tests {
     fn insufficient_funds_initiate_agreement() {
         let mut deps = initialize_contract();
 
-        let initiator_token = TokenInfo {
-            address: Addr::unchecked("tokenA"),
-            amount: 1000u128,
-        };
-        let counterparty_token = TokenInfo {
-            address: Addr::unchecked("tokenB"),
-            amount: 2000u128,
-        };
-        let counterparty = Addr::unchecked("counterparty");
+        let initiator_token = Asset::native("tokenA", 1000u128);
+        let counterparty_token = Asset::native("tokenB", 2000u128);
+        let counterparty = addr("counterparty");
 
         let msg = ExecuteMsg::InitiateAgreement {
             initiator_token: initiator_token.clone(),
             counterparty_token: counterparty_token.clone(),
-            counterparty: counterparty.clone(),
+            counterparty: Some(counterparty.clone()),
+            expires: None,
+            arbiter: None,
+            arbiter_fee_bps: None,
+            milestones: None,
+            htlc: None,
+            cancel_policy: None,
+            cancel_penalty: None,
+            initiator_assets: None,
+            counterparty_assets: None,
         };
         let info = message_info(&Addr::unchecked("initiator"), &[]); // Insufficient funds
         let res = execute(deps.as_mut(), mock_env(), info.clone(), msg);
 
         assert!(res.is_err());
         match res.err().unwrap() {
-            ContractError::InsufficientFunds {} => {}
+            ContractError::InsufficientFunds => {}
             _ => panic!("Unexpected error"),
         }
     }
@@ -165,20 +194,23 @@ mod tests {
     fn unexpected_funds_initiate_agreement() {
         let mut deps = initialize_contract();
 
-        let initiator_token = TokenInfo {
-            address: Addr::unchecked("tokenA"),
-            amount: 1000u128,
-        };
-        let counterparty_token = TokenInfo {
-            address: Addr::unchecked("tokenB"),
-            amount: 2000u128,
-        };
-        let counterparty = Addr::unchecked("counterparty");
+        let initiator_token = Asset::native("tokenA", 1000u128);
+        let counterparty_token = Asset::native("tokenB", 2000u128);
+        let counterparty = addr("counterparty");
 
         let msg = ExecuteMsg::InitiateAgreement {
             initiator_token: initiator_token.clone(),
             counterparty_token: counterparty_token.clone(),
-            counterparty: counterparty.clone(),
+            counterparty: Some(counterparty.clone()),
+            expires: None,
+            arbiter: None,
+            arbiter_fee_bps: None,
+            milestones: None,
+            htlc: None,
+            cancel_policy: None,
+            cancel_penalty: None,
+            initiator_assets: None,
+            counterparty_assets: None,
         };
         let info = message_info(&Addr::unchecked("initiator"), &coins(1000, "someToken"));
         let res = execute(deps.as_mut(), mock_env(), info.clone(), msg);
@@ -194,23 +226,272 @@ mod tests {
     }
 
     #[test]
-    fn insufficient_funds_accept_agreement() {
+    fn native_funds_for_cw20_token_initiate_agreement() {
+        let mut deps = initialize_contract();
+        let cw20_contract = deps.api.addr_make("cw20-contract");
+
+        let msg = ExecuteMsg::InitiateAgreement {
+            initiator_token: Asset::cw20(cw20_contract.clone(), 1000u128),
+            counterparty_token: Asset::native("tokenB", 2000u128),
+            counterparty: Some(addr("counterparty")),
+            expires: None,
+            arbiter: None,
+            arbiter_fee_bps: None,
+            milestones: None,
+            htlc: None,
+            cancel_policy: None,
+            cancel_penalty: None,
+            initiator_assets: None,
+            counterparty_assets: None,
+        };
+        let info = message_info(&Addr::unchecked("initiator"), &coins(1000, "tokenA"));
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg);
+
+        match res.err().unwrap() {
+            ContractError::UnexpectedFunds { expected, found } => {
+                assert_eq!(expected, cw20_contract.to_string());
+                assert_eq!(found, "tokenA");
+            }
+            _ => panic!("Unexpected error"),
+        }
+    }
+
+    #[test]
+    fn cw20_deposit_initiate_agreement() {
+        let mut deps = initialize_contract();
+        let initiator = deps.api.addr_make("initiator");
+        let cw20_contract = deps.api.addr_make("cw20-contract");
+
+        let initiator_token = Asset::cw20(cw20_contract.clone(), 1000u128);
+        let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: initiator.to_string(),
+            amount: Uint128::new(1000),
+            msg: to_json_binary(&ReceiveMsg::InitiateAgreement {
+                initiator_token: initiator_token.clone(),
+                counterparty_token: Asset::native("tokenB", 2000u128),
+                counterparty: Some(addr("counterparty")),
+                expires: None,
+                arbiter: None,
+                arbiter_fee_bps: None,
+                milestones: None,
+                htlc: None,
+                cancel_policy: None,
+                cancel_penalty: None,
+                initiator_assets: None,
+                counterparty_assets: None,
+            })
+            .unwrap(),
+        });
+        let info = message_info(&cw20_contract, &[]);
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        check_agreement_counts(&deps, 1, 1, 0, 0, 0);
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetAgreement { id: 1 }).unwrap();
+        let value: AgreementResponse = from_json(&res).unwrap();
+        assert_eq!(value.agreement.initiator, initiator);
+        assert_eq!(value.agreement.initiator_token, initiator_token);
+        assert_eq!(value.agreement.status, AgreementStatus::Initiated);
+    }
+
+    #[test]
+    fn nft_deposit_initiate_agreement() {
+        let mut deps = initialize_contract();
+        let initiator = deps.api.addr_make("initiator");
+        let collection = deps.api.addr_make("cw721-contract");
+
+        let initiator_token = Asset::cw721(collection.clone(), &["1", "2"]);
+        let msg = ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: initiator.to_string(),
+            token_id: "1".to_string(),
+            msg: to_json_binary(&ReceiveMsg::InitiateAgreement {
+                initiator_token: initiator_token.clone(),
+                counterparty_token: Asset::native("tokenB", 2000u128),
+                counterparty: Some(addr("counterparty")),
+                expires: None,
+                arbiter: None,
+                arbiter_fee_bps: None,
+                milestones: None,
+                htlc: None,
+                cancel_policy: None,
+                cancel_penalty: None,
+                initiator_assets: None,
+                counterparty_assets: None,
+            })
+            .unwrap(),
+        });
+        let info = message_info(&collection, &[]);
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        check_agreement_counts(&deps, 1, 1, 0, 0, 0);
+
+        // The counterparty cannot accept while the second NFT is not escrowed yet
+        let accept_msg = ExecuteMsg::AcceptAgreement { id: 1 };
+        let accept_info = message_info(&addr("counterparty"), &coins(2000, "tokenB"));
+        let res = execute(deps.as_mut(), mock_env(), accept_info, accept_msg);
+
+        match res.err().unwrap() {
+            ContractError::IncompleteDeposit { asset } => {
+                assert_eq!(asset, initiator_token.to_string());
+            }
+            _ => panic!("Unexpected error"),
+        }
+    }
+
+    #[test]
+    fn nft_deposit_from_wrong_sender() {
+        let mut deps = initialize_contract();
+        let initiator = deps.api.addr_make("initiator");
+        let collection = deps.api.addr_make("cw721-contract");
+
+        let msg = ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: initiator.to_string(),
+            token_id: "1".to_string(),
+            msg: to_json_binary(&ReceiveMsg::InitiateAgreement {
+                initiator_token: Asset::cw721(collection.clone(), &["1", "2"]),
+                counterparty_token: Asset::native("tokenB", 2000u128),
+                counterparty: Some(addr("counterparty")),
+                expires: None,
+                arbiter: None,
+                arbiter_fee_bps: None,
+                milestones: None,
+                htlc: None,
+                cancel_policy: None,
+                cancel_penalty: None,
+                initiator_assets: None,
+                counterparty_assets: None,
+            })
+            .unwrap(),
+        });
+        let info = message_info(&collection, &[]);
+        execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        // Only the initiator can escrow the remaining NFTs of the initiator token
+        let msg = ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: deps.api.addr_make("someone-else").to_string(),
+            token_id: "2".to_string(),
+            msg: to_json_binary(&ReceiveMsg::DepositNft { id: 1 }).unwrap(),
+        });
+        let res = execute(deps.as_mut(), mock_env(), info, msg);
+
+        match res.err().unwrap() {
+            ContractError::Unauthorized { expected, .. } => {
+                assert_eq!(expected, initiator.to_string());
+            }
+            _ => panic!("Unexpected error"),
+        }
+    }
+
+    #[test]
+    fn invalid_asset_initiate_agreement() {
+        let mut deps = initialize_contract();
+
+        let msg = ExecuteMsg::InitiateAgreement {
+            initiator_token: Asset::native("tokenA", 1000u128),
+            counterparty_token: Asset::native("tokenB", 0u128),
+            counterparty: Some(addr("counterparty")),
+            expires: None,
+            arbiter: None,
+            arbiter_fee_bps: None,
+            milestones: None,
+            htlc: None,
+            cancel_policy: None,
+            cancel_penalty: None,
+            initiator_assets: None,
+            counterparty_assets: None,
+        };
+        let info = message_info(&Addr::unchecked("initiator"), &coins(1000, "tokenA"));
+        let res = execute(deps.as_mut(), mock_env(), info, msg);
+
+        match res.err().unwrap() {
+            ContractError::InvalidAsset { asset, reason } => {
+                assert_eq!(asset, "native:tokenB:0");
+                assert_eq!(reason, "amount must be greater than zero");
+            }
+            _ => panic!("Unexpected error"),
+        }
+    }
+
+    #[test]
+    fn invalid_counterparty_initiate_agreement() {
         let mut deps = initialize_contract();
 
-        let initiator_token = TokenInfo {
-            address: Addr::unchecked("tokenA"),
-            amount: 1000u128,
+        let msg = ExecuteMsg::InitiateAgreement {
+            initiator_token: Asset::native("tokenA", 1000u128),
+            counterparty_token: Asset::native("tokenB", 2000u128),
+            counterparty: Some(Addr::unchecked("Counterparty")),
+            expires: None,
+            arbiter: None,
+            arbiter_fee_bps: None,
+            milestones: None,
+            htlc: None,
+            cancel_policy: None,
+            cancel_penalty: None,
+            initiator_assets: None,
+            counterparty_assets: None,
         };
-        let counterparty_token = TokenInfo {
-            address: Addr::unchecked("tokenB"),
-            amount: 2000u128,
+        let info = message_info(&Addr::unchecked("initiator"), &coins(1000, "tokenA"));
+        let res = execute(deps.as_mut(), mock_env(), info, msg);
+
+        assert!(matches!(res.err().unwrap(), ContractError::Std(_)));
+        check_agreement_counts(&deps, 0, 0, 0, 0, 0);
+    }
+
+    #[test]
+    fn unsupported_asset_initiate_agreement() {
+        let mut deps = initialize_contract();
+        let cw1155_contract = deps.api.addr_make("cw1155-contract");
+
+        let msg = ExecuteMsg::InitiateAgreement {
+            initiator_token: Asset::native("tokenA", 1000u128),
+            counterparty_token: Asset::Cw1155 {
+                address: cw1155_contract.clone(),
+                token_id: "sword".to_string(),
+                amount: Uint128::new(5),
+            },
+            counterparty: Some(addr("counterparty")),
+            expires: None,
+            arbiter: None,
+            arbiter_fee_bps: None,
+            milestones: None,
+            htlc: None,
+            cancel_policy: None,
+            cancel_penalty: None,
+            initiator_assets: None,
+            counterparty_assets: None,
         };
-        let counterparty = Addr::unchecked("counterparty");
+        let info = message_info(&Addr::unchecked("initiator"), &coins(1000, "tokenA"));
+        let res = execute(deps.as_mut(), mock_env(), info, msg);
+
+        match res.err().unwrap() {
+            ContractError::UnsupportedAsset { asset } => {
+                assert_eq!(asset, format!("cw1155:{}:sword:5", cw1155_contract));
+            }
+            _ => panic!("Unexpected error"),
+        }
+    }
+
+    #[test]
+    fn insufficient_funds_accept_agreement() {
+        let mut deps = initialize_contract();
+
+        let initiator_token = Asset::native("tokenA", 1000u128);
+        let counterparty_token = Asset::native("tokenB", 2000u128);
+        let counterparty = addr("counterparty");
 
         let msg = ExecuteMsg::InitiateAgreement {
             initiator_token: initiator_token.clone(),
             counterparty_token: counterparty_token.clone(),
-            counterparty: counterparty.clone(),
+            counterparty: Some(counterparty.clone()),
+            expires: None,
+            arbiter: None,
+            arbiter_fee_bps: None,
+            milestones: None,
+            htlc: None,
+            cancel_policy: None,
+            cancel_penalty: None,
+            initiator_assets: None,
+            counterparty_assets: None,
         };
         let info = message_info(&Addr::unchecked("initiator"), &coins(1000, "tokenA"));
         let res = execute(deps.as_mut(), mock_env(), info.clone(), msg);
@@ -219,12 +500,12 @@ mod tests {
 
         // Accept the agreement
         let accept_msg = ExecuteMsg::AcceptAgreement { id: 1 };
-        let accept_info = message_info(&Addr::unchecked("counterparty"), &[]);
+        let accept_info = message_info(&addr("counterparty"), &[]);
         let res = execute(deps.as_mut(), mock_env(), accept_info.clone(), accept_msg);
 
         assert!(res.is_err());
         match res.err().unwrap() {
-            ContractError::InsufficientFunds {} => {}
+            ContractError::InsufficientFunds => {}
             _ => panic!("Unexpected error"),
         }
     }
@@ -236,7 +517,7 @@ mod tests {
 
         // Accept the agreement
         let accept_msg = ExecuteMsg::AcceptAgreement { id: 1 };
-        let accept_info = message_info(&Addr::unchecked("counterparty"), &coins(2000, "someToken"));
+        let accept_info = message_info(&addr("counterparty"), &coins(2000, "someToken"));
         let res = execute(deps.as_mut(), mock_env(), accept_info.clone(), accept_msg);
 
         assert!(res.is_err());
@@ -266,13 +547,150 @@ mod tests {
         assert!(res.is_err());
         match res.err().unwrap() {
             ContractError::Unauthorized { expected, found } => {
-                assert_eq!(expected, "counterparty");
+                assert_eq!(expected, addr("counterparty").as_str());
                 assert_eq!(found, "some-other-counterparty");
             }
             _ => panic!("Unexpected error"),
         }
     }
 
+    fn initiate_open_offer(deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>) {
+        let msg = ExecuteMsg::InitiateAgreement {
+            initiator_token: Asset::native("tokenA", 1000u128),
+            counterparty_token: Asset::native("tokenB", 2000u128),
+            counterparty: None,
+            expires: None,
+            arbiter: None,
+            arbiter_fee_bps: None,
+            milestones: None,
+            htlc: None,
+            cancel_policy: None,
+            cancel_penalty: None,
+            initiator_assets: None,
+            counterparty_assets: None,
+        };
+        let info = message_info(&Addr::unchecked("initiator"), &coins(1000, "tokenA"));
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert!(res
+            .attributes
+            .contains(&Attribute::new("counterparty", "open")));
+    }
+
+    #[test]
+    fn accept_open_offer() {
+        let mut deps = initialize_contract();
+
+        initiate_open_offer(&mut deps);
+
+        // A sender whose funds do not satisfy the counterparty token cannot take the offer
+        let accept_msg = ExecuteMsg::AcceptAgreement { id: 1 };
+        let info = message_info(&Addr::unchecked("taker"), &coins(1000, "tokenB"));
+        let res = execute(deps.as_mut(), mock_env(), info, accept_msg.clone());
+        assert!(matches!(
+            res.unwrap_err(),
+            ContractError::IncorrectFundsAmount { .. }
+        ));
+
+        // The first sender with matching funds becomes the counterparty
+        let info = message_info(&Addr::unchecked("taker"), &coins(2000, "tokenB"));
+        let res = execute(deps.as_mut(), mock_env(), info, accept_msg.clone()).unwrap();
+        assert!(res
+            .attributes
+            .contains(&Attribute::new("counterparty", "taker")));
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetAgreement { id: 1 }).unwrap();
+        let value: AgreementResponse = from_json(&res).unwrap();
+        assert_eq!(value.agreement.counterparty, Some(Addr::unchecked("taker")));
+        assert_eq!(value.agreement.status, AgreementStatus::Accepted);
+
+        // Later takers are no longer the counterparty
+        let info = message_info(&Addr::unchecked("late-taker"), &coins(2000, "tokenB"));
+        let res = execute(deps.as_mut(), mock_env(), info, accept_msg);
+        match res.unwrap_err() {
+            ContractError::Unauthorized { expected, found } => {
+                assert_eq!(expected, "taker");
+                assert_eq!(found, "late-taker");
+            }
+            _ => panic!("Unexpected error"),
+        }
+
+        // The agreement executes with the taker as counterparty
+        let execute_msg = ExecuteMsg::ExecuteAgreement {
+            id: 1,
+            preimage: None,
+        };
+        let info = message_info(&Addr::unchecked("taker"), &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, execute_msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(BankMsg::Send {
+                    to_address: "taker".to_string(),
+                    amount: coins(1000, "tokenA"),
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: "initiator".to_string(),
+                    amount: coins(2000, "tokenB"),
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn initiator_cannot_accept_own_open_offer() {
+        let mut deps = initialize_contract();
+
+        initiate_open_offer(&mut deps);
+
+        let accept_msg = ExecuteMsg::AcceptAgreement { id: 1 };
+        let info = message_info(&Addr::unchecked("initiator"), &coins(2000, "tokenB"));
+        let res = execute(deps.as_mut(), mock_env(), info, accept_msg);
+        match res.unwrap_err() {
+            ContractError::InvalidCounterparty {
+                initiator,
+                counterparty,
+            } => {
+                assert_eq!(initiator, "initiator");
+                assert_eq!(counterparty, "initiator");
+            }
+            _ => panic!("Unexpected error"),
+        }
+    }
+
+    #[test]
+    fn query_open_offers() {
+        let mut deps = initialize_contract();
+
+        initiate_new_agreement(&mut deps, "initiator", 1000, "counterparty", 2000);
+        initiate_open_offer(&mut deps);
+        initiate_open_offer(&mut deps);
+
+        let open_offers = |deps: &OwnedDeps<MockStorage, MockApi, MockQuerier>| {
+            let res = query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::GetOpenOffers {
+                    start_after: None,
+                    limit: None,
+                },
+            )
+            .unwrap();
+            let value: AgreementsResponse = from_json(&res).unwrap();
+            value
+                .agreements
+                .iter()
+                .map(|agreement| agreement.id)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(open_offers(&deps), vec![2, 3]);
+
+        // Taken offers are no longer listed
+        let accept_msg = ExecuteMsg::AcceptAgreement { id: 2 };
+        let info = message_info(&Addr::unchecked("taker"), &coins(2000, "tokenB"));
+        execute(deps.as_mut(), mock_env(), info, accept_msg).unwrap();
+        assert_eq!(open_offers(&deps), vec![3]);
+    }
+
     #[test]
     fn unauthorized_execute_agreement() {
         let mut deps = initialize_contract();
@@ -281,20 +699,23 @@ mod tests {
 
         // Accept the agreement
         let accept_msg = ExecuteMsg::AcceptAgreement { id: 1 };
-        let accept_info = message_info(&Addr::unchecked("counterparty"), &coins(2000, "tokenB"));
+        let accept_info = message_info(&addr("counterparty"), &coins(2000, "tokenB"));
         let res = execute(deps.as_mut(), mock_env(), accept_info.clone(), accept_msg);
 
         assert!(res.is_ok());
 
         // Execute the agreement
-        let execute_msg = ExecuteMsg::ExecuteAgreement { id: 1 };
+        let execute_msg = ExecuteMsg::ExecuteAgreement {
+            id: 1,
+            preimage: None,
+        };
         let execute_info = message_info(&Addr::unchecked("some-other-counterparty"), &[]);
         let res = execute(deps.as_mut(), mock_env(), execute_info.clone(), execute_msg);
 
         assert!(res.is_err());
         match res.err().unwrap() {
             ContractError::Unauthorized { expected, found } => {
-                assert_eq!(expected, "initiator or counterparty");
+                assert_eq!(expected, format!("initiator or {}", addr("counterparty")));
                 assert_eq!(found, "some-other-counterparty");
             }
             _ => panic!("Unexpected error"),
@@ -309,7 +730,7 @@ mod tests {
 
         // Accept the agreement
         let accept_msg = ExecuteMsg::AcceptAgreement { id: 1 };
-        let accept_info = message_info(&Addr::unchecked("counterparty"), &coins(2000, "tokenB"));
+        let accept_info = message_info(&addr("counterparty"), &coins(2000, "tokenB"));
         let res = execute(deps.as_mut(), mock_env(), accept_info.clone(), accept_msg);
 
         assert!(res.is_ok());
@@ -322,7 +743,7 @@ mod tests {
         assert!(res.is_err());
         match res.err().unwrap() {
             ContractError::Unauthorized { expected, found } => {
-                assert_eq!(expected, "initiator or counterparty");
+                assert_eq!(expected, format!("initiator or {}", addr("counterparty")));
                 assert_eq!(found, "some-other-counterparty");
             }
             _ => panic!("Unexpected error"),
@@ -337,11 +758,14 @@ mod tests {
 
         // Accept the agreement
         let accept_msg = ExecuteMsg::AcceptAgreement { id: 1 };
-        let accept_info = message_info(&Addr::unchecked("counterparty"), &coins(2000, "tokenB"));
+        let accept_info = message_info(&addr("counterparty"), &coins(2000, "tokenB"));
         let _res = execute(deps.as_mut(), mock_env(), accept_info.clone(), accept_msg).unwrap();
 
         // Execute the agreement
-        let execute_msg = ExecuteMsg::ExecuteAgreement { id: 1 };
+        let execute_msg = ExecuteMsg::ExecuteAgreement {
+            id: 1,
+            preimage: None,
+        };
         let execute_info = message_info(&Addr::unchecked("initiator"), &[]);
         let res = execute(deps.as_mut(), mock_env(), execute_info.clone(), execute_msg).unwrap();
 
@@ -358,7 +782,7 @@ mod tests {
                 },
                 Attribute {
                     key: "status".to_string(),
-                    value: STATUS_EXECUTED.to_string()
+                    value: AgreementStatus::Executed.to_string()
                 },
                 Attribute {
                     key: "initiator".to_string(),
@@ -366,15 +790,15 @@ mod tests {
                 },
                 Attribute {
                     key: "counterparty".to_string(),
-                    value: "counterparty".to_string()
+                    value: addr("counterparty").to_string()
                 },
                 Attribute {
                     key: "initiator_token".to_string(),
-                    value: "1000tokenA".to_string()
+                    value: "native:tokenA:1000".to_string()
                 },
                 Attribute {
                     key: "counterparty_token".to_string(),
-                    value: "2000tokenB".to_string()
+                    value: "native:tokenB:2000".to_string()
                 }
             ]
         );
@@ -390,199 +814,3354 @@ mod tests {
         let query_res = query(deps.as_ref(), mock_env(), query_msg).unwrap();
         let agreement_response: AgreementResponse = from_json(&query_res).unwrap();
 
-        assert_eq!(agreement_response.agreement.status, STATUS_EXECUTED);
+        assert_eq!(
+            agreement_response.agreement.status,
+            AgreementStatus::Executed
+        );
     }
 
     #[test]
-    fn execute_agreement_insufficient_funds_in_contract() {
-        // Arrange
-        let mut deps = mock_dependencies_with_balances(&[
-            (
-                (&Addr::unchecked("initiator")).as_ref(),
-                &[coin(1000, "tokenA")],
-            ),
-            (
-                (&Addr::unchecked("counterparty")).as_ref(),
-                &[coin(2000, "tokenB")],
-            ),
-            (
-                (&Addr::unchecked("cosmos2contract")).as_ref(),
-                &[coin(500, "tokenA"), coin(1000, "tokenB")],
-            ),
-        ]);
+    fn execute_agreement_with_fees() {
+        let mut deps = initialize_contract();
+        let treasury = deps.api.addr_make("treasury");
 
-        // Initialize the contract
-        let init_msg = InstantiateMsg {};
-        let init_info = message_info(&Addr::unchecked("creator"), &[]);
-        let _res = instantiate(deps.as_mut(), mock_env(), init_info.clone(), init_msg).unwrap();
+        // The owner defaults to the instantiating sender and sets the fees
+        let update_msg = ExecuteMsg::UpdateConfig {
+            fee_recipient: Some(treasury.clone()),
+            initiator_fee_bps: Some(100),
+            counterparty_fee_bps: Some(25),
+        };
+        let info = message_info(&Addr::unchecked("creator"), &[]);
+        execute(deps.as_mut(), mock_env(), info, update_msg).unwrap();
 
         initiate_new_agreement(&mut deps, "initiator", 1000, "counterparty", 2000);
-
-        // Accept the agreement
         let accept_msg = ExecuteMsg::AcceptAgreement { id: 1 };
-        let accept_info = message_info(&Addr::unchecked("counterparty"), &coins(2000, "tokenB"));
-        let _res = execute(deps.as_mut(), mock_env(), accept_info.clone(), accept_msg).unwrap();
+        let info = message_info(&addr("counterparty"), &coins(2000, "tokenB"));
+        execute(deps.as_mut(), mock_env(), info, accept_msg).unwrap();
 
-        // Execute the agreement
-        let execute_msg = ExecuteMsg::ExecuteAgreement { id: 1 };
-        let execute_info = message_info(&Addr::unchecked("initiator"), &[]);
-        let res = execute(deps.as_mut(), mock_env(), execute_info.clone(), execute_msg);
+        // Each side's fee is deducted from the tokens it pays and sent to the fee recipient
+        let execute_msg = ExecuteMsg::ExecuteAgreement {
+            id: 1,
+            preimage: None,
+        };
+        let info = message_info(&Addr::unchecked("initiator"), &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, execute_msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(BankMsg::Send {
+                    to_address: "initiator".to_string(),
+                    amount: coins(1995, "tokenB"),
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: addr("counterparty").to_string(),
+                    amount: coins(990, "tokenA"),
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: treasury.to_string(),
+                    amount: vec![Coin::new(10u128, "tokenA"), Coin::new(5u128, "tokenB")],
+                }),
+            ]
+        );
+        assert!(res
+            .attributes
+            .contains(&Attribute::new("fees", "native:tokenB:5,native:tokenA:10")));
 
-        assert!(res.is_err());
-        match res.err().unwrap() {
-            ContractError::InsufficientContractFunds { expected, found } => {
-                assert_eq!(expected, "1000");
-                assert_eq!(found, "500");
-            }
-            _ => panic!("Unexpected error"),
-        }
+        // Fees accrue per denom
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetAccruedFees {
+                denom: "tokenA".to_string(),
+            },
+        )
+        .unwrap();
+        let value: AccruedFeesResponse = from_json(&res).unwrap();
+        assert_eq!(value.amount, Uint128::new(10));
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetAccruedFees {
+                denom: "tokenC".to_string(),
+            },
+        )
+        .unwrap();
+        let value: AccruedFeesResponse = from_json(&res).unwrap();
+        assert_eq!(value.amount, Uint128::zero());
+    }
+
+    // Configures protocol fees of 1% on the initiator token and 0.25% on the counterparty token
+    fn set_protocol_fees(deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>) -> Addr {
+        let treasury = deps.api.addr_make("treasury");
+        let update_msg = ExecuteMsg::UpdateConfig {
+            fee_recipient: Some(treasury.clone()),
+            initiator_fee_bps: Some(100),
+            counterparty_fee_bps: Some(25),
+        };
+        let info = message_info(&Addr::unchecked("creator"), &[]);
+        execute(deps.as_mut(), mock_env(), info, update_msg).unwrap();
+        treasury
     }
 
     #[test]
-    fn cancel_agreement() {
+    fn update_config() {
         let mut deps = initialize_contract();
 
-        initiate_new_agreement(&mut deps, "initiator", 1000, "counterparty", 2000);
-
-        let msg = ExecuteMsg::CancelAgreement { id: 1 };
-        let info = message_info(&Addr::unchecked("initiator"), &coins(1000, "tokenA"));
-        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        // Only the owner can update the config
+        let update_msg = ExecuteMsg::UpdateConfig {
+            fee_recipient: None,
+            initiator_fee_bps: Some(50),
+            counterparty_fee_bps: None,
+        };
+        let info = message_info(&Addr::unchecked("initiator"), &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, update_msg.clone());
+        match res.unwrap_err() {
+            ContractError::Unauthorized { expected, found } => {
+                assert_eq!(expected, "creator");
+                assert_eq!(found, "initiator");
+            }
+            _ => panic!("Unexpected error"),
+        }
 
+        let info = message_info(&Addr::unchecked("creator"), &[]);
+        execute(deps.as_mut(), mock_env(), info.clone(), update_msg).unwrap();
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetConfig {}).unwrap();
+        let value: ConfigResponse = from_json(&res).unwrap();
         assert_eq!(
-            res.attributes,
-            vec![
-                Attribute {
-                    key: "method".to_string(),
-                    value: "cancel_agreement".to_string()
-                },
-                Attribute {
-                    key: "id".to_string(),
-                    value: "1".to_string()
-                },
-                Attribute {
-                    key: "status".to_string(),
-                    value: STATUS_CANCELED.to_string()
-                },
-                Attribute {
-                    key: "initiator".to_string(),
-                    value: "initiator".to_string()
-                },
-                Attribute {
-                    key: "counterparty".to_string(),
-                    value: "counterparty".to_string()
-                },
-                Attribute {
-                    key: "initiator_token".to_string(),
-                    value: "1000tokenA".to_string()
-                },
-                Attribute {
-                    key: "counterparty_token".to_string(),
-                    value: "2000tokenB".to_string()
-                }
-            ]
+            value.config,
+            Config {
+                fee_recipient: Addr::unchecked("creator"),
+                initiator_fee_bps: 50,
+                counterparty_fee_bps: 0,
+                guardian: None,
+            }
         );
 
-        // Check agreement status counts
-        check_agreement_counts(&deps, 1, 0, 0, 0, 1);
-
-        let query_msg = QueryMsg::GetAgreement { id: 1 };
-        let query_res = query(deps.as_ref(), mock_env(), query_msg).unwrap();
-        let agreement_response: AgreementResponse = from_json(&query_res).unwrap();
+        // Fees cannot exceed the whole token
+        let update_msg = ExecuteMsg::UpdateConfig {
+            fee_recipient: None,
+            initiator_fee_bps: None,
+            counterparty_fee_bps: Some(10_001),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, update_msg);
+        assert!(matches!(
+            res.unwrap_err(),
+            ContractError::InvalidConfig { .. }
+        ));
+    }
 
-        assert_eq!(agreement_response.agreement.status, STATUS_CANCELED);
+    fn query_ownership(deps: &OwnedDeps<MockStorage, MockApi, MockQuerier>) -> OwnershipResponse {
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetOwnership {}).unwrap();
+        from_json(&res).unwrap()
     }
 
     #[test]
-    fn accept_cancelled_agreement() {
+    fn transfer_ownership() {
         let mut deps = initialize_contract();
+        let new_owner = deps.api.addr_make("new_owner");
 
-        let initiator_token = TokenInfo {
-            address: Addr::unchecked("tokenA"),
-            amount: 1000u128,
-        };
-        let counterparty_token = TokenInfo {
-            address: Addr::unchecked("tokenB"),
-            amount: 2000u128,
-        };
-        let counterparty = Addr::unchecked("counterparty");
+        // The instantiating sender owns the contract
+        assert_eq!(
+            query_ownership(&deps),
+            OwnershipResponse {
+                owner: Some(Addr::unchecked("creator")),
+                pending_owner: None,
+            }
+        );
 
-        let msg = ExecuteMsg::InitiateAgreement {
-            initiator_token: initiator_token.clone(),
-            counterparty_token: counterparty_token.clone(),
-            counterparty: counterparty.clone(),
+        // Only the owner can propose a new owner
+        let transfer_msg = ExecuteMsg::TransferOwnership {
+            new_owner: new_owner.clone(),
+        };
+        let info = message_info(&new_owner, &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, transfer_msg.clone());
+        assert!(matches!(
+            res.unwrap_err(),
+            ContractError::Unauthorized { .. }
+        ));
+
+        let info = message_info(&Addr::unchecked("creator"), &[]);
+        execute(deps.as_mut(), mock_env(), info, transfer_msg).unwrap();
+        assert_eq!(
+            query_ownership(&deps),
+            OwnershipResponse {
+                owner: Some(Addr::unchecked("creator")),
+                pending_owner: Some(new_owner.clone()),
+            }
+        );
+
+        // Only the proposed owner can accept
+        let info = message_info(&Addr::unchecked("initiator"), &[]);
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::AcceptOwnership {},
+        );
+        match res.unwrap_err() {
+            ContractError::Unauthorized { expected, found } => {
+                assert_eq!(expected, new_owner.to_string());
+                assert_eq!(found, "initiator");
+            }
+            _ => panic!("Unexpected error"),
+        }
+
+        let info = message_info(&new_owner, &[]);
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            ExecuteMsg::AcceptOwnership {},
+        )
+        .unwrap();
+        assert_eq!(
+            query_ownership(&deps),
+            OwnershipResponse {
+                owner: Some(new_owner.clone()),
+                pending_owner: None,
+            }
+        );
+
+        // The transfer cannot be accepted twice
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::AcceptOwnership {},
+        );
+        assert!(matches!(res.unwrap_err(), ContractError::NoPendingOwner {}));
+    }
+
+    #[test]
+    fn renounce_ownership() {
+        let mut deps = initialize_contract();
+
+        let info = message_info(&Addr::unchecked("creator"), &[]);
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            ExecuteMsg::RenounceOwnership {},
+        )
+        .unwrap();
+        assert_eq!(
+            query_ownership(&deps),
+            OwnershipResponse {
+                owner: None,
+                pending_owner: None,
+            }
+        );
+
+        // Admin operations are no longer possible
+        let update_msg = ExecuteMsg::UpdateConfig {
+            fee_recipient: None,
+            initiator_fee_bps: Some(100),
+            counterparty_fee_bps: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, update_msg);
+        assert!(matches!(res.unwrap_err(), ContractError::NoOwner {}));
+    }
+
+    fn query_paused(deps: &OwnedDeps<MockStorage, MockApi, MockQuerier>) -> PauseFlags {
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetPaused {}).unwrap();
+        let value: PausedResponse = from_json(&res).unwrap();
+        value.paused
+    }
+
+    #[test]
+    fn pause_and_unpause() {
+        let mut deps = initialize_contract();
+        let guardian = deps.api.addr_make("guardian");
+
+        initiate_new_agreement(&mut deps, "initiator", 1000, "counterparty", 2000);
+
+        // Only the owner or the guardian can pause
+        let pause_msg = ExecuteMsg::Pause {
+            initiate: true,
+            accept: true,
+            execute: false,
+        };
+        let info = message_info(&guardian, &[]);
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), pause_msg.clone());
+        assert!(matches!(
+            res.unwrap_err(),
+            ContractError::Unauthorized { .. }
+        ));
+
+        let set_guardian_msg = ExecuteMsg::SetGuardian {
+            guardian: Some(guardian.clone()),
+        };
+        let owner_info = message_info(&Addr::unchecked("creator"), &[]);
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            owner_info.clone(),
+            set_guardian_msg,
+        )
+        .unwrap();
+        execute(deps.as_mut(), mock_env(), info, pause_msg).unwrap();
+        assert_eq!(
+            query_paused(&deps),
+            PauseFlags {
+                initiate: true,
+                accept: true,
+                execute: false,
+            }
+        );
+
+        // Paused operations are rejected
+        let initiate_msg = ExecuteMsg::InitiateAgreement {
+            initiator_token: Asset::native("tokenA", 1000u128),
+            counterparty_token: Asset::native("tokenB", 2000u128),
+            counterparty: Some(addr("counterparty")),
+            expires: None,
+            arbiter: None,
+            arbiter_fee_bps: None,
+            milestones: None,
+            htlc: None,
+            cancel_policy: None,
+            cancel_penalty: None,
+            initiator_assets: None,
+            counterparty_assets: None,
+        };
+        let info = message_info(&Addr::unchecked("initiator"), &coins(1000, "tokenA"));
+        let res = execute(deps.as_mut(), mock_env(), info, initiate_msg);
+        match res.unwrap_err() {
+            ContractError::Paused { operation } => assert_eq!(operation, "initiate"),
+            _ => panic!("Unexpected error"),
+        }
+
+        let accept_msg = ExecuteMsg::AcceptAgreement { id: 1 };
+        let info = message_info(&addr("counterparty"), &coins(2000, "tokenB"));
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), accept_msg.clone());
+        match res.unwrap_err() {
+            ContractError::Paused { operation } => assert_eq!(operation, "accept"),
+            _ => panic!("Unexpected error"),
+        }
+
+        // The owner unpauses accepting only
+        let unpause_msg = ExecuteMsg::Unpause {
+            initiate: false,
+            accept: true,
+            execute: false,
+        };
+        execute(deps.as_mut(), mock_env(), owner_info, unpause_msg).unwrap();
+        assert_eq!(
+            query_paused(&deps),
+            PauseFlags {
+                initiate: true,
+                accept: false,
+                execute: false,
+            }
+        );
+        execute(deps.as_mut(), mock_env(), info, accept_msg).unwrap();
+    }
+
+    #[test]
+    fn cancel_agreement_while_paused() {
+        let mut deps = initialize_contract();
+
+        initiate_new_agreement(&mut deps, "initiator", 1000, "counterparty", 2000);
+
+        let pause_msg = ExecuteMsg::Pause {
+            initiate: true,
+            accept: true,
+            execute: true,
+        };
+        let info = message_info(&Addr::unchecked("creator"), &[]);
+        execute(deps.as_mut(), mock_env(), info, pause_msg).unwrap();
+
+        // Refunds remain available while everything else is paused
+        let cancel_msg = ExecuteMsg::CancelAgreement { id: 1 };
+        let info = message_info(&Addr::unchecked("initiator"), &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, cancel_msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "initiator".to_string(),
+                amount: coins(1000, "tokenA"),
+            })]
+        );
+    }
+
+    fn add_asset_rule(
+        deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>,
+        token: &str,
+        allowed: bool,
+        min_amount: Option<u128>,
+        max_amount: Option<u128>,
+    ) {
+        let msg = ExecuteMsg::AddAssetRule {
+            token: token.to_string(),
+            allowed,
+            min_amount: min_amount.map(Uint128::new),
+            max_amount: max_amount.map(Uint128::new),
+        };
+        let info = message_info(&Addr::unchecked("creator"), &[]);
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    }
+
+    fn try_initiate(
+        deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>,
+        initiator_amount: u128,
+        counterparty_amount: u128,
+    ) -> Result<Response, ContractError> {
+        let msg = ExecuteMsg::InitiateAgreement {
+            initiator_token: Asset::native("tokenA", initiator_amount),
+            counterparty_token: Asset::native("tokenB", counterparty_amount),
+            counterparty: Some(addr("counterparty")),
+            expires: None,
+            arbiter: None,
+            arbiter_fee_bps: None,
+            milestones: None,
+            htlc: None,
+            cancel_policy: None,
+            cancel_penalty: None,
+            initiator_assets: None,
+            counterparty_assets: None,
+        };
+        let info = message_info(
+            &Addr::unchecked("initiator"),
+            &coins(initiator_amount, "tokenA"),
+        );
+        execute(deps.as_mut(), mock_env(), info, msg)
+    }
+
+    #[test]
+    fn asset_rules_limit_agreements() {
+        let mut deps = initialize_contract();
+
+        // Only the owner manages asset rules
+        let msg = ExecuteMsg::AddAssetRule {
+            token: "tokenA".to_string(),
+            allowed: false,
+            min_amount: None,
+            max_amount: None,
+        };
+        let info = message_info(&Addr::unchecked("initiator"), &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, msg);
+        assert!(matches!(
+            res.unwrap_err(),
+            ContractError::Unauthorized { .. }
+        ));
+
+        // Denylisted tokens cannot be escrowed on either side
+        add_asset_rule(&mut deps, "tokenB", false, None, None);
+        match try_initiate(&mut deps, 1000, 2000).unwrap_err() {
+            ContractError::AssetNotAllowed { asset, reason } => {
+                assert_eq!(asset, "native:tokenB:2000");
+                assert_eq!(reason, "token is denylisted");
+            }
+            _ => panic!("Unexpected error"),
+        }
+
+        // Allowed tokens must be within their limits
+        add_asset_rule(&mut deps, "tokenB", true, None, None);
+        add_asset_rule(&mut deps, "tokenA", true, Some(500), Some(1000));
+        match try_initiate(&mut deps, 100, 2000).unwrap_err() {
+            ContractError::AssetNotAllowed { reason, .. } => {
+                assert_eq!(reason, "amount is below the minimum of 500")
+            }
+            _ => panic!("Unexpected error"),
+        }
+        match try_initiate(&mut deps, 1001, 2000).unwrap_err() {
+            ContractError::AssetNotAllowed { reason, .. } => {
+                assert_eq!(reason, "amount is above the maximum of 1000")
+            }
+            _ => panic!("Unexpected error"),
+        }
+        try_initiate(&mut deps, 1000, 2000).unwrap();
+
+        // Accepting checks the counterparty token against the rules in force
+        add_asset_rule(&mut deps, "tokenB", true, None, Some(1500));
+        let accept_msg = ExecuteMsg::AcceptAgreement { id: 1 };
+        let info = message_info(&addr("counterparty"), &coins(2000, "tokenB"));
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), accept_msg.clone());
+        assert!(matches!(
+            res.unwrap_err(),
+            ContractError::AssetNotAllowed { .. }
+        ));
+
+        let msg = ExecuteMsg::RemoveAssetRule {
+            token: "tokenB".to_string(),
+        };
+        let owner_info = message_info(&Addr::unchecked("creator"), &[]);
+        execute(deps.as_mut(), mock_env(), owner_info, msg).unwrap();
+        execute(deps.as_mut(), mock_env(), info, accept_msg).unwrap();
+    }
+
+    #[test]
+    fn asset_allowlist_mode() {
+        let mut deps = initialize_contract();
+
+        add_asset_rule(&mut deps, "tokenA", true, None, None);
+        let msg = ExecuteMsg::SetAssetListMode {
+            mode: AssetListMode::Allowlist,
+        };
+        let info = message_info(&Addr::unchecked("creator"), &[]);
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // Tokens without a rule are rejected in allowlist mode
+        match try_initiate(&mut deps, 1000, 2000).unwrap_err() {
+            ContractError::AssetNotAllowed { asset, reason } => {
+                assert_eq!(asset, "native:tokenB:2000");
+                assert_eq!(reason, "token is not allowlisted");
+            }
+            _ => panic!("Unexpected error"),
+        }
+
+        add_asset_rule(&mut deps, "tokenB", true, None, None);
+        try_initiate(&mut deps, 1000, 2000).unwrap();
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetAssetRules {
+                start_after: Some("tokenA".to_string()),
+                limit: None,
+            },
+        )
+        .unwrap();
+        let value: AssetRulesResponse = from_json(&res).unwrap();
+        assert_eq!(value.mode, AssetListMode::Allowlist);
+        assert_eq!(
+            value.rules,
+            vec![AssetRuleResponse {
+                token: "tokenB".to_string(),
+                rule: Some(AssetRule {
+                    allowed: true,
+                    min_amount: None,
+                    max_amount: None,
+                }),
+            }]
+        );
+        assert_eq!(value.next_start_after, None);
+
+        // A full page returns the cursor of the next one
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetAssetRules {
+                start_after: None,
+                limit: Some(1),
+            },
+        )
+        .unwrap();
+        let value: AssetRulesResponse = from_json(&res).unwrap();
+        assert_eq!(value.rules[0].token, "tokenA");
+        assert_eq!(value.next_start_after, Some("tokenA".to_string()));
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetAssetRule {
+                token: "tokenC".to_string(),
+            },
+        )
+        .unwrap();
+        let value: AssetRuleResponse = from_json(&res).unwrap();
+        assert_eq!(value.rule, None);
+    }
+
+    #[test]
+    fn execute_agreement_insufficient_funds_in_escrow() {
+        // Arrange: the contract holds plenty of both tokens for other agreements
+        let mut deps = mock_dependencies_with_balances(&[
+            (
+                Addr::unchecked("initiator").as_ref(),
+                &[coin(1000, "tokenA")],
+            ),
+            (addr("counterparty").as_ref(), &[coin(2000, "tokenB")]),
+            (
+                mock_env().contract.address.as_str(),
+                &[coin(50000, "tokenA"), coin(50000, "tokenB")],
+            ),
+        ]);
+
+        // Initialize the contract
+        let init_msg = InstantiateMsg::default();
+        let init_info = message_info(&Addr::unchecked("creator"), &[]);
+        let _res = instantiate(deps.as_mut(), mock_env(), init_info.clone(), init_msg).unwrap();
+
+        initiate_new_agreement(&mut deps, "initiator", 1000, "counterparty", 2000);
+
+        // Accept the agreement
+        let accept_msg = ExecuteMsg::AcceptAgreement { id: 1 };
+        let accept_info = message_info(&addr("counterparty"), &coins(2000, "tokenB"));
+        let _res = execute(deps.as_mut(), mock_env(), accept_info.clone(), accept_msg).unwrap();
+
+        // Only half of the initiator's deposit is left in the ledger of the agreement
+        DEPOSITS
+            .save(
+                deps.as_mut().storage,
+                (1, &Addr::unchecked("initiator")),
+                &vec![Asset::native("tokenA", 500u128)],
+            )
+            .unwrap();
+
+        // Execute the agreement
+        let execute_msg = ExecuteMsg::ExecuteAgreement {
+            id: 1,
+            preimage: None,
+        };
+        let execute_info = message_info(&Addr::unchecked("initiator"), &[]);
+        let res = execute(deps.as_mut(), mock_env(), execute_info.clone(), execute_msg);
+
+        assert!(res.is_err());
+        match res.err().unwrap() {
+            ContractError::InsufficientContractFunds { expected, found } => {
+                assert_eq!(expected, "1000");
+                assert_eq!(found, "500");
+            }
+            _ => panic!("Unexpected error"),
+        }
+    }
+
+    #[test]
+    fn agreement_deposits_ledger() {
+        let mut deps = initialize_contract();
+
+        initiate_new_agreement(&mut deps, "initiator", 1000, "counterparty", 2000);
+
+        let query_msg = QueryMsg::GetAgreementDeposits { id: 1 };
+        let res = query(deps.as_ref(), mock_env(), query_msg.clone()).unwrap();
+        let value: AgreementDepositsResponse = from_json(&res).unwrap();
+        assert_eq!(
+            value.deposits,
+            vec![AgreementDeposit {
+                depositor: Addr::unchecked("initiator"),
+                assets: vec![Asset::native("tokenA", 1000u128)],
+            }]
+        );
+
+        // Accept the agreement
+        let accept_msg = ExecuteMsg::AcceptAgreement { id: 1 };
+        let accept_info = message_info(&addr("counterparty"), &coins(2000, "tokenB"));
+        execute(deps.as_mut(), mock_env(), accept_info, accept_msg).unwrap();
+
+        let res = query(deps.as_ref(), mock_env(), query_msg.clone()).unwrap();
+        let value: AgreementDepositsResponse = from_json(&res).unwrap();
+        assert_eq!(value.deposits.len(), 2);
+
+        // Executing the agreement pays out and clears the ledger
+        let execute_msg = ExecuteMsg::ExecuteAgreement {
+            id: 1,
+            preimage: None,
+        };
+        let execute_info = message_info(&Addr::unchecked("initiator"), &[]);
+        execute(deps.as_mut(), mock_env(), execute_info, execute_msg).unwrap();
+
+        let res = query(deps.as_ref(), mock_env(), query_msg).unwrap();
+        let value: AgreementDepositsResponse = from_json(&res).unwrap();
+        assert!(value.deposits.is_empty());
+    }
+
+    #[test]
+    fn cancel_initiated_agreement_does_not_refund_other_deposits() {
+        let mut deps = initialize_contract();
+
+        // A second agreement escrows tokenB of the same counterparty in the contract
+        initiate_new_agreement(&mut deps, "initiator", 1000, "counterparty", 2000);
+        let msg = ExecuteMsg::InitiateAgreement {
+            initiator_token: Asset::native("tokenA", 1000u128),
+            counterparty_token: Asset::native("tokenB", 2000u128),
+            counterparty: Some(addr("counterparty")),
+            expires: None,
+            arbiter: None,
+            arbiter_fee_bps: None,
+            milestones: None,
+            htlc: None,
+            cancel_policy: None,
+            cancel_penalty: None,
+            initiator_assets: None,
+            counterparty_assets: None,
+        };
+        let info = message_info(&Addr::unchecked("initiator"), &coins(1000, "tokenA"));
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let accept_msg = ExecuteMsg::AcceptAgreement { id: 2 };
+        let accept_info = message_info(&addr("counterparty"), &coins(2000, "tokenB"));
+        execute(deps.as_mut(), mock_env(), accept_info, accept_msg).unwrap();
+
+        // Canceling the first agreement only refunds what was deposited for it
+        let msg = ExecuteMsg::CancelAgreement { id: 1 };
+        let info = message_info(&addr("counterparty"), &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "initiator".to_string(),
+                amount: coins(1000, "tokenA"),
+            })]
+        );
+
+        // The deposits of the second agreement are untouched
+        let query_msg = QueryMsg::GetAgreementDeposits { id: 2 };
+        let res = query(deps.as_ref(), mock_env(), query_msg).unwrap();
+        let value: AgreementDepositsResponse = from_json(&res).unwrap();
+        assert_eq!(value.deposits.len(), 2);
+    }
+
+    #[test]
+    fn initiate_agreement_already_expired() {
+        let mut deps = initialize_contract();
+        let env = mock_env();
+
+        let msg = ExecuteMsg::InitiateAgreement {
+            initiator_token: Asset::native("tokenA", 1000u128),
+            counterparty_token: Asset::native("tokenB", 2000u128),
+            counterparty: Some(addr("counterparty")),
+            expires: Some(Expiration::AtHeight(env.block.height)),
+            arbiter: None,
+            arbiter_fee_bps: None,
+            milestones: None,
+            htlc: None,
+            cancel_policy: None,
+            cancel_penalty: None,
+            initiator_assets: None,
+            counterparty_assets: None,
+        };
+        let info = message_info(&Addr::unchecked("initiator"), &coins(1000, "tokenA"));
+        let res = execute(deps.as_mut(), env.clone(), info, msg);
+
+        match res.err().unwrap() {
+            ContractError::InvalidExpiration { expires } => {
+                assert_eq!(expires, format!("expiration height: {}", env.block.height));
+            }
+            _ => panic!("Unexpected error"),
+        }
+    }
+
+    #[test]
+    fn accept_expired_agreement() {
+        let mut deps = initialize_contract();
+        let mut env = mock_env();
+
+        let msg = ExecuteMsg::InitiateAgreement {
+            initiator_token: Asset::native("tokenA", 1000u128),
+            counterparty_token: Asset::native("tokenB", 2000u128),
+            counterparty: Some(addr("counterparty")),
+            expires: Some(Expiration::AtHeight(env.block.height + 10)),
+            arbiter: None,
+            arbiter_fee_bps: None,
+            milestones: None,
+            htlc: None,
+            cancel_policy: None,
+            cancel_penalty: None,
+            initiator_assets: None,
+            counterparty_assets: None,
+        };
+        let info = message_info(&Addr::unchecked("initiator"), &coins(1000, "tokenA"));
+        execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        env.block.height += 10;
+
+        let accept_msg = ExecuteMsg::AcceptAgreement { id: 1 };
+        let accept_info = message_info(&addr("counterparty"), &coins(2000, "tokenB"));
+        let res = execute(deps.as_mut(), env, accept_info, accept_msg);
+
+        match res.err().unwrap() {
+            ContractError::AgreementExpired { id } => assert_eq!(id, 1),
+            _ => panic!("Unexpected error"),
+        }
+    }
+
+    #[test]
+    fn claim_expired_agreement() {
+        let mut deps = initialize_contract();
+        let mut env = mock_env();
+
+        let msg = ExecuteMsg::InitiateAgreement {
+            initiator_token: Asset::native("tokenA", 1000u128),
+            counterparty_token: Asset::native("tokenB", 2000u128),
+            counterparty: Some(addr("counterparty")),
+            expires: Some(Expiration::AtTime(env.block.time.plus_seconds(60))),
+            arbiter: None,
+            arbiter_fee_bps: None,
+            milestones: None,
+            htlc: None,
+            cancel_policy: None,
+            cancel_penalty: None,
+            initiator_assets: None,
+            counterparty_assets: None,
+        };
+        let info = message_info(&Addr::unchecked("initiator"), &coins(1000, "tokenA"));
+        execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        // Claiming before the expiration fails
+        let claim_msg = ExecuteMsg::ClaimExpired { id: 1 };
+        let claim_info = message_info(&Addr::unchecked("anyone"), &[]);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            claim_info.clone(),
+            claim_msg.clone(),
+        );
+
+        match res.err().unwrap() {
+            ContractError::AgreementNotExpired { id } => assert_eq!(id, 1),
+            _ => panic!("Unexpected error"),
+        }
+
+        // Anyone can refund the initiator once the agreement expired
+        env.block.time = env.block.time.plus_seconds(60);
+        let res = execute(deps.as_mut(), env, claim_info, claim_msg).unwrap();
+
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "initiator".to_string(),
+                amount: coins(1000, "tokenA"),
+            })]
+        );
+
+        check_agreement_counts(&deps, 1, 0, 0, 0, 0);
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetExpiredAgreementCount {},
+        )
+        .unwrap();
+        let value: ExpiredAgreementCountResponse = from_json(&res).unwrap();
+        assert_eq!(value.expired_agreement_count, 1);
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetAgreement { id: 1 }).unwrap();
+        let value: AgreementResponse = from_json(&res).unwrap();
+        assert_eq!(value.agreement.status, AgreementStatus::Expired);
+    }
+
+    fn initiate_arbitrated_agreement(
+        deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>,
+        arbiter_fee_bps: u16,
+    ) {
+        let msg = ExecuteMsg::InitiateAgreement {
+            initiator_token: Asset::native("tokenA", 1000u128),
+            counterparty_token: Asset::native("tokenB", 2000u128),
+            counterparty: Some(addr("counterparty")),
+            expires: None,
+            arbiter: Some(deps.api.addr_make("arbiter")),
+            arbiter_fee_bps: Some(arbiter_fee_bps),
+            milestones: None,
+            htlc: None,
+            cancel_policy: None,
+            cancel_penalty: None,
+            initiator_assets: None,
+            counterparty_assets: None,
+        };
+        let info = message_info(&Addr::unchecked("initiator"), &coins(1000, "tokenA"));
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let accept_msg = ExecuteMsg::AcceptAgreement { id: 1 };
+        let accept_info = message_info(&addr("counterparty"), &coins(2000, "tokenB"));
+        execute(deps.as_mut(), mock_env(), accept_info, accept_msg).unwrap();
+    }
+
+    #[test]
+    fn initiate_agreement_invalid_arbiter() {
+        let mut deps = initialize_contract();
+
+        let counterparty = deps.api.addr_make("counterparty");
+        let msg = ExecuteMsg::InitiateAgreement {
+            initiator_token: Asset::native("tokenA", 1000u128),
+            counterparty_token: Asset::native("tokenB", 2000u128),
+            counterparty: Some(counterparty.clone()),
+            expires: None,
+            arbiter: Some(counterparty),
+            arbiter_fee_bps: None,
+            milestones: None,
+            htlc: None,
+            cancel_policy: None,
+            cancel_penalty: None,
+            initiator_assets: None,
+            counterparty_assets: None,
+        };
+        let info = message_info(&Addr::unchecked("initiator"), &coins(1000, "tokenA"));
+        let res = execute(deps.as_mut(), mock_env(), info, msg);
+
+        match res.err().unwrap() {
+            ContractError::InvalidArbiter { reason } => {
+                assert_eq!(reason, "arbiter cannot be a party");
+            }
+            _ => panic!("Unexpected error"),
+        }
+
+        // The arbiter has to be a valid address
+        let msg = ExecuteMsg::InitiateAgreement {
+            initiator_token: Asset::native("tokenA", 1000u128),
+            counterparty_token: Asset::native("tokenB", 2000u128),
+            counterparty: Some(addr("counterparty")),
+            expires: None,
+            arbiter: Some(Addr::unchecked("Arbiter")),
+            arbiter_fee_bps: None,
+            milestones: None,
+            htlc: None,
+            cancel_policy: None,
+            cancel_penalty: None,
+            initiator_assets: None,
+            counterparty_assets: None,
+        };
+        let info = message_info(&Addr::unchecked("initiator"), &coins(1000, "tokenA"));
+        let res = execute(deps.as_mut(), mock_env(), info, msg);
+        assert!(matches!(res.err().unwrap(), ContractError::Std(_)));
+    }
+
+    #[test]
+    fn raise_dispute_without_arbiter() {
+        let mut deps = initialize_contract();
+
+        initiate_new_agreement(&mut deps, "initiator", 1000, "counterparty", 2000);
+
+        let accept_msg = ExecuteMsg::AcceptAgreement { id: 1 };
+        let accept_info = message_info(&addr("counterparty"), &coins(2000, "tokenB"));
+        execute(deps.as_mut(), mock_env(), accept_info, accept_msg).unwrap();
+
+        let msg = ExecuteMsg::RaiseDispute {
+            id: 1,
+            reason: "goods not delivered".to_string(),
+        };
+        let info = message_info(&Addr::unchecked("initiator"), &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, msg);
+
+        match res.err().unwrap() {
+            ContractError::NoArbiter { id } => assert_eq!(id, 1),
+            _ => panic!("Unexpected error"),
+        }
+    }
+
+    #[test]
+    fn raise_and_resolve_dispute() {
+        let mut deps = initialize_contract();
+
+        initiate_arbitrated_agreement(&mut deps, 100);
+
+        let msg = ExecuteMsg::RaiseDispute {
+            id: 1,
+            reason: "goods not delivered".to_string(),
+        };
+        let info = message_info(&Addr::unchecked("initiator"), &[]);
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetAgreement { id: 1 }).unwrap();
+        let value: AgreementResponse = from_json(&res).unwrap();
+        assert_eq!(value.agreement.status, AgreementStatus::Disputed);
+        assert_eq!(
+            value.agreement.dispute,
+            Some(Dispute {
+                raised_by: Addr::unchecked("initiator"),
+                reason: "goods not delivered".to_string(),
+            })
+        );
+
+        // The parties can no longer execute the disputed agreement
+        let execute_msg = ExecuteMsg::ExecuteAgreement {
+            id: 1,
+            preimage: None,
+        };
+        let execute_info = message_info(&addr("counterparty"), &[]);
+        let res = execute(deps.as_mut(), mock_env(), execute_info, execute_msg);
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::InvalidAgreementStatus { .. }
+        ));
+
+        // Only the arbiter can resolve the dispute
+        let payout = DisputePayout {
+            initiator: vec![
+                Asset::native("tokenA", 990u128),
+                Asset::native("tokenB", 980u128),
+            ],
+            counterparty: vec![Asset::native("tokenB", 1000u128)],
+        };
+        let msg = ExecuteMsg::ResolveDispute {
+            id: 1,
+            payout: payout.clone(),
+        };
+        let info = message_info(&Addr::unchecked("initiator"), &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, msg.clone());
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::Unauthorized { .. }
+        ));
+
+        // The payout has to split exactly the escrow minus the arbiter fee
+        let invalid_msg = ExecuteMsg::ResolveDispute {
+            id: 1,
+            payout: DisputePayout {
+                initiator: vec![Asset::native("tokenA", 1000u128)],
+                counterparty: vec![Asset::native("tokenB", 2000u128)],
+            },
+        };
+        let arbiter = deps.api.addr_make("arbiter");
+        let info = message_info(&arbiter, &[]);
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), invalid_msg);
+        match res.err().unwrap() {
+            ContractError::InvalidPayout { expected, found } => {
+                assert_eq!(expected, "native:tokenA:990,native:tokenB:1980");
+                assert_eq!(found, "native:tokenA:1000,native:tokenB:2000");
+            }
+            _ => panic!("Unexpected error"),
+        }
+
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(BankMsg::Send {
+                    to_address: "initiator".to_string(),
+                    amount: vec![Coin::new(990u128, "tokenA"), Coin::new(980u128, "tokenB")],
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: addr("counterparty").to_string(),
+                    amount: coins(1000, "tokenB"),
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: arbiter.to_string(),
+                    amount: vec![Coin::new(10u128, "tokenA"), Coin::new(20u128, "tokenB")],
+                }),
+            ]
+        );
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetAgreement { id: 1 }).unwrap();
+        let value: AgreementResponse = from_json(&res).unwrap();
+        assert_eq!(value.agreement.status, AgreementStatus::Resolved);
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetResolvedAgreementCount {},
+        )
+        .unwrap();
+        let value: ResolvedAgreementCountResponse = from_json(&res).unwrap();
+        assert_eq!(value.resolved_agreement_count, 1);
+    }
+
+    fn initiate_milestone_agreement(
+        deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>,
+        milestones: Vec<Uint128>,
+    ) -> Result<Response, ContractError> {
+        let msg = ExecuteMsg::InitiateAgreement {
+            initiator_token: Asset::native("tokenA", 1000u128),
+            counterparty_token: Asset::native("tokenB", 2000u128),
+            counterparty: Some(addr("counterparty")),
+            expires: None,
+            arbiter: None,
+            arbiter_fee_bps: None,
+            milestones: Some(milestones),
+            htlc: None,
+            cancel_policy: None,
+            cancel_penalty: None,
+            initiator_assets: None,
+            counterparty_assets: None,
+        };
+        let info = message_info(&Addr::unchecked("initiator"), &coins(1000, "tokenA"));
+        execute(deps.as_mut(), mock_env(), info, msg)
+    }
+
+    #[test]
+    fn initiate_agreement_invalid_milestones() {
+        let mut deps = initialize_contract();
+
+        let res =
+            initiate_milestone_agreement(&mut deps, vec![Uint128::new(500), Uint128::new(1000)]);
+
+        match res.err().unwrap() {
+            ContractError::InvalidMilestones { reason } => assert_eq!(
+                reason,
+                "milestone amounts must add up to the counterparty token amount"
+            ),
+            _ => panic!("Unexpected error"),
+        }
+    }
+
+    #[test]
+    fn approve_milestones() {
+        let mut deps = initialize_contract();
+
+        initiate_milestone_agreement(&mut deps, vec![Uint128::new(500), Uint128::new(1500)])
+            .unwrap();
+
+        let accept_msg = ExecuteMsg::AcceptAgreement { id: 1 };
+        let accept_info = message_info(&addr("counterparty"), &coins(2000, "tokenB"));
+        execute(deps.as_mut(), mock_env(), accept_info, accept_msg).unwrap();
+
+        // Milestone agreements cannot be executed in one step
+        let execute_msg = ExecuteMsg::ExecuteAgreement {
+            id: 1,
+            preimage: None,
+        };
+        let initiator_info = message_info(&Addr::unchecked("initiator"), &[]);
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            initiator_info.clone(),
+            execute_msg,
+        );
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::MilestoneAgreement { id: 1 }
+        ));
+
+        // Milestones are released in order
+        let msg = ExecuteMsg::ApproveMilestone { id: 1, index: 1 };
+        let res = execute(deps.as_mut(), mock_env(), initiator_info.clone(), msg);
+        match res.err().unwrap() {
+            ContractError::InvalidMilestone { id, index, next } => {
+                assert_eq!(id, 1);
+                assert_eq!(index, 1);
+                assert_eq!(next, "0");
+            }
+            _ => panic!("Unexpected error"),
+        }
+
+        let msg = ExecuteMsg::ApproveMilestone { id: 1, index: 0 };
+        let res = execute(deps.as_mut(), mock_env(), initiator_info.clone(), msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "initiator".to_string(),
+                amount: coins(500, "tokenB"),
+            })]
+        );
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetMilestones { id: 1 }).unwrap();
+        let value: MilestonesResponse = from_json(&res).unwrap();
+        assert_eq!(value.released, 1);
+        assert_eq!(value.released_amount, Uint128::new(500));
+        assert_eq!(value.milestones.len(), 2);
+
+        // A partly released agreement can no longer be canceled
+        let cancel_msg = ExecuteMsg::CancelAgreement { id: 1 };
+        let cancel_info = message_info(&addr("counterparty"), &[]);
+        let res = execute(deps.as_mut(), mock_env(), cancel_info, cancel_msg);
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::MilestoneReleased { id: 1 }
+        ));
+
+        // Releasing the last milestone executes the agreement
+        let msg = ExecuteMsg::ApproveMilestone { id: 1, index: 1 };
+        let res = execute(deps.as_mut(), mock_env(), initiator_info, msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(BankMsg::Send {
+                    to_address: "initiator".to_string(),
+                    amount: coins(1500, "tokenB"),
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: addr("counterparty").to_string(),
+                    amount: coins(1000, "tokenA"),
+                }),
+            ]
+        );
+
+        check_agreement_counts(&deps, 1, 0, 0, 1, 0);
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetAgreement { id: 1 }).unwrap();
+        let value: AgreementResponse = from_json(&res).unwrap();
+        assert_eq!(value.agreement.status, AgreementStatus::Executed);
+    }
+
+    #[test]
+    fn approve_milestones_with_fees() {
+        let mut deps = initialize_contract();
+        let treasury = set_protocol_fees(&mut deps);
+
+        initiate_milestone_agreement(&mut deps, vec![Uint128::new(800), Uint128::new(1200)])
+            .unwrap();
+        let accept_msg = ExecuteMsg::AcceptAgreement { id: 1 };
+        let accept_info = message_info(&addr("counterparty"), &coins(2000, "tokenB"));
+        execute(deps.as_mut(), mock_env(), accept_info, accept_msg).unwrap();
+
+        // Each released milestone pays the counterparty fee
+        let initiator_info = message_info(&Addr::unchecked("initiator"), &[]);
+        let msg = ExecuteMsg::ApproveMilestone { id: 1, index: 0 };
+        let res = execute(deps.as_mut(), mock_env(), initiator_info.clone(), msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(BankMsg::Send {
+                    to_address: "initiator".to_string(),
+                    amount: coins(798, "tokenB"),
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: treasury.to_string(),
+                    amount: coins(2, "tokenB"),
+                }),
+            ]
+        );
+
+        // The last milestone also pays the initiator fee on the settled initiator token
+        let msg = ExecuteMsg::ApproveMilestone { id: 1, index: 1 };
+        let res = execute(deps.as_mut(), mock_env(), initiator_info, msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(BankMsg::Send {
+                    to_address: "initiator".to_string(),
+                    amount: coins(1197, "tokenB"),
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: addr("counterparty").to_string(),
+                    amount: coins(990, "tokenA"),
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: treasury.to_string(),
+                    amount: vec![Coin::new(10u128, "tokenA"), Coin::new(3u128, "tokenB")],
+                }),
+            ]
+        );
+        assert!(res
+            .attributes
+            .contains(&Attribute::new("fees", "native:tokenB:3,native:tokenA:10")));
+    }
+
+    fn initiate_htlc_agreement(
+        deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>,
+        env: &Env,
+        preimage: &[u8],
+    ) -> Result<Response, ContractError> {
+        let msg = ExecuteMsg::InitiateAgreement {
+            initiator_token: Asset::native("tokenA", 1000u128),
+            counterparty_token: Asset::native("tokenB", 2000u128),
+            counterparty: Some(addr("counterparty")),
+            expires: None,
+            arbiter: None,
+            arbiter_fee_bps: None,
+            milestones: None,
+            htlc: Some(Htlc {
+                hashlock: HexBinary::from(Sha256::digest(preimage).as_slice()),
+                timelock: Expiration::AtTime(env.block.time.plus_seconds(60)),
+            }),
+            cancel_policy: None,
+            cancel_penalty: None,
+            initiator_assets: None,
+            counterparty_assets: None,
+        };
+        let info = message_info(&Addr::unchecked("initiator"), &coins(1000, "tokenA"));
+        execute(deps.as_mut(), env.clone(), info, msg)
+    }
+
+    #[test]
+    fn initiate_agreement_invalid_htlc() {
+        let mut deps = initialize_contract();
+        let env = mock_env();
+
+        let htlc_msg = |hashlock: HexBinary, timelock: Expiration| ExecuteMsg::InitiateAgreement {
+            initiator_token: Asset::native("tokenA", 1000u128),
+            counterparty_token: Asset::native("tokenB", 2000u128),
+            counterparty: Some(addr("counterparty")),
+            expires: None,
+            arbiter: None,
+            arbiter_fee_bps: None,
+            milestones: None,
+            htlc: Some(Htlc { hashlock, timelock }),
+            cancel_policy: None,
+            cancel_penalty: None,
+            initiator_assets: None,
+            counterparty_assets: None,
+        };
+        let hashlock = HexBinary::from(Sha256::digest(b"secret").as_slice());
+        let info = message_info(&Addr::unchecked("initiator"), &coins(1000, "tokenA"));
+
+        // The hashlock must be a sha256 digest and the timelock must pass at some point
+        for msg in [
+            htlc_msg(
+                HexBinary::from(b"secret"),
+                Expiration::AtTime(env.block.time.plus_seconds(60)),
+            ),
+            htlc_msg(hashlock.clone(), Expiration::Never {}),
+            htlc_msg(hashlock, Expiration::AtHeight(env.block.height)),
+        ] {
+            let res = execute(deps.as_mut(), env.clone(), info.clone(), msg);
+            assert!(matches!(
+                res.err().unwrap(),
+                ContractError::InvalidHtlc { .. }
+            ));
+        }
+
+        check_agreement_counts(&deps, 0, 0, 0, 0, 0);
+    }
+
+    #[test]
+    fn execute_htlc_agreement_with_preimage() {
+        let mut deps = initialize_contract();
+        let env = mock_env();
+
+        initiate_htlc_agreement(&mut deps, &env, b"secret").unwrap();
+
+        let accept_msg = ExecuteMsg::AcceptAgreement { id: 1 };
+        let accept_info = message_info(&addr("counterparty"), &coins(2000, "tokenB"));
+        execute(deps.as_mut(), env.clone(), accept_info, accept_msg).unwrap();
+
+        // Neither party can back out of an accepted HTLC agreement
+        let cancel_msg = ExecuteMsg::CancelAgreement { id: 1 };
+        let initiator_info = message_info(&Addr::unchecked("initiator"), &[]);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            initiator_info.clone(),
+            cancel_msg,
+        );
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::HtlcLocked { id: 1 }
+        ));
+
+        // Executing without or with a wrong preimage fails
+        for preimage in [None, Some(HexBinary::from(b"guess"))] {
+            let execute_msg = ExecuteMsg::ExecuteAgreement { id: 1, preimage };
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                initiator_info.clone(),
+                execute_msg,
+            );
+            assert!(matches!(
+                res.err().unwrap(),
+                ContractError::InvalidPreimage { id: 1 }
+            ));
+        }
+
+        // The correct preimage executes the agreement and is revealed in the events
+        let execute_msg = ExecuteMsg::ExecuteAgreement {
+            id: 1,
+            preimage: Some(HexBinary::from(b"secret")),
+        };
+        let res = execute(deps.as_mut(), env, initiator_info, execute_msg).unwrap();
+
+        assert_eq!(res.messages.len(), 2);
+        assert!(res.attributes.contains(&Attribute::new(
+            "preimage",
+            HexBinary::from(b"secret").to_hex()
+        )));
+        check_agreement_counts(&deps, 1, 0, 0, 1, 0);
+    }
+
+    #[test]
+    fn refund_htlc_agreement_after_timelock() {
+        let mut deps = initialize_contract();
+        let mut env = mock_env();
+
+        initiate_htlc_agreement(&mut deps, &env, b"secret").unwrap();
+
+        let accept_msg = ExecuteMsg::AcceptAgreement { id: 1 };
+        let accept_info = message_info(&addr("counterparty"), &coins(2000, "tokenB"));
+        execute(deps.as_mut(), env.clone(), accept_info, accept_msg).unwrap();
+
+        // Refunding before the timelock fails
+        let refund_msg = ExecuteMsg::RefundHtlc { id: 1 };
+        let refund_info = message_info(&Addr::unchecked("anyone"), &[]);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            refund_info.clone(),
+            refund_msg.clone(),
+        );
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::AgreementNotExpired { id: 1 }
+        ));
+
+        // Once the timelock passed the preimage no longer executes the agreement
+        env.block.time = env.block.time.plus_seconds(60);
+        let execute_msg = ExecuteMsg::ExecuteAgreement {
+            id: 1,
+            preimage: Some(HexBinary::from(b"secret")),
+        };
+        let initiator_info = message_info(&Addr::unchecked("initiator"), &[]);
+        let res = execute(deps.as_mut(), env.clone(), initiator_info, execute_msg);
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::AgreementExpired { id: 1 }
+        ));
+
+        // Anyone can refund both deposits
+        let res = execute(deps.as_mut(), env, refund_info, refund_msg).unwrap();
+
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(BankMsg::Send {
+                    to_address: addr("counterparty").to_string(),
+                    amount: coins(2000, "tokenB"),
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: "initiator".to_string(),
+                    amount: coins(1000, "tokenA"),
+                }),
+            ]
+        );
+        check_agreement_counts(&deps, 1, 0, 0, 0, 0);
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetAgreement { id: 1 }).unwrap();
+        let value: AgreementResponse = from_json(&res).unwrap();
+        assert_eq!(value.agreement.status, AgreementStatus::Expired);
+    }
+
+    #[test]
+    fn accept_htlc_agreement_after_timelock() {
+        let mut deps = initialize_contract();
+        let mut env = mock_env();
+
+        initiate_htlc_agreement(&mut deps, &env, b"secret").unwrap();
+
+        // The counterparty can no longer accept once the initiator may refund its deposit
+        env.block.time = env.block.time.plus_seconds(60);
+        let accept_msg = ExecuteMsg::AcceptAgreement { id: 1 };
+        let accept_info = message_info(&addr("counterparty"), &coins(2000, "tokenB"));
+        let res = execute(deps.as_mut(), env.clone(), accept_info, accept_msg);
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::AgreementExpired { id: 1 }
+        ));
+        check_agreement_counts(&deps, 1, 1, 0, 0, 0);
+
+        // The initiator refunds its deposit instead
+        let refund_msg = ExecuteMsg::RefundHtlc { id: 1 };
+        let refund_info = message_info(&Addr::unchecked("initiator"), &[]);
+        let res = execute(deps.as_mut(), env, refund_info, refund_msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "initiator".to_string(),
+                amount: coins(1000, "tokenA"),
+            })]
+        );
+    }
+
+    #[test]
+    fn dispute_htlc_agreement() {
+        let mut deps = initialize_contract();
+        let env = mock_env();
+
+        let msg = ExecuteMsg::InitiateAgreement {
+            initiator_token: Asset::native("tokenA", 1000u128),
+            counterparty_token: Asset::native("tokenB", 2000u128),
+            counterparty: Some(addr("counterparty")),
+            expires: None,
+            arbiter: Some(deps.api.addr_make("arbiter")),
+            arbiter_fee_bps: None,
+            milestones: None,
+            htlc: Some(Htlc {
+                hashlock: HexBinary::from(Sha256::digest(b"secret").as_slice()),
+                timelock: Expiration::AtTime(env.block.time.plus_seconds(60)),
+            }),
+            cancel_policy: None,
+            cancel_penalty: None,
+            initiator_assets: None,
+            counterparty_assets: None,
+        };
+        let info = message_info(&Addr::unchecked("initiator"), &coins(1000, "tokenA"));
+        execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+        let accept_msg = ExecuteMsg::AcceptAgreement { id: 1 };
+        let accept_info = message_info(&addr("counterparty"), &coins(2000, "tokenB"));
+        execute(deps.as_mut(), env.clone(), accept_info, accept_msg).unwrap();
+
+        // Even with an arbiter, hash time locked agreements settle by the preimage or timelock
+        let dispute_msg = ExecuteMsg::RaiseDispute {
+            id: 1,
+            reason: "preimage withheld".to_string(),
+        };
+        let info = message_info(&addr("counterparty"), &[]);
+        let res = execute(deps.as_mut(), env, info, dispute_msg);
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::HtlcLocked { id: 1 }
+        ));
+        check_agreement_counts(&deps, 1, 0, 1, 0, 0);
+    }
+
+    #[test]
+    fn refund_agreement_without_htlc() {
+        let mut deps = initialize_contract();
+        initiate_new_agreement(&mut deps, "initiator", 1000, "counterparty", 2000);
+
+        let refund_msg = ExecuteMsg::RefundHtlc { id: 1 };
+        let refund_info = message_info(&Addr::unchecked("anyone"), &[]);
+        let res = execute(deps.as_mut(), mock_env(), refund_info, refund_msg);
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::NotHtlc { id: 1 }
+        ));
+    }
+
+    #[test]
+    fn cancel_agreement() {
+        let mut deps = initialize_contract();
+
+        initiate_new_agreement(&mut deps, "initiator", 1000, "counterparty", 2000);
+
+        let msg = ExecuteMsg::CancelAgreement { id: 1 };
+        let info = message_info(&Addr::unchecked("initiator"), &coins(1000, "tokenA"));
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        assert_eq!(
+            res.attributes,
+            vec![
+                Attribute {
+                    key: "method".to_string(),
+                    value: "cancel_agreement".to_string()
+                },
+                Attribute {
+                    key: "id".to_string(),
+                    value: "1".to_string()
+                },
+                Attribute {
+                    key: "status".to_string(),
+                    value: AgreementStatus::Canceled.to_string()
+                },
+                Attribute {
+                    key: "initiator".to_string(),
+                    value: "initiator".to_string()
+                },
+                Attribute {
+                    key: "counterparty".to_string(),
+                    value: addr("counterparty").to_string()
+                },
+                Attribute {
+                    key: "initiator_token".to_string(),
+                    value: "native:tokenA:1000".to_string()
+                },
+                Attribute {
+                    key: "counterparty_token".to_string(),
+                    value: "native:tokenB:2000".to_string()
+                }
+            ]
+        );
+
+        // Check agreement status counts
+        check_agreement_counts(&deps, 1, 0, 0, 0, 1);
+
+        let query_msg = QueryMsg::GetAgreement { id: 1 };
+        let query_res = query(deps.as_ref(), mock_env(), query_msg).unwrap();
+        let agreement_response: AgreementResponse = from_json(&query_res).unwrap();
+
+        assert_eq!(
+            agreement_response.agreement.status,
+            AgreementStatus::Canceled
+        );
+    }
+
+    fn initiate_penalized_agreement(
+        deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>,
+        cancel_penalty: CancelPenalty,
+    ) -> Result<Response, ContractError> {
+        let msg = ExecuteMsg::InitiateAgreement {
+            initiator_token: Asset::native("tokenA", 1000u128),
+            counterparty_token: Asset::native("tokenB", 2000u128),
+            counterparty: Some(addr("counterparty")),
+            expires: None,
+            arbiter: None,
+            arbiter_fee_bps: None,
+            milestones: None,
+            htlc: None,
+            cancel_policy: None,
+            cancel_penalty: Some(cancel_penalty),
+            initiator_assets: None,
+            counterparty_assets: None,
+        };
+        let info = message_info(&Addr::unchecked("initiator"), &coins(1000, "tokenA"));
+        execute(deps.as_mut(), mock_env(), info, msg)
+    }
+
+    #[test]
+    fn cancel_accepted_agreement_with_penalty() {
+        let mut deps = initialize_contract();
+        initiate_penalized_agreement(&mut deps, CancelPenalty::Bps { bps: 1000 }).unwrap();
+
+        let accept_msg = ExecuteMsg::AcceptAgreement { id: 1 };
+        let info = message_info(&addr("counterparty"), &coins(2000, "tokenB"));
+        execute(deps.as_mut(), mock_env(), info, accept_msg).unwrap();
+
+        // The canceling counterparty pays 10% of its deposit to the initiator
+        let cancel_msg = ExecuteMsg::CancelAgreement { id: 1 };
+        let info = message_info(&addr("counterparty"), &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, cancel_msg).unwrap();
+
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(BankMsg::Send {
+                    to_address: addr("counterparty").to_string(),
+                    amount: coins(1800, "tokenB"),
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: "initiator".to_string(),
+                    amount: coins(1000, "tokenA"),
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: "initiator".to_string(),
+                    amount: coins(200, "tokenB"),
+                }),
+            ]
+        );
+        assert!(res
+            .attributes
+            .contains(&Attribute::new("penalty_paid_to", "initiator")));
+        check_agreement_counts(&deps, 1, 0, 0, 0, 1);
+    }
+
+    #[test]
+    fn cancel_penalty_applies_only_after_acceptance() {
+        let mut deps = initialize_contract();
+        let penalty = CancelPenalty::Fixed {
+            initiator: Uint128::new(300),
+            counterparty: Uint128::new(500),
+        };
+        initiate_penalized_agreement(&mut deps, penalty).unwrap();
+
+        // Canceling an initiated agreement refunds the whole deposit
+        let cancel_msg = ExecuteMsg::CancelAgreement { id: 1 };
+        let info = message_info(&Addr::unchecked("initiator"), &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, cancel_msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "initiator".to_string(),
+                amount: coins(1000, "tokenA"),
+            })]
+        );
+    }
+
+    #[test]
+    fn initiate_agreement_invalid_cancel_penalty() {
+        let mut deps = initialize_contract();
+
+        for penalty in [
+            CancelPenalty::Bps { bps: 10_001 },
+            CancelPenalty::Fixed {
+                initiator: Uint128::new(1001),
+                counterparty: Uint128::zero(),
+            },
+        ] {
+            let res = initiate_penalized_agreement(&mut deps, penalty);
+            assert!(matches!(
+                res.err().unwrap(),
+                ContractError::InvalidCancelPenalty { .. }
+            ));
+        }
+    }
+
+    #[test]
+    fn swap_asset_baskets() {
+        let mut deps = initialize_contract();
+        let msg = InitiateAgreement {
+            initiator_token: Asset::native("uatom", 100u128),
+            counterparty_token: Asset::native("uusdc", 300u128),
+            initiator_assets: Some(vec![Asset::native("uosmo", 50u128)]),
+            ..Default::default()
+        };
+        let basket = vec![Coin::new(100u128, "uatom"), Coin::new(50u128, "uosmo")];
+
+        // The initiator's funds must match the basket exactly
+        let res = initiate(&mut deps, msg.clone(), &coins(100, "uatom"));
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::InsufficientFunds
+        ));
+        let funds = vec![Coin::new(100u128, "uatom"), Coin::new(40u128, "uosmo")];
+        let res = initiate(&mut deps, msg.clone(), &funds);
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::IncorrectFundsAmount { .. }
+        ));
+        let mut funds = basket.clone();
+        funds.push(Coin::new(1u128, "ujuno"));
+        let res = initiate(&mut deps, msg.clone(), &funds);
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::UnexpectedFunds { .. }
+        ));
+
+        let res = initiate(&mut deps, msg, &basket).unwrap();
+        assert!(res
+            .attributes
+            .contains(&Attribute::new("initiator_assets", "native:uosmo:50")));
+
+        // The counterparty's funds must match its side exactly as well
+        let accept_msg = ExecuteMsg::AcceptAgreement { id: 1 };
+        let funds = vec![Coin::new(50u128, "uosmo"), Coin::new(300u128, "uusdc")];
+        let info = message_info(&addr("counterparty"), &funds);
+        let res = execute(deps.as_mut(), mock_env(), info, accept_msg.clone());
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::UnexpectedFunds { .. }
+        ));
+        let info = message_info(&addr("counterparty"), &coins(300, "uusdc"));
+        execute(deps.as_mut(), mock_env(), info, accept_msg).unwrap();
+
+        // Each party receives the other side's assets in a single bank send
+        let execute_msg = ExecuteMsg::ExecuteAgreement {
+            id: 1,
+            preimage: None,
+        };
+        let info = message_info(&Addr::unchecked("initiator"), &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, execute_msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(BankMsg::Send {
+                    to_address: "initiator".to_string(),
+                    amount: coins(300, "uusdc"),
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: addr("counterparty").to_string(),
+                    amount: basket,
+                }),
+            ]
+        );
+        check_agreement_counts(&deps, 1, 0, 0, 1, 0);
+    }
+
+    #[test]
+    fn cancel_basket_agreement_refunds_in_one_send() {
+        let mut deps = initialize_contract();
+        let basket = vec![Coin::new(100u128, "uatom"), Coin::new(50u128, "uosmo")];
+        initiate(
+            &mut deps,
+            InitiateAgreement {
+                initiator_token: Asset::native("uatom", 100u128),
+                counterparty_token: Asset::native("uusdc", 300u128),
+                initiator_assets: Some(vec![Asset::native("uosmo", 50u128)]),
+                ..Default::default()
+            },
+            &basket,
+        )
+        .unwrap();
+
+        let cancel_msg = ExecuteMsg::CancelAgreement { id: 1 };
+        let info = message_info(&Addr::unchecked("initiator"), &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, cancel_msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "initiator".to_string(),
+                amount: basket,
+            })]
+        );
+    }
+
+    #[test]
+    fn invalid_baskets() {
+        let mut deps = initialize_contract();
+        let basket = vec![Coin::new(100u128, "uatom"), Coin::new(50u128, "uosmo")];
+
+        // Baskets only hold distinct bank denoms
+        let cw20 = Asset::cw20(deps.api.addr_make("cw20"), 50u128);
+        let res = initiate(
+            &mut deps,
+            InitiateAgreement {
+                initiator_token: Asset::native("uatom", 100u128),
+                counterparty_token: Asset::native("uusdc", 300u128),
+                counterparty_assets: Some(vec![cw20]),
+                ..Default::default()
+            },
+            &coins(100, "uatom"),
+        );
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::InvalidBasket { .. }
+        ));
+        let duplicate = vec![Asset::native("uatom", 50u128)];
+        let res = initiate(
+            &mut deps,
+            InitiateAgreement {
+                initiator_token: Asset::native("uatom", 100u128),
+                counterparty_token: Asset::native("uusdc", 300u128),
+                initiator_assets: Some(duplicate),
+                ..Default::default()
+            },
+            &coins(150, "uatom"),
+        );
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::InvalidBasket { .. }
+        ));
+
+        // Milestones cannot split a counterparty basket
+        let msg = InitiateAgreement {
+            initiator_token: Asset::native("uatom", 100u128),
+            counterparty_token: Asset::native("uusdc", 300u128),
+            milestones: Some(vec![Uint128::new(100), Uint128::new(200)]),
+            counterparty_assets: Some(vec![Asset::native("uosmo", 50u128)]),
+            ..Default::default()
+        };
+        let res = initiate(&mut deps, msg, &coins(100, "uatom"));
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::InvalidBasket { .. }
+        ));
+
+        // The terms of a basket agreement cannot be amended or partially filled
+        initiate(
+            &mut deps,
+            InitiateAgreement {
+                initiator_token: Asset::native("uatom", 100u128),
+                counterparty_token: Asset::native("uusdc", 300u128),
+                initiator_assets: Some(vec![Asset::native("uosmo", 50u128)]),
+                ..Default::default()
+            },
+            &basket,
+        )
+        .unwrap();
+        let amend_msg = ExecuteMsg::AmendAgreement {
+            id: 1,
+            counterparty: Some(addr("counterparty")),
+            counterparty_token: Asset::native("uusdc", 400u128),
+            initiator_token: Asset::native("uatom", 100u128),
+        };
+        let info = message_info(&Addr::unchecked("initiator"), &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, amend_msg);
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::BasketAgreement { id: 1 }
+        ));
+        let fill_msg = ExecuteMsg::FillAgreement { id: 1 };
+        let info = message_info(&addr("counterparty"), &coins(150, "uusdc"));
+        let res = execute(deps.as_mut(), mock_env(), info, fill_msg);
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::InvalidFill { .. }
+        ));
+    }
+
+    #[test]
+    fn mutual_consent_cancel() {
+        let mut deps = initialize_contract();
+
+        let msg = ExecuteMsg::InitiateAgreement {
+            initiator_token: Asset::native("tokenA", 1000u128),
+            counterparty_token: Asset::native("tokenB", 2000u128),
+            counterparty: Some(addr("counterparty")),
+            expires: None,
+            arbiter: None,
+            arbiter_fee_bps: None,
+            milestones: None,
+            htlc: None,
+            cancel_policy: Some(CancelPolicy::MutualConsent),
+            cancel_penalty: None,
+            initiator_assets: None,
+            counterparty_assets: None,
+        };
+        let info = message_info(&Addr::unchecked("initiator"), &coins(1000, "tokenA"));
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let accept_msg = ExecuteMsg::AcceptAgreement { id: 1 };
+        let counterparty_info = message_info(&addr("counterparty"), &coins(2000, "tokenB"));
+        execute(deps.as_mut(), mock_env(), counterparty_info, accept_msg).unwrap();
+
+        // Neither party can cancel alone once accepted
+        let initiator_info = message_info(&Addr::unchecked("initiator"), &[]);
+        let counterparty_info = message_info(&addr("counterparty"), &[]);
+        let cancel_msg = ExecuteMsg::CancelAgreement { id: 1 };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            initiator_info.clone(),
+            cancel_msg,
+        );
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::MutualCancelRequired { id: 1 }
+        ));
+
+        // Confirming needs a request of the other party
+        let confirm_msg = ExecuteMsg::ConfirmCancel { id: 1 };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            counterparty_info.clone(),
+            confirm_msg.clone(),
+        );
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::NoCancelRequest { id: 1 }
+        ));
+
+        let request_msg = ExecuteMsg::RequestCancel { id: 1 };
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            initiator_info.clone(),
+            request_msg,
+        )
+        .unwrap();
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetCancelRequest { id: 1 },
+        )
+        .unwrap();
+        let value: CancelRequestResponse = from_json(&res).unwrap();
+        assert_eq!(value.cancel_policy, CancelPolicy::MutualConsent);
+        assert_eq!(value.requested_by, Some(Addr::unchecked("initiator")));
+
+        // The requesting party cannot confirm its own request
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            initiator_info,
+            confirm_msg.clone(),
+        );
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::NoCancelRequest { id: 1 }
+        ));
+
+        // The other party's confirmation cancels the agreement and refunds both parties
+        let res = execute(deps.as_mut(), mock_env(), counterparty_info, confirm_msg).unwrap();
+        assert_eq!(res.messages.len(), 2);
+        check_agreement_counts(&deps, 1, 0, 0, 0, 1);
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetCancelRequest { id: 1 },
+        )
+        .unwrap();
+        let value: CancelRequestResponse = from_json(&res).unwrap();
+        assert_eq!(value.requested_by, None);
+    }
+
+    #[test]
+    fn settling_agreement_clears_cancel_request() {
+        let mut deps = initialize_contract();
+        let arbiter = deps.api.addr_make("arbiter");
+
+        // Two accepted agreements requiring mutual consent, each with a pending cancel request
+        for id in 1..=2 {
+            let msg = ExecuteMsg::InitiateAgreement {
+                initiator_token: Asset::native("tokenA", 1000u128),
+                counterparty_token: Asset::native("tokenB", 2000u128),
+                counterparty: Some(addr("counterparty")),
+                expires: None,
+                arbiter: Some(arbiter.clone()),
+                arbiter_fee_bps: None,
+                milestones: None,
+                htlc: None,
+                cancel_policy: Some(CancelPolicy::MutualConsent),
+                cancel_penalty: None,
+                initiator_assets: None,
+                counterparty_assets: None,
+            };
+            let info = message_info(&Addr::unchecked("initiator"), &coins(1000, "tokenA"));
+            execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+            let accept_msg = ExecuteMsg::AcceptAgreement { id };
+            let info = message_info(&addr("counterparty"), &coins(2000, "tokenB"));
+            execute(deps.as_mut(), mock_env(), info, accept_msg).unwrap();
+
+            let request_msg = ExecuteMsg::RequestCancel { id };
+            let info = message_info(&Addr::unchecked("initiator"), &[]);
+            execute(deps.as_mut(), mock_env(), info, request_msg).unwrap();
+        }
+
+        // Executing or disputing the agreement drops the request it no longer applies to
+        let execute_msg = ExecuteMsg::ExecuteAgreement {
+            id: 1,
+            preimage: None,
+        };
+        let info = message_info(&addr("counterparty"), &[]);
+        execute(deps.as_mut(), mock_env(), info, execute_msg).unwrap();
+        let dispute_msg = ExecuteMsg::RaiseDispute {
+            id: 2,
+            reason: "goods not delivered".to_string(),
+        };
+        let info = message_info(&addr("counterparty"), &[]);
+        execute(deps.as_mut(), mock_env(), info, dispute_msg).unwrap();
+
+        assert_eq!(CANCEL_REQUESTS.may_load(&deps.storage, 1).unwrap(), None);
+        assert_eq!(CANCEL_REQUESTS.may_load(&deps.storage, 2).unwrap(), None);
+    }
+
+    #[test]
+    fn request_cancel_of_unilateral_agreement() {
+        let mut deps = initialize_contract();
+        initiate_new_agreement(&mut deps, "initiator", 1000, "counterparty", 2000);
+
+        let accept_msg = ExecuteMsg::AcceptAgreement { id: 1 };
+        let info = message_info(&addr("counterparty"), &coins(2000, "tokenB"));
+        execute(deps.as_mut(), mock_env(), info, accept_msg).unwrap();
+
+        let request_msg = ExecuteMsg::RequestCancel { id: 1 };
+        let info = message_info(&Addr::unchecked("initiator"), &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, request_msg);
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::UnilateralCancel { id: 1 }
+        ));
+    }
+
+    #[test]
+    fn reject_agreement() {
+        let mut deps = initialize_contract();
+        initiate_new_agreement(&mut deps, "initiator", 1000, "counterparty", 2000);
+
+        // Only the counterparty can reject
+        let reject_msg = ExecuteMsg::RejectAgreement {
+            id: 1,
+            reason: "price too low".to_string(),
+        };
+        let info = message_info(&Addr::unchecked("initiator"), &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, reject_msg.clone());
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::Unauthorized { .. }
+        ));
+
+        // Rejecting refunds the initiator
+        let info = message_info(&addr("counterparty"), &[]);
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), reject_msg.clone()).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "initiator".to_string(),
+                amount: coins(1000, "tokenA"),
+            })]
+        );
+        assert!(res
+            .attributes
+            .contains(&Attribute::new("reason", "price too low")));
+
+        // The rejection is recorded apart from cancellations
+        check_agreement_counts(&deps, 1, 0, 0, 0, 0);
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetRejectedAgreementCount {},
+        )
+        .unwrap();
+        let value: RejectedAgreementCountResponse = from_json(&res).unwrap();
+        assert_eq!(value.rejected_agreement_count, 1);
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetAgreement { id: 1 }).unwrap();
+        let value: AgreementResponse = from_json(&res).unwrap();
+        assert_eq!(value.agreement.status, AgreementStatus::Rejected);
+        assert_eq!(
+            value.agreement.rejection,
+            Some(Rejection {
+                rejected_by: addr("counterparty"),
+                reason: "price too low".to_string(),
+            })
+        );
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetAgreementsByStatus {
+                status: AgreementStatus::Rejected,
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap();
+        let value: AgreementsResponse = from_json(&res).unwrap();
+        assert_eq!(value.agreements.len(), 1);
+
+        // A rejected agreement cannot be rejected again
+        let res = execute(deps.as_mut(), mock_env(), info, reject_msg);
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::InvalidAgreementStatus { .. }
+        ));
+    }
+
+    #[test]
+    fn accept_cancelled_agreement() {
+        let mut deps = initialize_contract();
+
+        let initiator_token = Asset::native("tokenA", 1000u128);
+        let counterparty_token = Asset::native("tokenB", 2000u128);
+        let counterparty = addr("counterparty");
+
+        let msg = ExecuteMsg::InitiateAgreement {
+            initiator_token: initiator_token.clone(),
+            counterparty_token: counterparty_token.clone(),
+            counterparty: Some(counterparty.clone()),
+            expires: None,
+            arbiter: None,
+            arbiter_fee_bps: None,
+            milestones: None,
+            htlc: None,
+            cancel_policy: None,
+            cancel_penalty: None,
+            initiator_assets: None,
+            counterparty_assets: None,
+        };
+        let info = message_info(&Addr::unchecked("initiator"), &coins(1000, "tokenA"));
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let msg = ExecuteMsg::CancelAgreement { id: 1 };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        assert_eq!(
+            res.attributes,
+            vec![
+                Attribute {
+                    key: "method".to_string(),
+                    value: "cancel_agreement".to_string()
+                },
+                Attribute {
+                    key: "id".to_string(),
+                    value: "1".to_string()
+                },
+                Attribute {
+                    key: "status".to_string(),
+                    value: AgreementStatus::Canceled.to_string()
+                },
+                Attribute {
+                    key: "initiator".to_string(),
+                    value: "initiator".to_string()
+                },
+                Attribute {
+                    key: "counterparty".to_string(),
+                    value: addr("counterparty").to_string()
+                },
+                Attribute {
+                    key: "initiator_token".to_string(),
+                    value: "native:tokenA:1000".to_string()
+                },
+                Attribute {
+                    key: "counterparty_token".to_string(),
+                    value: "native:tokenB:2000".to_string()
+                }
+            ]
+        );
+
+        let msg = ExecuteMsg::AcceptAgreement { id: 1 };
+        let info = message_info(&counterparty, &coins(2000, "tokenB"));
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg);
+
+        assert!(res.is_err());
+        match res.err().unwrap() {
+            ContractError::InvalidAgreementStatus { expected, found } => {
+                assert_eq!(expected, AgreementStatus::Initiated.to_string());
+                assert_eq!(found, AgreementStatus::Canceled.to_string());
+            }
+            _ => panic!("Unexpected error"),
+        }
+    }
+
+    fn initiate_fillable_offer(
+        deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>,
+        initiator_amount: u128,
+        counterparty_amount: u128,
+    ) {
+        let msg = ExecuteMsg::InitiateAgreement {
+            initiator_token: Asset::native("tokenA", initiator_amount),
+            counterparty_token: Asset::native("tokenB", counterparty_amount),
+            counterparty: None,
+            expires: None,
+            arbiter: None,
+            arbiter_fee_bps: None,
+            milestones: None,
+            htlc: None,
+            cancel_policy: None,
+            cancel_penalty: None,
+            initiator_assets: None,
+            counterparty_assets: None,
+        };
+        let info = message_info(
+            &Addr::unchecked("initiator"),
+            &coins(initiator_amount, "tokenA"),
+        );
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    }
+
+    fn fill(
+        deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>,
+        filler: &str,
+        amount: u128,
+    ) -> Result<Response, ContractError> {
+        let msg = ExecuteMsg::FillAgreement { id: 1 };
+        let info = message_info(&Addr::unchecked(filler), &coins(amount, "tokenB"));
+        execute(deps.as_mut(), mock_env(), info, msg)
+    }
+
+    #[test]
+    fn fill_agreement_in_parts() {
+        let mut deps = initialize_contract();
+
+        initiate_fillable_offer(&mut deps, 100, 3);
+
+        // Each fill pays the initiator and receives its share of the initiator token, rounded down
+        let res = fill(&mut deps, "filler1", 1).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(BankMsg::Send {
+                    to_address: "filler1".to_string(),
+                    amount: coins(33, "tokenA"),
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: "initiator".to_string(),
+                    amount: coins(1, "tokenB"),
+                }),
+            ]
+        );
+        assert!(res.attributes.contains(&Attribute::new("remaining", "67")));
+        fill(&mut deps, "filler2", 1).unwrap();
+
+        // The fill completing the agreement receives the rounding dust and executes it
+        let res = fill(&mut deps, "filler1", 1).unwrap();
+        assert_eq!(
+            res.messages[0],
+            SubMsg::new(BankMsg::Send {
+                to_address: "filler1".to_string(),
+                amount: coins(34, "tokenA"),
+            })
+        );
+        check_agreement_counts(&deps, 1, 0, 0, 1, 0);
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetFills {
+                id: 1,
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap();
+        let value: FillsResponse = from_json(&res).unwrap();
+        assert_eq!(
+            value.fills,
+            vec![
+                AgreementFill {
+                    index: 0,
+                    filler: Addr::unchecked("filler1"),
+                    amount: Uint128::new(1),
+                    payout: Uint128::new(33),
+                },
+                AgreementFill {
+                    index: 1,
+                    filler: Addr::unchecked("filler2"),
+                    amount: Uint128::new(1),
+                    payout: Uint128::new(33),
+                },
+                AgreementFill {
+                    index: 2,
+                    filler: Addr::unchecked("filler1"),
+                    amount: Uint128::new(1),
+                    payout: Uint128::new(34),
+                },
+            ]
+        );
+        assert_eq!(value.filled, Uint128::new(3));
+        assert_eq!(value.remaining, Uint128::zero());
+        assert_eq!(value.next_start_after, None);
+
+        // A full page returns the cursor of the next one
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetFills {
+                id: 1,
+                start_after: None,
+                limit: Some(2),
+            },
+        )
+        .unwrap();
+        let value: FillsResponse = from_json(&res).unwrap();
+        assert_eq!(value.fills.len(), 2);
+        assert_eq!(value.next_start_after, Some(1));
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetAgreementDeposits { id: 1 },
+        )
+        .unwrap();
+        let value: AgreementDepositsResponse = from_json(&res).unwrap();
+        assert!(value.deposits.is_empty());
+    }
+
+    #[test]
+    fn fill_agreement_with_fees() {
+        let mut deps = initialize_contract();
+        let treasury = set_protocol_fees(&mut deps);
+
+        initiate_fillable_offer(&mut deps, 1000, 2000);
+
+        // A full fill takes each side's fee like executing the agreement does
+        let res = fill(&mut deps, "filler", 2000).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(BankMsg::Send {
+                    to_address: "filler".to_string(),
+                    amount: coins(990, "tokenA"),
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: "initiator".to_string(),
+                    amount: coins(1995, "tokenB"),
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: treasury.to_string(),
+                    amount: vec![Coin::new(10u128, "tokenA"), Coin::new(5u128, "tokenB")],
+                }),
+            ]
+        );
+        assert!(res
+            .attributes
+            .contains(&Attribute::new("fees", "native:tokenA:10,native:tokenB:5")));
+        check_agreement_counts(&deps, 1, 0, 0, 1, 0);
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetAccruedFees {
+                denom: "tokenB".to_string(),
+            },
+        )
+        .unwrap();
+        let value: AccruedFeesResponse = from_json(&res).unwrap();
+        assert_eq!(value.amount, Uint128::new(5));
+    }
+
+    #[test]
+    fn invalid_fills() {
+        let mut deps = initialize_contract();
+
+        initiate_fillable_offer(&mut deps, 10, 100);
+
+        // Fills too small to pay out any of the initiator token are rejected
+        match fill(&mut deps, "filler", 5).unwrap_err() {
+            ContractError::InvalidFill { id, .. } => assert_eq!(id, 1),
+            _ => panic!("Unexpected error"),
+        }
+
+        // Fills beyond the unfilled amount are rejected
+        fill(&mut deps, "filler", 60).unwrap();
+        match fill(&mut deps, "filler", 50).unwrap_err() {
+            ContractError::InvalidFill { reason, .. } => {
+                assert_eq!(reason, "fill of `50` exceeds the unfilled `40`")
+            }
+            _ => panic!("Unexpected error"),
+        }
+
+        // The initiator cannot fill its own offer
+        assert!(matches!(
+            fill(&mut deps, "initiator", 10).unwrap_err(),
+            ContractError::InvalidCounterparty { .. }
+        ));
+
+        // A partially filled agreement can no longer be accepted as a whole
+        let accept_msg = ExecuteMsg::AcceptAgreement { id: 1 };
+        let info = message_info(&Addr::unchecked("taker"), &coins(100, "tokenB"));
+        let res = execute(deps.as_mut(), mock_env(), info, accept_msg);
+        assert!(matches!(
+            res.unwrap_err(),
+            ContractError::InvalidFill { .. }
+        ));
+    }
+
+    #[test]
+    fn cancel_partially_filled_agreement() {
+        let mut deps = initialize_contract();
+
+        initiate_fillable_offer(&mut deps, 1000, 2000);
+        fill(&mut deps, "filler", 500).unwrap();
+
+        // Canceling refunds the initiator token that has not been filled
+        let cancel_msg = ExecuteMsg::CancelAgreement { id: 1 };
+        let info = message_info(&Addr::unchecked("initiator"), &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, cancel_msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "initiator".to_string(),
+                amount: coins(750, "tokenA"),
+            })]
+        );
+        check_agreement_counts(&deps, 1, 0, 0, 0, 1);
+    }
+
+    proptest! {
+        #[test]
+        fn fills_pay_out_the_initiator_token_without_dust(
+            initiator_amount in 1u128..1_000_000_000_000,
+            counterparty_amount in 1u128..1_000_000_000_000,
+            splits in prop::collection::vec(1u128..1_000_000_000_000, 1..20),
+        ) {
+            let initiator_amount = Uint128::new(initiator_amount);
+            let counterparty_amount = Uint128::new(counterparty_amount);
+
+            // Split the counterparty token into fills, the last one completing the agreement
+            let mut fills: Vec<Uint128> = Vec::new();
+            let mut unfilled = counterparty_amount;
+            for split in splits {
+                let amount = Uint128::new(split).min(unfilled);
+                if amount.is_zero() {
+                    break;
+                }
+                fills.push(amount);
+                unfilled -= amount;
+            }
+            if !unfilled.is_zero() {
+                fills.push(unfilled);
+            }
+
+            let mut filled = Uint128::zero();
+            let mut paid = Uint128::zero();
+            for amount in fills {
+                let payout = fill_payout(initiator_amount, counterparty_amount, filled, amount);
+
+                // Every fill is paid its exact share, rounded down or up by less than one token
+                let exact_floor = amount.multiply_ratio(initiator_amount, counterparty_amount);
+                prop_assert!(payout + Uint128::one() >= exact_floor);
+                prop_assert!(payout <= exact_floor + Uint128::one());
+
+                filled += amount;
+                paid += payout;
+
+                // Paid out never exceeds the share of what has been filled
+                prop_assert!(paid <= filled.multiply_ratio(initiator_amount, counterparty_amount));
+            }
+
+            // Once completely filled the whole initiator token has been paid out
+            prop_assert_eq!(filled, counterparty_amount);
+            prop_assert_eq!(paid, initiator_amount);
+        }
+    }
+
+    // Returns the signing key and the account address of a test initiator
+    fn signer(deps: &OwnedDeps<MockStorage, MockApi, MockQuerier>, seed: u8) -> (SigningKey, Addr) {
+        let key = SigningKey::from_bytes(&[seed; 32].into()).unwrap();
+        let pubkey = key.verifying_key().to_encoded_point(true);
+        let account = Ripemd160::digest(Sha256::digest(pubkey.as_bytes()));
+        let addr = deps
+            .api
+            .addr_humanize(&CanonicalAddr::from(account.as_slice()))
+            .unwrap();
+        (key, addr)
+    }
+
+    fn sign_offer(env: &Env, key: &SigningKey, offer: &SignedOffer) -> (Binary, Binary) {
+        let digest = signed_offer_digest(env, offer).unwrap();
+        let signature: Signature = key.sign_prehash(&digest).unwrap();
+        let pubkey = key.verifying_key().to_encoded_point(true);
+        (
+            Binary::from(signature.to_bytes().as_slice()),
+            Binary::from(pubkey.as_bytes()),
+        )
+    }
+
+    fn new_signed_offer(initiator: &Addr, env: &Env) -> SignedOffer {
+        SignedOffer {
+            initiator: initiator.clone(),
+            initiator_token: Asset::native("tokenA", 1000u128),
+            counterparty_token: Asset::native("tokenB", 2000u128),
+            counterparty: None,
+            nonce: 1,
+            expires: Expiration::AtTime(env.block.time.plus_seconds(60)),
+        }
+    }
+
+    fn query_balances(
+        deps: &OwnedDeps<MockStorage, MockApi, MockQuerier>,
+        owner: &Addr,
+    ) -> Vec<Asset> {
+        let msg = QueryMsg::GetBalances {
+            owner: owner.clone(),
+        };
+        let res: BalancesResponse =
+            from_json(query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        res.balances
+    }
+
+    #[test]
+    fn deposit_and_withdraw_balance() {
+        let mut deps = initialize_contract();
+        let owner = Addr::unchecked("initiator");
+
+        // Deposits of the same denom add up
+        let info = message_info(&owner, &coins(600, "tokenA"));
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            ExecuteMsg::Deposit {},
+        )
+        .unwrap();
+        execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Deposit {}).unwrap();
+        assert_eq!(
+            query_balances(&deps, &owner),
+            vec![Asset::native("tokenA", 1200u128)]
+        );
+
+        // Withdrawing more than the balance fails
+        let info = message_info(&owner, &[]);
+        let msg = ExecuteMsg::Withdraw {
+            asset: Asset::native("tokenA", 1201u128),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg);
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::InsufficientBalance { .. }
+        ));
+
+        // Withdrawing part of the balance sends it back
+        let msg = ExecuteMsg::Withdraw {
+            asset: Asset::native("tokenA", 200u128),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "initiator".to_string(),
+                amount: coins(200, "tokenA"),
+            })]
+        );
+        assert_eq!(
+            query_balances(&deps, &owner),
+            vec![Asset::native("tokenA", 1000u128)]
+        );
+    }
+
+    #[test]
+    fn accept_signed_offer() {
+        let mut deps = initialize_contract();
+        let env = mock_env();
+        let (key, initiator) = signer(&deps, 7);
+
+        // The initiator funds its balance and signs the offer off-chain
+        let info = message_info(&initiator, &coins(1000, "tokenA"));
+        execute(deps.as_mut(), env.clone(), info, ExecuteMsg::Deposit {}).unwrap();
+        let offer = new_signed_offer(&initiator, &env);
+        let (signature, pubkey) = sign_offer(&env, &key, &offer);
+
+        // The counterparty settles the offer in one transaction
+        let msg = ExecuteMsg::AcceptSignedOffer {
+            offer: offer.clone(),
+            signature,
+            pubkey,
+        };
+        let info = message_info(&addr("counterparty"), &coins(2000, "tokenB"));
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), msg.clone()).unwrap();
+
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(BankMsg::Send {
+                    to_address: addr("counterparty").to_string(),
+                    amount: coins(1000, "tokenA"),
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: initiator.to_string(),
+                    amount: coins(2000, "tokenB"),
+                }),
+            ]
+        );
+        assert!(query_balances(&deps, &initiator).is_empty());
+        check_agreement_counts(&deps, 1, 0, 0, 1, 0);
+
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetAgreement { id: 1 }).unwrap();
+        let value: AgreementResponse = from_json(&res).unwrap();
+        assert_eq!(value.agreement.status, AgreementStatus::Executed);
+        assert_eq!(value.agreement.counterparty, Some(addr("counterparty")));
+
+        let res = query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::GetSignedOffer { offer },
+        )
+        .unwrap();
+        let value: SignedOfferResponse = from_json(&res).unwrap();
+        assert_eq!(value.agreement_id, Some(1));
+        assert!(!value.revoked);
+
+        // The same offer cannot be replayed
+        let res = execute(deps.as_mut(), env, info, msg);
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::SignedOfferUsed {}
+        ));
+    }
+
+    #[test]
+    fn invalid_signed_offers() {
+        let mut deps = initialize_contract();
+        let mut env = mock_env();
+        let (key, initiator) = signer(&deps, 7);
+        let (other_key, _) = signer(&deps, 9);
+
+        let info = message_info(&initiator, &coins(1000, "tokenA"));
+        execute(deps.as_mut(), env.clone(), info, ExecuteMsg::Deposit {}).unwrap();
+        let counterparty_info = message_info(&addr("counterparty"), &coins(2000, "tokenB"));
+        let mut accept = |env: &Env, offer: &SignedOffer, signature: Binary, pubkey: Binary| {
+            let msg = ExecuteMsg::AcceptSignedOffer {
+                offer: offer.clone(),
+                signature,
+                pubkey,
+            };
+            execute(deps.as_mut(), env.clone(), counterparty_info.clone(), msg)
+                .err()
+                .unwrap()
+        };
+
+        // Offers signed by another key are rejected
+        let offer = new_signed_offer(&initiator, &env);
+        let (signature, pubkey) = sign_offer(&env, &other_key, &offer);
+        assert!(matches!(
+            accept(&env, &offer, signature.clone(), pubkey),
+            ContractError::Unauthorized { .. }
+        ));
+        let (_, pubkey) = sign_offer(&env, &key, &offer);
+        assert!(matches!(
+            accept(&env, &offer, signature, pubkey),
+            ContractError::InvalidSignature {}
+        ));
+
+        // Altered terms invalidate the signature
+        let (signature, pubkey) = sign_offer(&env, &key, &offer);
+        let mut altered = offer.clone();
+        altered.initiator_token = Asset::native("tokenA", 1u128);
+        assert!(matches!(
+            accept(&env, &altered, signature, pubkey),
+            ContractError::InvalidSignature {}
+        ));
+
+        // Offers for another counterparty cannot be taken
+        let mut private = offer.clone();
+        private.counterparty = Some(Addr::unchecked("someone"));
+        let (signature, pubkey) = sign_offer(&env, &key, &private);
+        assert!(matches!(
+            accept(&env, &private, signature, pubkey),
+            ContractError::Unauthorized { .. }
+        ));
+
+        // Offers exceeding the initiator's balance cannot be settled
+        let mut large = offer.clone();
+        large.initiator_token = Asset::native("tokenA", 1001u128);
+        let (signature, pubkey) = sign_offer(&env, &key, &large);
+        assert!(matches!(
+            accept(&env, &large, signature, pubkey),
+            ContractError::InsufficientBalance { .. }
+        ));
+
+        // Offers that never expire are rejected
+        let mut perpetual = offer.clone();
+        perpetual.expires = Expiration::Never {};
+        let (signature, pubkey) = sign_offer(&env, &key, &perpetual);
+        assert!(matches!(
+            accept(&env, &perpetual, signature, pubkey),
+            ContractError::InvalidExpiration { .. }
+        ));
+
+        // Expired offers are rejected
+        let (signature, pubkey) = sign_offer(&env, &key, &offer);
+        env.block.time = env.block.time.plus_seconds(60);
+        assert!(matches!(
+            accept(&env, &offer, signature, pubkey),
+            ContractError::SignedOfferExpired {}
+        ));
+
+        check_agreement_counts(&deps, 0, 0, 0, 0, 0);
+    }
+
+    #[test]
+    fn revoke_signed_offer() {
+        let mut deps = initialize_contract();
+        let env = mock_env();
+        let (key, initiator) = signer(&deps, 7);
+
+        let info = message_info(&initiator, &coins(1000, "tokenA"));
+        execute(deps.as_mut(), env.clone(), info, ExecuteMsg::Deposit {}).unwrap();
+        let offer = new_signed_offer(&initiator, &env);
+        let (signature, pubkey) = sign_offer(&env, &key, &offer);
+
+        // Only the initiator can revoke its offer
+        let revoke_msg = ExecuteMsg::RevokeSignedOffer {
+            offer: offer.clone(),
+        };
+        let info = message_info(&addr("counterparty"), &[]);
+        let res = execute(deps.as_mut(), env.clone(), info, revoke_msg.clone());
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::Unauthorized { .. }
+        ));
+        let info = message_info(&initiator, &[]);
+        execute(deps.as_mut(), env.clone(), info.clone(), revoke_msg.clone()).unwrap();
+
+        let res = query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::GetSignedOffer {
+                offer: offer.clone(),
+            },
+        )
+        .unwrap();
+        let value: SignedOfferResponse = from_json(&res).unwrap();
+        assert_eq!(value.agreement_id, None);
+        assert!(value.revoked);
+
+        // The revoked offer can neither be accepted nor revoked again
+        let msg = ExecuteMsg::AcceptSignedOffer {
+            offer,
+            signature,
+            pubkey,
+        };
+        let counterparty_info = message_info(&addr("counterparty"), &coins(2000, "tokenB"));
+        let res = execute(deps.as_mut(), env.clone(), counterparty_info, msg);
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::SignedOfferRevoked {}
+        ));
+        let res = execute(deps.as_mut(), env, info, revoke_msg);
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::SignedOfferRevoked {}
+        ));
+
+        // The initiator keeps its balance
+        assert_eq!(
+            query_balances(&deps, &initiator),
+            vec![Asset::native("tokenA", 1000u128)]
+        );
+        check_agreement_counts(&deps, 0, 0, 0, 0, 0);
+    }
+
+    fn amend(
+        deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>,
+        sender: &Addr,
+        funds: &[Coin],
+        counterparty: &Addr,
+        initiator_amount: u128,
+    ) -> Result<Response, ContractError> {
+        let msg = ExecuteMsg::AmendAgreement {
+            id: 1,
+            counterparty: Some(counterparty.clone()),
+            counterparty_token: Asset::native("tokenB", 2000u128),
+            initiator_token: Asset::native("tokenA", initiator_amount),
+        };
+        let info = message_info(sender, funds);
+        execute(deps.as_mut(), mock_env(), info, msg)
+    }
+
+    #[test]
+    fn amend_agreement() {
+        let mut deps = initialize_contract();
+        initiate_new_agreement(&mut deps, "initiator", 1000, "counterparty", 2000);
+
+        // The initiator fixes the counterparty and tops up its deposit
+        let res = amend(
+            &mut deps,
+            &Addr::unchecked("initiator"),
+            &coins(500, "tokenA"),
+            &addr("counterparty2"),
+            1500,
+        )
+        .unwrap();
+        assert!(res.messages.is_empty());
+        assert_eq!(res.events.len(), 1);
+        assert_eq!(res.events[0].ty, "amend_agreement");
+        assert!(res.events[0].attributes.contains(&Attribute::new(
+            "previous_counterparty",
+            addr("counterparty")
+        )));
+        assert!(res.events[0]
+            .attributes
+            .contains(&Attribute::new("counterparty", addr("counterparty2"))));
+
+        // Lowering the initiator token refunds the difference
+        let res = amend(
+            &mut deps,
+            &Addr::unchecked("initiator"),
+            &[],
+            &addr("counterparty2"),
+            800,
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "initiator".to_string(),
+                amount: coins(700, "tokenA"),
+            })]
+        );
+
+        // The agreement keeps its ID and counts as initiated only once
+        check_agreement_counts(&deps, 1, 1, 0, 0, 0);
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetAgreementsByCounterparty {
+                counterparty: addr("counterparty2"),
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap();
+        let value: AgreementsResponse = from_json(&res).unwrap();
+        assert_eq!(value.agreements.len(), 1);
+        assert_eq!(
+            value.agreements[0].initiator_token,
+            Asset::native("tokenA", 800u128)
+        );
+
+        // Only the new counterparty can accept
+        let accept_msg = ExecuteMsg::AcceptAgreement { id: 1 };
+        let info = message_info(&addr("counterparty"), &coins(2000, "tokenB"));
+        let res = execute(deps.as_mut(), mock_env(), info, accept_msg.clone());
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::Unauthorized { .. }
+        ));
+        let info = message_info(&addr("counterparty2"), &coins(2000, "tokenB"));
+        execute(deps.as_mut(), mock_env(), info, accept_msg).unwrap();
+    }
+
+    #[test]
+    fn amend_agreement_refunds_partial_counterparty_deposit() {
+        let mut deps = initialize_contract();
+        let counterparty = deps.api.addr_make("counterparty");
+        let collection = deps.api.addr_make("cw721-contract");
+
+        let msg = ExecuteMsg::InitiateAgreement {
+            initiator_token: Asset::native("tokenA", 1000u128),
+            counterparty_token: Asset::cw721(collection.clone(), &["1", "2"]),
+            counterparty: Some(counterparty.clone()),
+            expires: None,
+            arbiter: None,
+            arbiter_fee_bps: None,
+            milestones: None,
+            htlc: None,
+            cancel_policy: None,
+            cancel_penalty: None,
+            initiator_assets: None,
+            counterparty_assets: None,
         };
         let info = message_info(&Addr::unchecked("initiator"), &coins(1000, "tokenA"));
-        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // The counterparty escrows the first of its two NFTs
+        let msg = ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: counterparty.to_string(),
+            token_id: "1".to_string(),
+            msg: to_json_binary(&ReceiveMsg::AcceptAgreement { id: 1 }).unwrap(),
+        });
+        let info = message_info(&collection, &[]);
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // Replacing the counterparty returns the NFT to the previous counterparty
+        let res = amend(
+            &mut deps,
+            &Addr::unchecked("initiator"),
+            &[],
+            &addr("counterparty2"),
+            1000,
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(
+                nft_transfer_msg(&collection, "1", &counterparty).unwrap()
+            )]
+        );
+        assert!(DEPOSITS
+            .may_load(deps.as_ref().storage, (1, &counterparty))
+            .unwrap()
+            .is_none());
+
+        // Executing the amended agreement pays nothing of the refunded NFT to the initiator
+        let accept_msg = ExecuteMsg::AcceptAgreement { id: 1 };
+        let info = message_info(&addr("counterparty2"), &coins(2000, "tokenB"));
+        execute(deps.as_mut(), mock_env(), info, accept_msg).unwrap();
+        let execute_msg = ExecuteMsg::ExecuteAgreement {
+            id: 1,
+            preimage: None,
+        };
+        let info = message_info(&Addr::unchecked("initiator"), &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, execute_msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(BankMsg::Send {
+                    to_address: "initiator".to_string(),
+                    amount: coins(2000, "tokenB"),
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: addr("counterparty2").to_string(),
+                    amount: coins(1000, "tokenA"),
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_amendments() {
+        let mut deps = initialize_contract();
+        initiate_new_agreement(&mut deps, "initiator", 1000, "counterparty", 2000);
+
+        // Only the initiator can amend
+        let res = amend(
+            &mut deps,
+            &addr("counterparty"),
+            &[],
+            &addr("counterparty"),
+            1000,
+        );
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::Unauthorized { .. }
+        ));
+
+        // Increases must be attached exactly, and the new counterparty must be a valid address
+        let res = amend(
+            &mut deps,
+            &Addr::unchecked("initiator"),
+            &coins(100, "tokenA"),
+            &addr("counterparty"),
+            1200,
+        );
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::IncorrectFundsAmount { .. }
+        ));
+        let res = amend(
+            &mut deps,
+            &Addr::unchecked("initiator"),
+            &[],
+            &Addr::unchecked("Counterparty2"),
+            1000,
+        );
+        assert!(matches!(res.err().unwrap(), ContractError::Std(_)));
+
+        // Accepted agreements can no longer be amended
+        let accept_msg = ExecuteMsg::AcceptAgreement { id: 1 };
+        let info = message_info(&addr("counterparty"), &coins(2000, "tokenB"));
+        execute(deps.as_mut(), mock_env(), info, accept_msg).unwrap();
+        let res = amend(
+            &mut deps,
+            &Addr::unchecked("initiator"),
+            &[],
+            &addr("counterparty"),
+            1000,
+        );
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::InvalidAgreementStatus { .. }
+        ));
+
+        // The initiator cannot be its own counterparty
+        let mut deps = initialize_contract();
+        let initiator = addr("initiator");
+        let msg = ExecuteMsg::InitiateAgreement {
+            initiator_token: Asset::native("tokenA", 1000u128),
+            counterparty_token: Asset::native("tokenB", 2000u128),
+            counterparty: None,
+            expires: None,
+            arbiter: None,
+            arbiter_fee_bps: None,
+            milestones: None,
+            htlc: None,
+            cancel_policy: None,
+            cancel_penalty: None,
+            initiator_assets: None,
+            counterparty_assets: None,
+        };
+        let info = message_info(&initiator, &coins(1000, "tokenA"));
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let res = amend(&mut deps, &initiator, &[], &initiator, 1000);
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::InvalidCounterparty { .. }
+        ));
+    }
+
+    fn propose(
+        deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>,
+        proposer: &Addr,
+        funds: &[Coin],
+        initiator_amount: u128,
+        counterparty_amount: u128,
+    ) -> Result<Response, ContractError> {
+        let msg = ExecuteMsg::ProposeCounterOffer {
+            id: 1,
+            initiator_token: Asset::native("tokenA", initiator_amount),
+            counterparty_token: Asset::native("tokenB", counterparty_amount),
+        };
+        let info = message_info(proposer, funds);
+        execute(deps.as_mut(), mock_env(), info, msg)
+    }
+
+    #[test]
+    fn accepting_counter_offer_refunds_partial_counterparty_deposit() {
+        let mut deps = initialize_contract();
+        let counterparty = deps.api.addr_make("counterparty");
+        let collection = deps.api.addr_make("cw721-contract");
+
+        let msg = ExecuteMsg::InitiateAgreement {
+            initiator_token: Asset::native("tokenA", 1000u128),
+            counterparty_token: Asset::cw721(collection.clone(), &["1", "2"]),
+            counterparty: Some(counterparty.clone()),
+            expires: None,
+            arbiter: None,
+            arbiter_fee_bps: None,
+            milestones: None,
+            htlc: None,
+            cancel_policy: None,
+            cancel_penalty: None,
+            initiator_assets: None,
+            counterparty_assets: None,
+        };
+        let info = message_info(&Addr::unchecked("initiator"), &coins(1000, "tokenA"));
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // The counterparty escrows the first of its two NFTs
+        let msg = ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: counterparty.to_string(),
+            token_id: "1".to_string(),
+            msg: to_json_binary(&ReceiveMsg::AcceptAgreement { id: 1 }).unwrap(),
+        });
+        let info = message_info(&collection, &[]);
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // Accepting payment in tokens instead returns the NFT to the counterparty
+        propose(&mut deps, &Addr::unchecked("initiator"), &[], 1000, 2000).unwrap();
+        let msg = ExecuteMsg::AcceptCounterOffer { id: 1 };
+        let info = message_info(&counterparty, &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(
+                nft_transfer_msg(&collection, "1", &counterparty).unwrap()
+            )]
+        );
+
+        // The initiator receives only the new counterparty token on execution
+        let accept_msg = ExecuteMsg::AcceptAgreement { id: 1 };
+        let info = message_info(&counterparty, &coins(2000, "tokenB"));
+        execute(deps.as_mut(), mock_env(), info, accept_msg).unwrap();
+        let execute_msg = ExecuteMsg::ExecuteAgreement {
+            id: 1,
+            preimage: None,
+        };
+        let info = message_info(&Addr::unchecked("initiator"), &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, execute_msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(BankMsg::Send {
+                    to_address: "initiator".to_string(),
+                    amount: coins(2000, "tokenB"),
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: counterparty.to_string(),
+                    amount: coins(1000, "tokenA"),
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn negotiate_counter_offers() {
+        let mut deps = initialize_contract();
+        initiate_new_agreement(&mut deps, "initiator", 1000, "counterparty", 2000);
+
+        // The counterparty asks for more of the initiator token
+        propose(&mut deps, &addr("counterparty"), &[], 1500, 2000).unwrap();
+
+        // The agreement cannot be accepted while a counter-offer is pending
+        let accept_msg = ExecuteMsg::AcceptAgreement { id: 1 };
+        let accept_info = message_info(&addr("counterparty"), &coins(2000, "tokenB"));
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            accept_info.clone(),
+            accept_msg.clone(),
+        );
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::CounterOfferPending { id: 1 }
+        ));
+
+        // The initiator answers with a smaller increase, escrowing the difference right away
+        let res = propose(
+            &mut deps,
+            &Addr::unchecked("initiator"),
+            &coins(200, "tokenA"),
+            1200,
+            2400,
+        )
+        .unwrap();
+        assert!(res.messages.is_empty());
+
+        // The counterparty answers in turn, superseding the proposal and refunding its top-up
+        let res = propose(&mut deps, &addr("counterparty"), &[], 1100, 2400).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "initiator".to_string(),
+                amount: coins(200, "tokenA"),
+            })]
+        );
+
+        // The initiator accepts and tops up its deposit
+        let accept_offer_msg = ExecuteMsg::AcceptCounterOffer { id: 1 };
+        let initiator_info = message_info(&Addr::unchecked("initiator"), &coins(100, "tokenA"));
+        execute(deps.as_mut(), mock_env(), initiator_info, accept_offer_msg).unwrap();
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetCounterOffers {
+                id: 1,
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap();
+        let value: CounterOffersResponse = from_json(&res).unwrap();
+        assert_eq!(
+            value
+                .counter_offers
+                .iter()
+                .map(|offer| (offer.round, offer.proposer.clone(), offer.status))
+                .collect::<Vec<_>>(),
+            vec![
+                (0, addr("counterparty"), CounterOfferStatus::Superseded),
+                (
+                    1,
+                    Addr::unchecked("initiator"),
+                    CounterOfferStatus::Superseded
+                ),
+                (2, addr("counterparty"), CounterOfferStatus::Accepted),
+            ]
+        );
+        assert_eq!(value.next_start_after, None);
+
+        // A full page returns the cursor of the next one
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetCounterOffers {
+                id: 1,
+                start_after: Some(0),
+                limit: Some(1),
+            },
+        )
+        .unwrap();
+        let value: CounterOffersResponse = from_json(&res).unwrap();
+        assert_eq!(value.counter_offers[0].round, 1);
+        assert_eq!(value.next_start_after, Some(1));
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetAgreement { id: 1 }).unwrap();
+        let value: AgreementResponse = from_json(&res).unwrap();
+        assert_eq!(
+            value.agreement.initiator_token,
+            Asset::native("tokenA", 1100u128)
+        );
+        assert_eq!(
+            value.agreement.counterparty_token,
+            Asset::native("tokenB", 2400u128)
+        );
+
+        // The agreement is accepted on the negotiated terms
+        let res = execute(deps.as_mut(), mock_env(), accept_info, accept_msg);
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::IncorrectFundsAmount { .. }
+        ));
+        let accept_info = message_info(&addr("counterparty"), &coins(2400, "tokenB"));
+        let accept_msg = ExecuteMsg::AcceptAgreement { id: 1 };
+        execute(deps.as_mut(), mock_env(), accept_info, accept_msg).unwrap();
+
+        let execute_msg = ExecuteMsg::ExecuteAgreement {
+            id: 1,
+            preimage: None,
+        };
+        let initiator_info = message_info(&Addr::unchecked("initiator"), &[]);
+        let res = execute(deps.as_mut(), mock_env(), initiator_info, execute_msg).unwrap();
+        assert!(res.messages.contains(&SubMsg::new(BankMsg::Send {
+            to_address: addr("counterparty").to_string(),
+            amount: coins(1100, "tokenA"),
+        })));
+    }
+
+    #[test]
+    fn accepting_counter_offer_refunds_initiator() {
+        let mut deps = initialize_contract();
+        initiate_new_agreement(&mut deps, "initiator", 1000, "counterparty", 2000);
+
+        // The initiator offers less and the counterparty agrees
+        propose(&mut deps, &Addr::unchecked("initiator"), &[], 600, 2000).unwrap();
+        let accept_offer_msg = ExecuteMsg::AcceptCounterOffer { id: 1 };
+        let info = message_info(&addr("counterparty"), &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, accept_offer_msg).unwrap();
+
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "initiator".to_string(),
+                amount: coins(400, "tokenA"),
+            })]
+        );
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetAgreementDeposits { id: 1 },
+        )
+        .unwrap();
+        let value: AgreementDepositsResponse = from_json(&res).unwrap();
+        assert_eq!(
+            value.deposits,
+            vec![AgreementDeposit {
+                depositor: Addr::unchecked("initiator"),
+                assets: vec![Asset::native("tokenA", 600u128)],
+            }]
+        );
+    }
 
-        let msg = ExecuteMsg::CancelAgreement { id: 1 };
-        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+    #[test]
+    fn counter_offers_reject_partially_filled_agreements() {
+        let mut deps = initialize_contract();
+        initiate_new_agreement(&mut deps, "initiator", 1000, "counterparty", 2000);
+
+        // The counterparty asks for a lower price, then fills half on the current terms
+        propose(&mut deps, &addr("counterparty"), &[], 1000, 200).unwrap();
+        let fill_msg = ExecuteMsg::FillAgreement { id: 1 };
+        let info = message_info(&addr("counterparty"), &coins(1000, "tokenB"));
+        execute(deps.as_mut(), mock_env(), info, fill_msg).unwrap();
+
+        // The filled part can no longer be renegotiated
+        let accept_offer_msg = ExecuteMsg::AcceptCounterOffer { id: 1 };
+        let info = message_info(&Addr::unchecked("initiator"), &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, accept_offer_msg);
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::InvalidFill { id: 1, .. }
+        ));
+        let res = propose(&mut deps, &Addr::unchecked("initiator"), &[], 500, 1000);
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::InvalidFill { id: 1, .. }
+        ));
+    }
+
+    #[test]
+    fn amending_counts_escrowed_top_up() {
+        let mut deps = initialize_contract();
+        initiate_new_agreement(&mut deps, "initiator", 1000, "counterparty", 2000);
+
+        // The initiator escrows a top-up with a counter-offer
+        propose(
+            &mut deps,
+            &Addr::unchecked("initiator"),
+            &coins(500, "tokenA"),
+            1500,
+            2000,
+        )
+        .unwrap();
 
+        // Amending to less than the escrowed deposit needs no further funds and refunds the rest
+        let res = amend(
+            &mut deps,
+            &Addr::unchecked("initiator"),
+            &[],
+            &addr("counterparty"),
+            1200,
+        )
+        .unwrap();
         assert_eq!(
-            res.attributes,
-            vec![
-                Attribute {
-                    key: "method".to_string(),
-                    value: "cancel_agreement".to_string()
-                },
-                Attribute {
-                    key: "id".to_string(),
-                    value: "1".to_string()
-                },
-                Attribute {
-                    key: "status".to_string(),
-                    value: STATUS_CANCELED.to_string()
-                },
-                Attribute {
-                    key: "initiator".to_string(),
-                    value: "initiator".to_string()
-                },
-                Attribute {
-                    key: "counterparty".to_string(),
-                    value: "counterparty".to_string()
-                },
-                Attribute {
-                    key: "initiator_token".to_string(),
-                    value: "1000tokenA".to_string()
-                },
-                Attribute {
-                    key: "counterparty_token".to_string(),
-                    value: "2000tokenB".to_string()
-                }
-            ]
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "initiator".to_string(),
+                amount: coins(300, "tokenA"),
+            })]
+        );
+        let res = amend(
+            &mut deps,
+            &Addr::unchecked("initiator"),
+            &coins(100, "tokenA"),
+            &addr("counterparty"),
+            1200,
         );
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::UnexpectedFunds { .. }
+        ));
+    }
 
-        let msg = ExecuteMsg::AcceptAgreement { id: 1 };
-        let info = message_info(&counterparty, &coins(2000, "tokenB"));
-        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg);
+    #[test]
+    fn invalid_counter_offers() {
+        let mut deps = initialize_contract();
+        initiate_new_agreement(&mut deps, "initiator", 1000, "counterparty", 2000);
 
-        assert!(res.is_err());
-        match res.err().unwrap() {
-            ContractError::InvalidAgreementStatus { expected, found } => {
-                assert_eq!(expected, format!("{}", STATUS_INITIATED));
-                assert_eq!(found, STATUS_CANCELED);
-            }
-            _ => panic!("Unexpected error"),
-        }
+        // Only the parties can negotiate
+        let res = propose(&mut deps, &Addr::unchecked("stranger"), &[], 1000, 1500);
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::Unauthorized { .. }
+        ));
+
+        // The initiator must escrow an increase of its deposit, the counterparty sends nothing
+        let res = propose(&mut deps, &Addr::unchecked("initiator"), &[], 1200, 2000);
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::InsufficientFunds
+        ));
+        let res = propose(
+            &mut deps,
+            &addr("counterparty"),
+            &coins(100, "tokenB"),
+            1000,
+            1500,
+        );
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::UnexpectedFunds { .. }
+        ));
+
+        // There is nothing to accept before a proposal, and proposers cannot accept their own
+        let accept_offer_msg = ExecuteMsg::AcceptCounterOffer { id: 1 };
+        let info = message_info(&addr("counterparty"), &[]);
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            accept_offer_msg.clone(),
+        );
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::NoCounterOffer { id: 1 }
+        ));
+
+        propose(&mut deps, &addr("counterparty"), &[], 1000, 1500).unwrap();
+        let res = execute(deps.as_mut(), mock_env(), info, accept_offer_msg);
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::Unauthorized { .. }
+        ));
     }
 
     #[test]
     fn query_agreements_by_initiator() {
         let mut deps = initialize_contract();
 
-        let initiator_token = TokenInfo {
-            address: Addr::unchecked("tokenA"),
-            amount: 1000u128,
-        };
-        let counterparty_token = TokenInfo {
-            address: Addr::unchecked("tokenB"),
-            amount: 2000u128,
-        };
-        let counterparty = Addr::unchecked("counterparty");
+        let initiator_token = Asset::native("tokenA", 1000u128);
+        let counterparty_token = Asset::native("tokenB", 2000u128);
+        let counterparty = addr("counterparty");
 
         let msg = ExecuteMsg::InitiateAgreement {
             initiator_token: initiator_token.clone(),
             counterparty_token: counterparty_token.clone(),
-            counterparty: counterparty.clone(),
+            counterparty: Some(counterparty.clone()),
+            expires: None,
+            arbiter: None,
+            arbiter_fee_bps: None,
+            milestones: None,
+            htlc: None,
+            cancel_policy: None,
+            cancel_penalty: None,
+            initiator_assets: None,
+            counterparty_assets: None,
         };
         let info = message_info(&Addr::unchecked("initiator"), &coins(1000, "tokenA"));
         let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
@@ -590,7 +4169,16 @@ mod tests {
         let msg = ExecuteMsg::InitiateAgreement {
             initiator_token: initiator_token.clone(),
             counterparty_token: counterparty_token.clone(),
-            counterparty: Addr::unchecked("counterparty2"),
+            counterparty: Some(addr("counterparty2")),
+            expires: None,
+            arbiter: None,
+            arbiter_fee_bps: None,
+            milestones: None,
+            htlc: None,
+            cancel_policy: None,
+            cancel_penalty: None,
+            initiator_assets: None,
+            counterparty_assets: None,
         };
         let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
 
@@ -599,8 +4187,8 @@ mod tests {
             mock_env(),
             QueryMsg::GetAgreementsByInitiator {
                 initiator: Addr::unchecked("initiator"),
-                page: 0,
-                page_size: 10,
+                start_after: None,
+                limit: None,
             },
         )
         .unwrap();
@@ -609,22 +4197,128 @@ mod tests {
     }
 
     #[test]
-    fn query_agreements_by_counterparty() {
+    fn query_agreements_by_initiator_pages_over_matches() {
         let mut deps = initialize_contract();
 
-        let initiator_token = TokenInfo {
-            address: Addr::unchecked("tokenA"),
-            amount: 1000u128,
+        // Interleave the agreements of two initiators
+        for i in 0..10 {
+            let initiator = if i % 5 == 0 { "initiator" } else { "other" };
+            let msg = ExecuteMsg::InitiateAgreement {
+                initiator_token: Asset::native("tokenA", 1000u128),
+                counterparty_token: Asset::native("tokenB", 2000u128),
+                counterparty: Some(addr("counterparty")),
+                expires: None,
+                arbiter: None,
+                arbiter_fee_bps: None,
+                milestones: None,
+                htlc: None,
+                cancel_policy: None,
+                cancel_penalty: None,
+                initiator_assets: None,
+                counterparty_assets: None,
+            };
+            let info = message_info(&Addr::unchecked(initiator), &coins(1000, "tokenA"));
+            execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        }
+
+        // Each page only holds the agreements of the requested initiator
+        let query_page = |start_after| {
+            let res = query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::GetAgreementsByInitiator {
+                    initiator: Addr::unchecked("initiator"),
+                    start_after,
+                    limit: Some(1),
+                },
+            )
+            .unwrap();
+            let value: AgreementsResponse = from_json(&res).unwrap();
+            let ids = value
+                .agreements
+                .iter()
+                .map(|agreement| agreement.id)
+                .collect::<Vec<_>>();
+            (ids, value.next_start_after)
         };
-        let counterparty_token = TokenInfo {
-            address: Addr::unchecked("tokenB"),
-            amount: 2000u128,
+        assert_eq!(query_page(None), (vec![1], Some(1)));
+        assert_eq!(query_page(Some(1)), (vec![6], Some(6)));
+        assert_eq!(query_page(Some(6)), (vec![], None));
+    }
+
+    #[test]
+    fn query_agreements_by_status_limits() {
+        let mut deps = initialize_contract();
+
+        for _ in 0..35 {
+            let msg = ExecuteMsg::InitiateAgreement {
+                initiator_token: Asset::native("tokenA", 1000u128),
+                counterparty_token: Asset::native("tokenB", 2000u128),
+                counterparty: Some(addr("counterparty")),
+                expires: None,
+                arbiter: None,
+                arbiter_fee_bps: None,
+                milestones: None,
+                htlc: None,
+                cancel_policy: None,
+                cancel_penalty: None,
+                initiator_assets: None,
+                counterparty_assets: None,
+            };
+            let info = message_info(&Addr::unchecked("initiator"), &coins(1000, "tokenA"));
+            execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        }
+
+        let query_page = |start_after, limit| {
+            let res = query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::GetAgreementsByStatus {
+                    status: AgreementStatus::Initiated,
+                    start_after,
+                    limit,
+                },
+            )
+            .unwrap();
+            from_json::<AgreementsResponse>(&res).unwrap()
         };
 
+        // Without a limit the default page size applies
+        let value = query_page(None, None);
+        assert_eq!(value.agreements.len(), DEFAULT_LIMIT as usize);
+        assert_eq!(value.next_start_after, Some(10));
+
+        // Larger limits are capped at the maximum page size
+        let value = query_page(None, Some(100));
+        assert_eq!(value.agreements.len(), MAX_LIMIT as usize);
+        assert_eq!(value.next_start_after, Some(30));
+
+        // The last page has no cursor
+        let value = query_page(Some(30), Some(100));
+        assert_eq!(value.agreements.len(), 5);
+        assert_eq!(value.next_start_after, None);
+    }
+
+    #[test]
+    fn query_agreements_by_counterparty() {
+        let mut deps = initialize_contract();
+
+        let initiator_token = Asset::native("tokenA", 1000u128);
+        let counterparty_token = Asset::native("tokenB", 2000u128);
+
         let msg = ExecuteMsg::InitiateAgreement {
             initiator_token: initiator_token.clone(),
             counterparty_token: counterparty_token.clone(),
-            counterparty: Addr::unchecked("counterparty"),
+            counterparty: Some(addr("counterparty")),
+            expires: None,
+            arbiter: None,
+            arbiter_fee_bps: None,
+            milestones: None,
+            htlc: None,
+            cancel_policy: None,
+            cancel_penalty: None,
+            initiator_assets: None,
+            counterparty_assets: None,
         };
         let info = message_info(&Addr::unchecked("initiator"), &coins(1000, "tokenA"));
         let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
@@ -632,7 +4326,16 @@ mod tests {
         let msg = ExecuteMsg::InitiateAgreement {
             initiator_token: initiator_token.clone(),
             counterparty_token: counterparty_token.clone(),
-            counterparty: Addr::unchecked("counterparty2"),
+            counterparty: Some(addr("counterparty2")),
+            expires: None,
+            arbiter: None,
+            arbiter_fee_bps: None,
+            milestones: None,
+            htlc: None,
+            cancel_policy: None,
+            cancel_penalty: None,
+            initiator_assets: None,
+            counterparty_assets: None,
         };
         let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
 
@@ -640,9 +4343,9 @@ mod tests {
             deps.as_ref(),
             mock_env(),
             QueryMsg::GetAgreementsByCounterparty {
-                counterparty: Addr::unchecked("counterparty"),
-                page: 0,
-                page_size: 10,
+                counterparty: addr("counterparty"),
+                start_after: None,
+                limit: None,
             },
         )
         .unwrap();
@@ -654,19 +4357,22 @@ mod tests {
     fn query_agreements_by_status() {
         let mut deps = initialize_contract();
 
-        let initiator_token = TokenInfo {
-            address: Addr::unchecked("tokenA"),
-            amount: 1000u128,
-        };
-        let counterparty_token = TokenInfo {
-            address: Addr::unchecked("tokenB"),
-            amount: 2000u128,
-        };
+        let initiator_token = Asset::native("tokenA", 1000u128);
+        let counterparty_token = Asset::native("tokenB", 2000u128);
 
         let msg = ExecuteMsg::InitiateAgreement {
             initiator_token: initiator_token.clone(),
             counterparty_token: counterparty_token.clone(),
-            counterparty: Addr::unchecked("counterparty"),
+            counterparty: Some(addr("counterparty")),
+            expires: None,
+            arbiter: None,
+            arbiter_fee_bps: None,
+            milestones: None,
+            htlc: None,
+            cancel_policy: None,
+            cancel_penalty: None,
+            initiator_assets: None,
+            counterparty_assets: None,
         };
         let info = message_info(&Addr::unchecked("initiator"), &coins(1000, "tokenA"));
         let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
@@ -674,7 +4380,16 @@ mod tests {
         let msg = ExecuteMsg::InitiateAgreement {
             initiator_token: initiator_token.clone(),
             counterparty_token: counterparty_token.clone(),
-            counterparty: Addr::unchecked("counterparty2"),
+            counterparty: Some(addr("counterparty2")),
+            expires: None,
+            arbiter: None,
+            arbiter_fee_bps: None,
+            milestones: None,
+            htlc: None,
+            cancel_policy: None,
+            cancel_penalty: None,
+            initiator_assets: None,
+            counterparty_assets: None,
         };
         let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
 
@@ -682,9 +4397,9 @@ mod tests {
             deps.as_ref(),
             mock_env(),
             QueryMsg::GetAgreementsByStatus {
-                status: STATUS_INITIATED.to_string(),
-                page: 0,
-                page_size: 10,
+                status: AgreementStatus::Initiated,
+                start_after: None,
+                limit: None,
             },
         )
         .unwrap();
@@ -696,19 +4411,22 @@ mod tests {
     fn query_total_and_initiated_agreement_count() {
         let mut deps = initialize_contract();
 
-        let initiator_token = TokenInfo {
-            address: Addr::unchecked("tokenA"),
-            amount: 1000u128,
-        };
-        let counterparty_token = TokenInfo {
-            address: Addr::unchecked("tokenB"),
-            amount: 2000u128,
-        };
+        let initiator_token = Asset::native("tokenA", 1000u128);
+        let counterparty_token = Asset::native("tokenB", 2000u128);
 
         let msg = ExecuteMsg::InitiateAgreement {
             initiator_token: initiator_token.clone(),
             counterparty_token: counterparty_token.clone(),
-            counterparty: Addr::unchecked("counterparty"),
+            counterparty: Some(addr("counterparty")),
+            expires: None,
+            arbiter: None,
+            arbiter_fee_bps: None,
+            milestones: None,
+            htlc: None,
+            cancel_policy: None,
+            cancel_penalty: None,
+            initiator_assets: None,
+            counterparty_assets: None,
         };
         let info = message_info(&Addr::unchecked("initiator"), &coins(1000, "tokenA"));
         let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
@@ -716,7 +4434,16 @@ mod tests {
         let msg = ExecuteMsg::InitiateAgreement {
             initiator_token: initiator_token.clone(),
             counterparty_token: counterparty_token.clone(),
-            counterparty: Addr::unchecked("counterparty2"),
+            counterparty: Some(addr("counterparty2")),
+            expires: None,
+            arbiter: None,
+            arbiter_fee_bps: None,
+            milestones: None,
+            htlc: None,
+            cancel_policy: None,
+            cancel_penalty: None,
+            initiator_assets: None,
+            counterparty_assets: None,
         };
         let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
 
@@ -728,19 +4455,22 @@ mod tests {
     fn query_accepted_agreement_count() {
         let mut deps = initialize_contract();
 
-        let initiator_token = TokenInfo {
-            address: Addr::unchecked("tokenA"),
-            amount: 1000u128,
-        };
-        let counterparty_token = TokenInfo {
-            address: Addr::unchecked("tokenB"),
-            amount: 2000u128,
-        };
+        let initiator_token = Asset::native("tokenA", 1000u128);
+        let counterparty_token = Asset::native("tokenB", 2000u128);
 
         let msg = ExecuteMsg::InitiateAgreement {
             initiator_token: initiator_token.clone(),
             counterparty_token: counterparty_token.clone(),
-            counterparty: Addr::unchecked("counterparty"),
+            counterparty: Some(addr("counterparty")),
+            expires: None,
+            arbiter: None,
+            arbiter_fee_bps: None,
+            milestones: None,
+            htlc: None,
+            cancel_policy: None,
+            cancel_penalty: None,
+            initiator_assets: None,
+            counterparty_assets: None,
         };
         let info = message_info(&Addr::unchecked("initiator"), &coins(1000, "tokenA"));
         let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
@@ -748,12 +4478,21 @@ mod tests {
         let msg = ExecuteMsg::InitiateAgreement {
             initiator_token: initiator_token.clone(),
             counterparty_token: counterparty_token.clone(),
-            counterparty: Addr::unchecked("counterparty2"),
+            counterparty: Some(addr("counterparty2")),
+            expires: None,
+            arbiter: None,
+            arbiter_fee_bps: None,
+            milestones: None,
+            htlc: None,
+            cancel_policy: None,
+            cancel_penalty: None,
+            initiator_assets: None,
+            counterparty_assets: None,
         };
         let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
 
         let msg = ExecuteMsg::AcceptAgreement { id: 1 };
-        let info = message_info(&Addr::unchecked("counterparty"), &coins(2000, "tokenB"));
+        let info = message_info(&addr("counterparty"), &coins(2000, "tokenB"));
         let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg);
 
         // Check agreement status counts
@@ -764,19 +4503,22 @@ mod tests {
     fn query_executed_agreement_count() {
         let mut deps = initialize_contract();
 
-        let initiator_token = TokenInfo {
-            address: Addr::unchecked("tokenA"),
-            amount: 1000u128,
-        };
-        let counterparty_token = TokenInfo {
-            address: Addr::unchecked("tokenB"),
-            amount: 2000u128,
-        };
+        let initiator_token = Asset::native("tokenA", 1000u128);
+        let counterparty_token = Asset::native("tokenB", 2000u128);
 
         let msg = ExecuteMsg::InitiateAgreement {
             initiator_token: initiator_token.clone(),
             counterparty_token: counterparty_token.clone(),
-            counterparty: Addr::unchecked("counterparty"),
+            counterparty: Some(addr("counterparty")),
+            expires: None,
+            arbiter: None,
+            arbiter_fee_bps: None,
+            milestones: None,
+            htlc: None,
+            cancel_policy: None,
+            cancel_penalty: None,
+            initiator_assets: None,
+            counterparty_assets: None,
         };
         let info = message_info(&Addr::unchecked("initiator"), &coins(1000, "tokenA"));
         let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
@@ -784,15 +4526,27 @@ mod tests {
         let msg = ExecuteMsg::InitiateAgreement {
             initiator_token: initiator_token.clone(),
             counterparty_token: counterparty_token.clone(),
-            counterparty: Addr::unchecked("counterparty2"),
+            counterparty: Some(addr("counterparty2")),
+            expires: None,
+            arbiter: None,
+            arbiter_fee_bps: None,
+            milestones: None,
+            htlc: None,
+            cancel_policy: None,
+            cancel_penalty: None,
+            initiator_assets: None,
+            counterparty_assets: None,
         };
         let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
 
         let msg = ExecuteMsg::AcceptAgreement { id: 1 };
-        let info = message_info(&Addr::unchecked("counterparty"), &coins(2000, "tokenB"));
+        let info = message_info(&addr("counterparty"), &coins(2000, "tokenB"));
         let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg);
 
-        let execute_msg = ExecuteMsg::ExecuteAgreement { id: 1 };
+        let execute_msg = ExecuteMsg::ExecuteAgreement {
+            id: 1,
+            preimage: None,
+        };
         let execute_info = message_info(&Addr::unchecked("initiator"), &[]);
         let _res = execute(deps.as_mut(), mock_env(), execute_info.clone(), execute_msg);
 
@@ -804,19 +4558,22 @@ mod tests {
     fn query_canceled_agreement_count() {
         let mut deps = initialize_contract();
 
-        let initiator_token = TokenInfo {
-            address: Addr::unchecked("tokenA"),
-            amount: 1000u128,
-        };
-        let counterparty_token = TokenInfo {
-            address: Addr::unchecked("tokenB"),
-            amount: 2000u128,
-        };
+        let initiator_token = Asset::native("tokenA", 1000u128);
+        let counterparty_token = Asset::native("tokenB", 2000u128);
 
         let msg = ExecuteMsg::InitiateAgreement {
             initiator_token: initiator_token.clone(),
             counterparty_token: counterparty_token.clone(),
-            counterparty: Addr::unchecked("counterparty"),
+            counterparty: Some(addr("counterparty")),
+            expires: None,
+            arbiter: None,
+            arbiter_fee_bps: None,
+            milestones: None,
+            htlc: None,
+            cancel_policy: None,
+            cancel_penalty: None,
+            initiator_assets: None,
+            counterparty_assets: None,
         };
         let info = message_info(&Addr::unchecked("initiator"), &coins(1000, "tokenA"));
         let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
@@ -824,12 +4581,21 @@ mod tests {
         let msg = ExecuteMsg::InitiateAgreement {
             initiator_token: initiator_token.clone(),
             counterparty_token: counterparty_token.clone(),
-            counterparty: Addr::unchecked("counterparty2"),
+            counterparty: Some(addr("counterparty2")),
+            expires: None,
+            arbiter: None,
+            arbiter_fee_bps: None,
+            milestones: None,
+            htlc: None,
+            cancel_policy: None,
+            cancel_penalty: None,
+            initiator_assets: None,
+            counterparty_assets: None,
         };
         let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
 
         let msg = ExecuteMsg::AcceptAgreement { id: 1 };
-        let info = message_info(&Addr::unchecked("counterparty"), &coins(2000, "tokenB"));
+        let info = message_info(&addr("counterparty"), &coins(2000, "tokenB"));
         let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg);
 
         let execute_msg = ExecuteMsg::CancelAgreement { id: 1 };
@@ -839,4 +4605,490 @@ mod tests {
         // Check agreement status counts
         check_agreement_counts(&deps, 2, 1, 0, 0, 1);
     }
+
+    // A buyer pays a seller for its goods through a broker, who keeps a commission
+    fn initiate_brokered_agreement(
+        deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>,
+    ) -> (Addr, Addr, Addr) {
+        let buyer = deps.api.addr_make("buyer");
+        let seller = deps.api.addr_make("seller");
+        let broker = deps.api.addr_make("broker");
+        let msg = ExecuteMsg::InitiateMultiPartyAgreement {
+            participants: vec![
+                Participant {
+                    address: buyer.clone(),
+                    deposit: vec![Asset::native("uusdc", 1000u128)],
+                    payout: vec![Asset::native("ugold", 10u128)],
+                },
+                Participant {
+                    address: seller.clone(),
+                    deposit: vec![Asset::native("ugold", 10u128)],
+                    payout: vec![Asset::native("uusdc", 950u128)],
+                },
+                Participant {
+                    address: broker.clone(),
+                    deposit: vec![],
+                    payout: vec![Asset::native("uusdc", 50u128)],
+                },
+            ],
+        };
+        let info = message_info(&broker, &[]);
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        (buyer, seller, broker)
+    }
+
+    fn query_multi_party_agreement(
+        deps: &OwnedDeps<MockStorage, MockApi, MockQuerier>,
+        id: u64,
+    ) -> MultiPartyAgreementResponse {
+        let msg = QueryMsg::GetMultiPartyAgreement { id };
+        from_json(query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn execute_multi_party_agreement() {
+        let mut deps = initialize_contract();
+        let (buyer, seller, broker) = initiate_brokered_agreement(&mut deps);
+        let value = query_multi_party_agreement(&deps, 1);
+        assert_eq!(value.agreement.status, AgreementStatus::Initiated);
+        assert_eq!(value.pending, vec![buyer.clone(), seller.clone()]);
+
+        // The agreement cannot execute before every participant has deposited
+        let execute_msg = ExecuteMsg::ExecuteMultiPartyAgreement { id: 1 };
+        let info = message_info(&broker, &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, execute_msg.clone());
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::InvalidAgreementStatus { .. }
+        ));
+
+        // The buyer deposits in two parts but never more than it owes
+        let deposit_msg = ExecuteMsg::DepositMultiParty { id: 1 };
+        let info = message_info(&buyer, &coins(600, "uusdc"));
+        execute(deps.as_mut(), mock_env(), info, deposit_msg.clone()).unwrap();
+        let info = message_info(&buyer, &coins(500, "uusdc"));
+        let res = execute(deps.as_mut(), mock_env(), info, deposit_msg.clone());
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::UnexpectedFunds { .. }
+        ));
+        let info = message_info(&buyer, &coins(400, "uusdc"));
+        execute(deps.as_mut(), mock_env(), info, deposit_msg.clone()).unwrap();
+        let value = query_multi_party_agreement(&deps, 1);
+        assert_eq!(value.pending, vec![seller.clone()]);
+
+        // The last deposit makes the agreement executable
+        let info = message_info(&seller, &coins(10, "ugold"));
+        let res = execute(deps.as_mut(), mock_env(), info, deposit_msg).unwrap();
+        assert!(res
+            .attributes
+            .contains(&Attribute::new("status", "accepted")));
+
+        let info = message_info(&broker, &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, execute_msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(BankMsg::Send {
+                    to_address: buyer.to_string(),
+                    amount: coins(10, "ugold"),
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: seller.to_string(),
+                    amount: coins(950, "uusdc"),
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: broker.to_string(),
+                    amount: coins(50, "uusdc"),
+                }),
+            ]
+        );
+        let value = query_multi_party_agreement(&deps, 1);
+        assert_eq!(value.agreement.status, AgreementStatus::Executed);
+        assert!(value.deposits.is_empty());
+
+        // Multi-party agreements do not affect the counts of two-party agreements
+        check_agreement_counts(&deps, 0, 0, 0, 0, 0);
+    }
+
+    #[test]
+    fn multi_party_agreements_are_fee_exempt() {
+        let mut deps = initialize_contract();
+        set_protocol_fees(&mut deps);
+        let (buyer, seller, broker) = initiate_brokered_agreement(&mut deps);
+
+        let deposit_msg = ExecuteMsg::DepositMultiParty { id: 1 };
+        let info = message_info(&buyer, &coins(1000, "uusdc"));
+        execute(deps.as_mut(), mock_env(), info, deposit_msg.clone()).unwrap();
+        let info = message_info(&seller, &coins(10, "ugold"));
+        execute(deps.as_mut(), mock_env(), info, deposit_msg).unwrap();
+
+        // Every participant receives its full payout and no fees accrue
+        let execute_msg = ExecuteMsg::ExecuteMultiPartyAgreement { id: 1 };
+        let info = message_info(&broker, &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, execute_msg).unwrap();
+        assert_eq!(res.messages.len(), 3);
+        assert_eq!(
+            res.messages[1],
+            SubMsg::new(BankMsg::Send {
+                to_address: seller.to_string(),
+                amount: coins(950, "uusdc"),
+            })
+        );
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetAccruedFees {
+                denom: "uusdc".to_string(),
+            },
+        )
+        .unwrap();
+        let value: AccruedFeesResponse = from_json(&res).unwrap();
+        assert_eq!(value.amount, Uint128::zero());
+    }
+
+    #[test]
+    fn cancel_multi_party_agreement_refunds_actual_deposits() {
+        let mut deps = initialize_contract();
+        let (buyer, seller, _) = initiate_brokered_agreement(&mut deps);
+
+        let deposit_msg = ExecuteMsg::DepositMultiParty { id: 1 };
+        let info = message_info(&buyer, &coins(600, "uusdc"));
+        execute(deps.as_mut(), mock_env(), info, deposit_msg.clone()).unwrap();
+
+        // Only what the buyer has deposited so far is refunded
+        let cancel_msg = ExecuteMsg::CancelMultiPartyAgreement { id: 1 };
+        let info = message_info(&seller, &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, cancel_msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: buyer.to_string(),
+                amount: coins(600, "uusdc"),
+            })]
+        );
+        let value = query_multi_party_agreement(&deps, 1);
+        assert_eq!(value.agreement.status, AgreementStatus::Canceled);
+
+        // No further deposits are taken once canceled
+        let info = message_info(&seller, &coins(10, "ugold"));
+        let res = execute(deps.as_mut(), mock_env(), info, deposit_msg);
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::InvalidAgreementStatus { .. }
+        ));
+    }
+
+    #[test]
+    fn invalid_multi_party_agreements() {
+        let mut deps = initialize_contract();
+        let buyer = deps.api.addr_make("buyer");
+        let seller = deps.api.addr_make("seller");
+        let participant = |address: &Addr, deposit: u128, payout: u128| Participant {
+            address: address.clone(),
+            deposit: vec![Asset::native("uusdc", deposit)],
+            payout: vec![Asset::native("uusdc", payout)],
+        };
+
+        // A single participant, a duplicate participant, and payouts exceeding the deposits
+        for participants in [
+            vec![participant(&buyer, 100, 100)],
+            vec![participant(&buyer, 100, 50), participant(&buyer, 10, 60)],
+            vec![participant(&buyer, 100, 50), participant(&seller, 10, 70)],
+        ] {
+            let msg = ExecuteMsg::InitiateMultiPartyAgreement { participants };
+            let info = message_info(&buyer, &[]);
+            let res = execute(deps.as_mut(), mock_env(), info, msg);
+            assert!(matches!(
+                res.err().unwrap(),
+                ContractError::InvalidParticipants { .. }
+            ));
+        }
+
+        // Deposits are made by participants only, and only in the tokens they owe
+        initiate_brokered_agreement(&mut deps);
+        let deposit_msg = ExecuteMsg::DepositMultiParty { id: 1 };
+        let info = message_info(&Addr::unchecked("outsider"), &coins(10, "ugold"));
+        let res = execute(deps.as_mut(), mock_env(), info, deposit_msg.clone());
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::Unauthorized { .. }
+        ));
+        let info = message_info(&seller, &coins(10, "uusdc"));
+        let res = execute(deps.as_mut(), mock_env(), info, deposit_msg);
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::UnexpectedFunds { .. }
+        ));
+    }
+
+    // Marks the stored state as written by the version storing statuses as strings
+    fn set_legacy_version(deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>) {
+        let stored = cw2::get_contract_version(deps.as_ref().storage).unwrap();
+        cw2::set_contract_version(deps.as_mut().storage, stored.contract, "0.0.1").unwrap();
+    }
+
+    #[test]
+    fn migrate_string_statuses() {
+        let mut deps = initialize_contract();
+        set_legacy_version(&mut deps);
+
+        // State as written by the first version, storing statuses and tokens as strings
+        for (id, status) in [(1u64, "initiated"), (2, "accepted"), (3, "canceled")] {
+            let legacy = format!(
+                r#"{{"id":{id},"initiator":"initiator","initiator_token":{{"address":"tokenA","amount":1000}},"counterparty":"counterparty","counterparty_token":{{"address":"tokenB","amount":2000}},"status":"{status}"}}"#
+            );
+            deps.storage.set(&AGREEMENTS.key(id), legacy.as_bytes());
+        }
+        EXPIRED_AGREEMENT_COUNT.remove(deps.as_mut().storage);
+
+        let res = migrate(deps.as_mut(), mock_env(), MigrateMsg::default()).unwrap();
+        assert_eq!(
+            res.attributes.last().unwrap(),
+            Attribute {
+                key: "migrated_agreements".to_string(),
+                value: "3".to_string()
+            }
+        );
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetAgreement { id: 2 }).unwrap();
+        let value: AgreementResponse = from_json(&res).unwrap();
+        assert_eq!(value.agreement.status, AgreementStatus::Accepted);
+        assert_eq!(
+            value.agreement.initiator_token,
+            Asset::native("tokenA", 1000u128)
+        );
+        assert_eq!(
+            value.agreement.counterparty,
+            Some(Addr::unchecked("counterparty"))
+        );
+        assert_eq!(
+            value.agreement.counterparty_token,
+            Asset::native("tokenB", 2000u128)
+        );
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetAgreementsByStatus {
+                status: AgreementStatus::Canceled,
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap();
+        let value: AgreementsResponse = from_json(&res).unwrap();
+        assert_eq!(value.agreements.len(), 1);
+        assert_eq!(value.agreements[0].id, 3);
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetExpiredAgreementCount {},
+        )
+        .unwrap();
+        let value: ExpiredAgreementCountResponse = from_json(&res).unwrap();
+        assert_eq!(value.expired_agreement_count, 0);
+    }
+
+    #[test]
+    fn migrate_backfills_legacy_deposits() {
+        let mut deps = initialize_contract();
+        set_legacy_version(&mut deps);
+
+        // Funded agreements of the first version, which kept no deposit ledger
+        for (id, status) in [(1u64, "initiated"), (2, "accepted")] {
+            let legacy = format!(
+                r#"{{"id":{id},"initiator":"initiator","initiator_token":{{"address":"tokenA","amount":1000}},"counterparty":"counterparty","counterparty_token":{{"address":"tokenB","amount":2000}},"status":"{status}"}}"#
+            );
+            deps.storage.set(&AGREEMENTS.key(id), legacy.as_bytes());
+        }
+        TOTAL_AGREEMENT_COUNT
+            .save(deps.as_mut().storage, &2)
+            .unwrap();
+        INITIATED_AGREEMENT_COUNT
+            .save(deps.as_mut().storage, &1)
+            .unwrap();
+        ACCEPTED_AGREEMENT_COUNT
+            .save(deps.as_mut().storage, &1)
+            .unwrap();
+        migrate(deps.as_mut(), mock_env(), MigrateMsg::default()).unwrap();
+
+        // The accepted agreement executes with both escrowed tokens
+        let execute_msg = ExecuteMsg::ExecuteAgreement {
+            id: 2,
+            preimage: None,
+        };
+        let info = message_info(&Addr::unchecked("initiator"), &[]);
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), execute_msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(BankMsg::Send {
+                    to_address: "initiator".to_string(),
+                    amount: coins(2000, "tokenB"),
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: Addr::unchecked("counterparty").to_string(),
+                    amount: coins(1000, "tokenA"),
+                }),
+            ]
+        );
+
+        // Canceling the initiated agreement refunds the initiator token
+        let cancel_msg = ExecuteMsg::CancelAgreement { id: 1 };
+        let res = execute(deps.as_mut(), mock_env(), info, cancel_msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "initiator".to_string(),
+                amount: coins(1000, "tokenA"),
+            })]
+        );
+        check_agreement_counts(&deps, 2, 0, 0, 1, 1);
+    }
+
+    #[test]
+    fn migrate_creates_missing_ownership_and_config() {
+        let mut deps = initialize_contract();
+
+        // State as written by the version without an owner and config
+        OWNERSHIP.remove(deps.as_mut().storage);
+        CONFIG.remove(deps.as_mut().storage);
+
+        let res = migrate(deps.as_mut(), mock_env(), MigrateMsg::default());
+        assert!(matches!(
+            res.unwrap_err(),
+            ContractError::InvalidMigration { .. }
+        ));
+
+        let owner = deps.api.addr_make("owner");
+        let msg = MigrateMsg {
+            owner: Some(owner.clone()),
+        };
+        migrate(deps.as_mut(), mock_env(), msg).unwrap();
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetOwnership {}).unwrap();
+        let value: OwnershipResponse = from_json(&res).unwrap();
+        assert_eq!(value.owner, Some(owner.clone()));
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetConfig {}).unwrap();
+        let value: ConfigResponse = from_json(&res).unwrap();
+        assert_eq!(
+            value.config,
+            Config {
+                fee_recipient: owner,
+                initiator_fee_bps: 0,
+                counterparty_fee_bps: 0,
+                guardian: None,
+            }
+        );
+    }
+
+    #[test]
+    fn migrate_takes_owner_from_legacy_config() {
+        let mut deps = initialize_contract();
+        let owner = deps.api.addr_make("owner");
+        let treasury = deps.api.addr_make("treasury");
+
+        // State as written by the version keeping the owner in the config
+        OWNERSHIP.remove(deps.as_mut().storage);
+        let legacy = format!(
+            r#"{{"owner":"{}","fee_recipient":"{}","initiator_fee_bps":100,"counterparty_fee_bps":25}}"#,
+            owner, treasury
+        );
+        deps.storage.set(CONFIG.as_slice(), legacy.as_bytes());
+
+        // No owner is needed in the message, the config already names one
+        migrate(deps.as_mut(), mock_env(), MigrateMsg::default()).unwrap();
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetOwnership {}).unwrap();
+        let value: OwnershipResponse = from_json(&res).unwrap();
+        assert_eq!(value.owner, Some(owner));
+
+        // The fees are kept and the owner is dropped from the config
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetConfig {}).unwrap();
+        let value: ConfigResponse = from_json(&res).unwrap();
+        assert_eq!(
+            value.config,
+            Config {
+                fee_recipient: treasury,
+                initiator_fee_bps: 100,
+                counterparty_fee_bps: 25,
+                guardian: None,
+            }
+        );
+        let stored = deps.storage.get(CONFIG.as_slice()).unwrap();
+        assert!(!String::from_utf8(stored).unwrap().contains("owner"));
+    }
+
+    #[test]
+    fn migrate_preserves_current_agreements() {
+        let mut deps = initialize_contract();
+        let env = mock_env();
+
+        // Agreements written by the current version with fields the legacy format lacks
+        initiate_htlc_agreement(&mut deps, &env, b"secret").unwrap();
+        let basket = vec![Coin::new(100u128, "uatom"), Coin::new(50u128, "uosmo")];
+        initiate(
+            &mut deps,
+            InitiateAgreement {
+                initiator_token: Asset::native("uatom", 100u128),
+                counterparty_token: Asset::native("uusdc", 300u128),
+                initiator_assets: Some(vec![Asset::native("uosmo", 50u128)]),
+                ..Default::default()
+            },
+            &basket,
+        )
+        .unwrap();
+        initiate_penalized_agreement(&mut deps, CancelPenalty::Bps { bps: 1000 }).unwrap();
+        let agreements = |deps: &OwnedDeps<MockStorage, MockApi, MockQuerier>| {
+            AGREEMENTS
+                .range(deps.as_ref().storage, None, None, Order::Ascending)
+                .collect::<StdResult<Vec<_>>>()
+                .unwrap()
+        };
+        let before = agreements(&deps);
+
+        let res = migrate(deps.as_mut(), mock_env(), MigrateMsg::default()).unwrap();
+        assert!(res
+            .attributes
+            .contains(&Attribute::new("migrated_agreements", "3")));
+        assert_eq!(agreements(&deps), before);
+
+        // The hashlock still guards execution
+        let accept_msg = ExecuteMsg::AcceptAgreement { id: 1 };
+        let info = message_info(&addr("counterparty"), &coins(2000, "tokenB"));
+        execute(deps.as_mut(), env.clone(), info, accept_msg).unwrap();
+        let execute_msg = ExecuteMsg::ExecuteAgreement {
+            id: 1,
+            preimage: None,
+        };
+        let info = message_info(&addr("counterparty"), &[]);
+        let res = execute(deps.as_mut(), env, info, execute_msg);
+        assert!(matches!(
+            res.err().unwrap(),
+            ContractError::InvalidPreimage { id: 1 }
+        ));
+    }
+
+    #[test]
+    fn migrate_unknown_status() {
+        let mut deps = initialize_contract();
+        set_legacy_version(&mut deps);
+
+        let legacy = r#"{"id":1,"initiator":"initiator","initiator_token":{"address":"tokenA","amount":1000},"counterparty":"counterparty","counterparty_token":{"address":"tokenB","amount":2000},"status":"pending"}"#;
+        deps.storage.set(&AGREEMENTS.key(1), legacy.as_bytes());
+
+        let res = migrate(deps.as_mut(), mock_env(), MigrateMsg::default());
+
+        match res.err().unwrap() {
+            ContractError::UnknownAgreementStatus { status } => assert_eq!(status, "pending"),
+            _ => panic!("Unexpected error"),
+        }
+    }
+
+    #[test]
+    fn query_agreements_by_unknown_status() {
+        let msg = r#"{"get_agreements_by_status":{"status":"initated","limit":10}}"#;
+
+        assert!(from_json::<QueryMsg>(msg).is_err());
+    }
 }
@@ -1,6 +1,18 @@
-use crate::msg::TokenInfo;
+use crate::asset::{assets_to_string, merge_assets, Asset};
+use crate::contract::BPS_DENOMINATOR;
+use crate::msg::SignedOffer;
+use crate::state::{
+    AgreementStatus, AssetListMode, CancelPenalty, Htlc, Participant, PauseFlags, ASSET_LIST_MODE,
+    ASSET_RULES, CONFIG, DEPOSITS, OWNERSHIP, PAUSED, REVOKED_OFFER_ID, USED_OFFERS,
+};
 use crate::ContractError;
-use cosmwasm_std::{Addr, Coin, DepsMut, Env, Uint128};
+use cosmwasm_std::{
+    to_json_vec, Addr, Api, Binary, BlockInfo, CanonicalAddr, Coin, Env, HexBinary, StdResult,
+    Storage, Uint128,
+};
+use cw_utils::Expiration;
+use ripemd::Ripemd160;
+use sha2::{Digest, Sha256};
 
 // Asserts that the sender matches the expected counterparty address.
 pub fn assert_sender_matches_counterparty(
@@ -60,16 +72,63 @@ pub fn assert_sender_authorized(
     Ok(())
 }
 
+// Asserts that the sender is the owner of the contract, guarding every admin operation.
+pub fn assert_owner(storage: &dyn Storage, sender: &Addr) -> Result<(), ContractError> {
+    // Load the owner, a renounced contract has none
+    let owner = OWNERSHIP
+        .load(storage)?
+        .owner
+        .ok_or(ContractError::NoOwner {})?;
+
+    // Return an Unauthorized error if the sender is not the owner
+    assert_sender_authorized(sender, &[&owner])
+}
+
+// Asserts that the sender is the owner or the guardian of the contract.
+pub fn assert_owner_or_guardian(storage: &dyn Storage, sender: &Addr) -> Result<(), ContractError> {
+    // Load the owner and the guardian, either of which may be unset
+    let owner = OWNERSHIP.load(storage)?.owner;
+    let guardian = CONFIG.load(storage)?.guardian;
+
+    // Return an Unauthorized error if the sender is neither
+    assert_sender_authorized(
+        sender,
+        &owner.iter().chain(guardian.iter()).collect::<Vec<_>>(),
+    )
+}
+
+// Asserts that an operation is not paused, `is_paused` picks its flag.
+pub fn assert_not_paused(
+    storage: &dyn Storage,
+    operation: &str,
+    is_paused: impl Fn(&PauseFlags) -> bool,
+) -> Result<(), ContractError> {
+    // Contracts that were never paused have no flags stored
+    let paused = PAUSED.may_load(storage)?.unwrap_or_default();
+    if is_paused(&paused) {
+        return Err(ContractError::Paused {
+            operation: operation.to_string(),
+        });
+    }
+
+    // Return Ok(()) if the operation is not paused
+    Ok(())
+}
+
 // Asserts that the agreement status matches one of the allowed statuses.
 pub fn assert_agreement_has_status(
-    agreement_status: &str,
-    allowed_statuses: &[&str],
+    agreement_status: &AgreementStatus,
+    allowed_statuses: &[AgreementStatus],
 ) -> Result<(), ContractError> {
     // Check if the agreement status is in the list of allowed statuses
-    if !allowed_statuses.contains(&agreement_status) {
+    if !allowed_statuses.contains(agreement_status) {
         // If not, return an InvalidAgreementStatus error indicating expected and found statuses
         return Err(ContractError::InvalidAgreementStatus {
-            expected: allowed_statuses.join(", "),
+            expected: allowed_statuses
+                .iter()
+                .map(|status| status.as_str())
+                .collect::<Vec<_>>()
+                .join(", "),
             found: agreement_status.to_string(),
         });
     }
@@ -78,69 +137,670 @@ pub fn assert_agreement_has_status(
     Ok(())
 }
 
-// Asserts that the funds match the expected token amount.
-pub fn assert_funds_match_token_amount(
-    funds: &Vec<Coin>,
-    token: &TokenInfo,
+// Asserts that an optional expiration has not already passed at the current block.
+pub fn assert_expiration_in_future(
+    expires: &Option<Expiration>,
+    block: &BlockInfo,
 ) -> Result<(), ContractError> {
-    // Convert token amount from u128 to Uint128 for consistency
-    let token_amount = Uint128::from(token.amount);
+    // Check if the expiration is set and already reached
+    if let Some(expires) = expires.filter(|expires| expires.is_expired(block)) {
+        // If so, return an InvalidExpiration error
+        return Err(ContractError::InvalidExpiration {
+            expires: expires.to_string(),
+        });
+    }
+
+    // Return Ok(()) if the agreement can still be accepted
+    Ok(())
+}
+
+// Asserts that the agreement with an optional expiration has not expired at the current block.
+pub fn assert_agreement_not_expired(
+    id: u64,
+    expires: &Option<Expiration>,
+    block: &BlockInfo,
+) -> Result<(), ContractError> {
+    // Check if the expiration is set and already reached
+    if expires.is_some_and(|expires| expires.is_expired(block)) {
+        // If so, return an AgreementExpired error
+        return Err(ContractError::AgreementExpired { id });
+    }
+
+    // Return Ok(()) if the agreement has not expired
+    Ok(())
+}
+
+// Asserts that an optional arbiter is not one of the parties and its fee is at most 100%.
+pub fn assert_arbiter_valid(
+    arbiter: &Option<Addr>,
+    arbiter_fee_bps: u16,
+    parties: &[&Addr],
+) -> Result<(), ContractError> {
+    // Check the arbiter is independent of the parties and the fee needs an arbiter to be paid to
+    let reason = match arbiter {
+        Some(arbiter) if parties.contains(&arbiter) => "arbiter cannot be a party",
+        None if arbiter_fee_bps > 0 => "fee set without an arbiter",
+        _ if u128::from(arbiter_fee_bps) > BPS_DENOMINATOR => "fee cannot exceed 10000 bps",
+        _ => return Ok(()),
+    };
+
+    // Return an InvalidArbiter error for any violation
+    Err(ContractError::InvalidArbiter {
+        reason: reason.to_string(),
+    })
+}
+
+// Asserts that the fees of both sides are at most 100%.
+pub fn assert_fees_valid(
+    initiator_fee_bps: u16,
+    counterparty_fee_bps: u16,
+) -> Result<(), ContractError> {
+    // Check each side's fee is a valid share of the tokens it is taken from
+    if u128::from(initiator_fee_bps.max(counterparty_fee_bps)) > BPS_DENOMINATOR {
+        return Err(ContractError::InvalidConfig {
+            reason: "fee cannot exceed 10000 bps".to_string(),
+        });
+    }
+
+    // Return Ok(()) if both fees are valid
+    Ok(())
+}
+
+// Asserts that milestones, if any, split a fungible counterparty token into non-zero parts.
+pub fn assert_milestones_valid(
+    milestones: &[Uint128],
+    counterparty_token: &Asset,
+) -> Result<(), ContractError> {
+    // Agreements without milestones are executed in a single step
+    if milestones.is_empty() {
+        return Ok(());
+    }
+
+    // Check the milestones can be released in parts of the counterparty token
+    let reason = if counterparty_token.is_nft() {
+        "counterparty token must be fungible"
+    } else if milestones.iter().any(|amount| amount.is_zero()) {
+        "milestone amount must be greater than zero"
+    } else if milestones.iter().sum::<Uint128>() != counterparty_token.amount() {
+        "milestone amounts must add up to the counterparty token amount"
+    } else {
+        return Ok(());
+    };
+
+    // Return an InvalidMilestones error for any violation
+    Err(ContractError::InvalidMilestones {
+        reason: reason.to_string(),
+    })
+}
+
+// Asserts that the further assets of a basket are bank assets, like its token, which can be
+// deposited together, and that no denom appears twice.
+pub fn assert_basket_valid(token: &Asset, assets: &[Asset]) -> Result<(), ContractError> {
+    // A token without further assets is not a basket
+    if assets.is_empty() {
+        return Ok(());
+    }
+
+    // Check every asset of the basket is a bank asset of a distinct denom
+    let basket: Vec<&Asset> = std::iter::once(token).chain(assets).collect();
+    let reason = if basket
+        .iter()
+        .any(|asset| !matches!(asset, Asset::Native { .. }))
+    {
+        "basket assets must be native tokens".to_string()
+    } else if let Some(duplicate) =
+        (1..basket.len()).find(|&i| basket[..i].iter().any(|a| a.is_same_token(basket[i])))
+    {
+        format!(
+            "denom `{}` appears more than once",
+            basket[duplicate].token()
+        )
+    } else {
+        return Ok(());
+    };
+
+    // Return an InvalidBasket error for any violation
+    Err(ContractError::InvalidBasket { reason })
+}
+
+// Asserts that a multi-party agreement has at least two distinct participants, something to
+// deposit, no token listed twice in a deposit, and payouts adding up to exactly the deposits.
+pub fn assert_participants_valid(participants: &[Participant]) -> Result<(), ContractError> {
+    let deposits = merge_assets(participants.iter().flat_map(|p| p.deposit.clone()));
+    let payouts = merge_assets(participants.iter().flat_map(|p| p.payout.clone()));
+    let reason = if participants.len() < 2 {
+        "at least two participants are required".to_string()
+    } else if let Some(duplicate) = (1..participants.len()).find(|&i| {
+        participants[..i]
+            .iter()
+            .any(|p| p.address == participants[i].address)
+    }) {
+        format!(
+            "participant `{}` appears more than once",
+            participants[duplicate].address
+        )
+    } else if deposits.is_empty() {
+        "at least one participant must deposit".to_string()
+    } else if let Some(participant) = participants.iter().find(|p| {
+        (1..p.deposit.len()).any(|i| {
+            p.deposit[..i]
+                .iter()
+                .any(|a| a.is_same_token(&p.deposit[i]))
+        })
+    }) {
+        format!(
+            "deposit of `{}` lists a token more than once",
+            participant.address
+        )
+    } else if deposits != payouts {
+        format!(
+            "payouts `{}` must add up to the deposits `{}`",
+            assets_to_string(&payouts),
+            assets_to_string(&deposits)
+        )
+    } else {
+        return Ok(());
+    };
+
+    // Return an InvalidParticipants error for any violation
+    Err(ContractError::InvalidParticipants { reason })
+}
+
+// Returns what is still missing of the required assets given the escrowed ones, fungible assets
+// reduced to the missing amount and NFTs to the missing token ids.
+pub fn outstanding_assets(escrowed: &[Asset], required: &[Asset]) -> Vec<Asset> {
+    required
+        .iter()
+        .filter_map(|asset| match asset {
+            Asset::Cw721 { address, token_ids } => {
+                let missing: Vec<String> = token_ids
+                    .iter()
+                    .filter(|token_id| {
+                        !escrowed.iter().any(|held| {
+                            matches!(held, Asset::Cw721 { address: a, token_ids: ids }
+                                if a == address && ids.contains(token_id))
+                        })
+                    })
+                    .cloned()
+                    .collect();
+                (!missing.is_empty()).then(|| Asset::Cw721 {
+                    address: address.clone(),
+                    token_ids: missing,
+                })
+            }
+            _ => {
+                let covered: Uint128 = escrowed.iter().map(|held| held.covered_amount(asset)).sum();
+                let missing = asset.amount().saturating_sub(covered);
+                (!missing.is_zero()).then(|| asset.with_amount(missing))
+            }
+        })
+        .collect()
+}
+
+// Asserts that a deposit is not empty and covers no more than the outstanding assets.
+pub fn assert_deposit_outstanding(
+    deposit: &Deposit,
+    outstanding: &[Asset],
+) -> Result<(), ContractError> {
+    // Check that something was deposited
+    let assets = deposit.to_assets();
+    if assets.is_empty() {
+        return Err(ContractError::InsufficientFunds);
+    }
+
+    // Check that every deposited asset is still owed in at least the deposited amount
+    for asset in &assets {
+        if !outstanding
+            .iter()
+            .any(|owed| owed.covered_amount(asset) >= asset.amount())
+        {
+            return Err(ContractError::UnexpectedFunds {
+                expected: assets_to_string(outstanding),
+                found: asset.to_string(),
+            });
+        }
+    }
+
+    // Return Ok(()) if the deposit is owed
+    Ok(())
+}
+
+// Asserts that an optional cancel penalty is a valid share or does not exceed the deposits,
+// and that NFT deposits are not penalized.
+pub fn assert_cancel_penalty_valid(
+    penalty: &Option<CancelPenalty>,
+    initiator_token: &Asset,
+    counterparty_token: &Asset,
+) -> Result<(), ContractError> {
+    // Determine the penalty each party would pay
+    let (initiator, counterparty) = match penalty {
+        None => return Ok(()),
+        Some(CancelPenalty::Bps { bps }) if u128::from(*bps) > BPS_DENOMINATOR => {
+            return Err(ContractError::InvalidCancelPenalty {
+                reason: "penalty cannot exceed 10000 bps".to_string(),
+            })
+        }
+        Some(CancelPenalty::Bps { bps }) => (Uint128::from(*bps), Uint128::from(*bps)),
+        Some(CancelPenalty::Fixed {
+            initiator,
+            counterparty,
+        }) => (*initiator, *counterparty),
+    };
+
+    // Check each party's penalty against its deposit
+    for (amount, deposit) in [
+        (initiator, initiator_token),
+        (counterparty, counterparty_token),
+    ] {
+        if amount.is_zero() {
+            continue;
+        }
+        if deposit.is_nft() {
+            return Err(ContractError::InvalidCancelPenalty {
+                reason: "NFT deposits cannot be penalized".to_string(),
+            });
+        }
+        if matches!(penalty, Some(CancelPenalty::Fixed { .. })) && amount > deposit.amount() {
+            return Err(ContractError::InvalidCancelPenalty {
+                reason: "penalty cannot exceed the deposit".to_string(),
+            });
+        }
+    }
+    Ok(())
+}
+
+// Asserts that an optional hash time lock has a sha256 hashlock and a timelock in the future,
+// and is not combined with milestones.
+pub fn assert_htlc_valid(
+    htlc: &Option<Htlc>,
+    milestones: &[Uint128],
+    block: &BlockInfo,
+) -> Result<(), ContractError> {
+    // Agreements without a hash time lock need no checks
+    let Some(htlc) = htlc else {
+        return Ok(());
+    };
+
+    // Check the hashlock is a sha256 digest and the deposits can be refunded at some point
+    let reason = if htlc.hashlock.len() != 32 {
+        "hashlock must be a 32 byte sha256 digest"
+    } else if matches!(htlc.timelock, Expiration::Never {}) || htlc.timelock.is_expired(block) {
+        "timelock must be in the future"
+    } else if !milestones.is_empty() {
+        "milestone agreements cannot be hash time locked"
+    } else {
+        return Ok(());
+    };
+
+    // Return an InvalidHtlc error for any violation
+    Err(ContractError::InvalidHtlc {
+        reason: reason.to_string(),
+    })
+}
+
+// Asserts that the preimage hashes to the sha256 hashlock of an agreement.
+pub fn assert_preimage_matches(
+    id: u64,
+    htlc: &Htlc,
+    preimage: &Option<HexBinary>,
+) -> Result<(), ContractError> {
+    // Hash the preimage and compare it with the hashlock
+    match preimage {
+        Some(preimage)
+            if Sha256::digest(preimage.as_slice()).as_slice() == htlc.hashlock.as_slice() =>
+        {
+            Ok(())
+        }
+        _ => Err(ContractError::InvalidPreimage { id }),
+    }
+}
 
+// Returns the sha256 digest an initiator signs for an offer, bound to this chain and contract.
+pub fn signed_offer_digest(env: &Env, offer: &SignedOffer) -> StdResult<[u8; 32]> {
+    let payload = to_json_vec(&(&env.block.chain_id, &env.contract.address, offer))?;
+    Ok(Sha256::digest(payload).into())
+}
+
+// Asserts that the signed offer with the digest has been neither accepted nor revoked.
+pub fn assert_offer_unused(storage: &dyn Storage, digest: &[u8]) -> Result<(), ContractError> {
+    match USED_OFFERS.may_load(storage, digest)? {
+        Some(REVOKED_OFFER_ID) => Err(ContractError::SignedOfferRevoked {}),
+        Some(_) => Err(ContractError::SignedOfferUsed {}),
+        None => Ok(()),
+    }
+}
+
+// Asserts that the signature over the digest was made by the key of the signer's account.
+pub fn assert_signed_by(
+    api: &dyn Api,
+    digest: &[u8],
+    signature: &Binary,
+    pubkey: &Binary,
+    signer: &Addr,
+) -> Result<(), ContractError> {
+    // Derive the account of the compressed public key as Cosmos SDK chains do
+    if pubkey.len() != 33 {
+        return Err(ContractError::InvalidSignature {});
+    }
+    let account = CanonicalAddr::from(Ripemd160::digest(Sha256::digest(pubkey)).as_slice());
+    if api.addr_canonicalize(signer.as_str())? != account {
+        return Err(ContractError::Unauthorized {
+            expected: signer.to_string(),
+            found: api.addr_humanize(&account)?.to_string(),
+        });
+    }
+
+    // Verify the signature, malformed signatures are rejected like invalid ones
+    match api.secp256k1_verify(digest, signature, pubkey) {
+        Ok(true) => Ok(()),
+        _ => Err(ContractError::InvalidSignature {}),
+    }
+}
+
+// Funds accompanying a call: native coins, tokens received through a CW20 `Send`
+// or a single NFT received through a CW721 `SendNft`.
+pub enum Deposit {
+    Native(Vec<Coin>),
+    Cw20 { contract: Addr, amount: Uint128 },
+    Cw721 { contract: Addr, token_id: String },
+}
+
+impl Deposit {
+    // Converts the deposit into the assets recorded in the escrow ledger.
+    pub fn to_assets(&self) -> Vec<Asset> {
+        match self {
+            Deposit::Native(funds) => funds
+                .iter()
+                .map(|coin| Asset::native(coin.denom.clone(), coin.amount))
+                .collect(),
+            Deposit::Cw20 { contract, amount } => vec![Asset::cw20(contract.clone(), *amount)],
+            Deposit::Cw721 { contract, token_id } => {
+                vec![Asset::cw721(contract.clone(), &[token_id])]
+            }
+        }
+    }
+}
+
+// Asserts that the asset is of a token standard that can be escrowed.
+pub fn assert_asset_supported(asset: &Asset) -> Result<(), ContractError> {
+    // Only native, CW20 and CW721 tokens can currently be deposited
+    match asset {
+        Asset::Native { .. } | Asset::Cw20 { .. } | Asset::Cw721 { .. } => Ok(()),
+        Asset::Cw1155 { .. } => Err(ContractError::UnsupportedAsset {
+            asset: asset.to_string(),
+        }),
+    }
+}
+
+// Asserts that the asset may be escrowed and its amount is within the limits of its rule.
+pub fn assert_asset_allowed(storage: &dyn Storage, asset: &Asset) -> Result<(), ContractError> {
+    let not_allowed = |reason: String| ContractError::AssetNotAllowed {
+        asset: asset.to_string(),
+        reason,
+    };
+
+    // Look up the rule of the asset's token, unlisted tokens depend on the list mode
+    let rule = match ASSET_RULES.may_load(storage, &asset.token())? {
+        Some(rule) => rule,
+        None => {
+            return match ASSET_LIST_MODE.may_load(storage)?.unwrap_or_default() {
+                AssetListMode::Denylist => Ok(()),
+                AssetListMode::Allowlist => {
+                    Err(not_allowed("token is not allowlisted".to_string()))
+                }
+            }
+        }
+    };
+
+    // Check the token is not denied and the amount is within its limits
+    if !rule.allowed {
+        return Err(not_allowed("token is denylisted".to_string()));
+    }
+    if let Some(min_amount) = rule.min_amount.filter(|min| asset.amount() < *min) {
+        return Err(not_allowed(format!(
+            "amount is below the minimum of {}",
+            min_amount
+        )));
+    }
+    if let Some(max_amount) = rule.max_amount.filter(|max| asset.amount() > *max) {
+        return Err(not_allowed(format!(
+            "amount is above the maximum of {}",
+            max_amount
+        )));
+    }
+
+    // Return Ok(()) if the asset is allowed
+    Ok(())
+}
+
+// Asserts that a deposit matches the expected asset.
+pub fn assert_deposit_matches_token_amount(
+    deposit: &Deposit,
+    asset: &Asset,
+) -> Result<(), ContractError> {
+    // Dispatch to the check for the kind of deposit that was made
+    match deposit {
+        Deposit::Native(funds) => assert_funds_match_token_amount(funds, asset),
+        Deposit::Cw20 { contract, amount } => {
+            assert_cw20_matches_token_amount(contract, *amount, asset)
+        }
+        Deposit::Cw721 { contract, token_id } => {
+            assert_nft_matches_asset(contract, token_id, asset)
+        }
+    }
+}
+
+// Asserts that a deposit matches the expected assets, a basket of several assets can only be
+// deposited as native funds.
+pub fn assert_deposit_matches_assets(
+    deposit: &Deposit,
+    assets: &[Asset],
+) -> Result<(), ContractError> {
+    match (deposit, assets) {
+        (_, [asset]) => assert_deposit_matches_token_amount(deposit, asset),
+        (Deposit::Native(funds), _) => assert_funds_match_assets(funds, assets),
+        _ => Err(ContractError::UnexpectedFunds {
+            expected: assets_to_string(assets),
+            found: assets_to_string(&deposit.to_assets()),
+        }),
+    }
+}
+
+// Asserts that nothing was deposited with a call that only takes funds when needed.
+pub fn assert_no_deposit(deposit: &Deposit) -> Result<(), ContractError> {
+    let assets = deposit.to_assets();
+    if !assets.is_empty() {
+        return Err(ContractError::UnexpectedFunds {
+            expected: "no funds".to_string(),
+            found: assets_to_string(&assets),
+        });
+    }
+    Ok(())
+}
+
+// Asserts that the deposit tops up the held assets to the required one, if they fall short of it.
+pub fn assert_top_up_matches(
+    deposit: &Deposit,
+    held: &[Asset],
+    required: &Asset,
+) -> Result<(), ContractError> {
+    // Nothing is expected when the held assets already cover the required one
+    let covered: Uint128 = held
+        .iter()
+        .map(|asset| asset.covered_amount(required))
+        .sum();
+    let shortfall = required.amount().saturating_sub(covered);
+    if shortfall.is_zero() {
+        return assert_no_deposit(deposit);
+    }
+
+    // Otherwise the deposit must make up exactly the difference
+    assert_deposit_matches_token_amount(deposit, &required.with_amount(shortfall))
+}
+
+// Asserts that the funds match the expected asset.
+pub fn assert_funds_match_token_amount(funds: &[Coin], asset: &Asset) -> Result<(), ContractError> {
+    assert_funds_match_assets(funds, std::slice::from_ref(asset))
+}
+
+// Asserts that the funds match the expected assets exactly, with no coin missing or left over.
+pub fn assert_funds_match_assets(funds: &[Coin], assets: &[Asset]) -> Result<(), ContractError> {
     // Iterate through each coin in the provided funds
     for coin in funds.iter() {
-        // Check if the denomination of the coin matches the token's address
-        if coin.denom != token.address.to_string() {
+        // Check if the coin is one of the expected native tokens
+        if !assets
+            .iter()
+            .any(|asset| matches!(asset, Asset::Native { denom, .. } if *denom == coin.denom))
+        {
             // If not, return an error indicating unexpected funds
             return Err(ContractError::UnexpectedFunds {
-                expected: token.address.to_string(),
+                expected: assets
+                    .iter()
+                    .map(|asset| asset.token())
+                    .collect::<Vec<_>>()
+                    .join(","),
                 found: coin.denom.clone(),
             });
         }
     }
 
-    // Find the specific coin that matches the token's address
-    let sent_funds = funds
-        .iter()
-        .find(|coin| coin.denom == token.address.to_string());
-
-    // Match against the found coin
-    match sent_funds {
-        Some(coin) if coin.amount == token_amount => Ok(()), // If amount matches, return Ok
-        Some(coin) => Err(ContractError::IncorrectFundsAmount {
-            // If amount doesn't match, return error
-            expected: token_amount.to_string(),
-            found: coin.amount.to_string(),
+    // Check that each asset was sent in exactly its amount
+    for asset in assets {
+        // Find the specific coin that matches the asset's denom
+        match funds.iter().find(|coin| coin.denom == asset.token()) {
+            Some(coin) if coin.amount == asset.amount() => {} // If amount matches, continue
+            Some(coin) => {
+                // If amount doesn't match, return error
+                return Err(ContractError::IncorrectFundsAmount {
+                    expected: asset.amount().to_string(),
+                    found: coin.amount.to_string(),
+                });
+            }
+            None => return Err(ContractError::InsufficientFunds), // If no funds are found for the asset, return insufficient funds error
+        }
+    }
+
+    // Return Ok(()) if the funds match
+    Ok(())
+}
+
+// Asserts that a CW20 deposit matches the expected asset.
+pub fn assert_cw20_matches_token_amount(
+    cw20_contract: &Addr,
+    amount: Uint128,
+    asset: &Asset,
+) -> Result<(), ContractError> {
+    // Check if the deposit was made with the asset's CW20 contract
+    if !matches!(asset, Asset::Cw20 { address, .. } if address == cw20_contract) {
+        // If not, return an error indicating unexpected funds
+        return Err(ContractError::UnexpectedFunds {
+            expected: asset.token(),
+            found: cw20_contract.to_string(),
+        });
+    }
+
+    // Check if the deposited amount matches the asset amount
+    if amount != asset.amount() {
+        // If amount doesn't match, return an IncorrectFundsAmount error
+        return Err(ContractError::IncorrectFundsAmount {
+            expected: asset.amount().to_string(),
+            found: amount.to_string(),
+        });
+    }
+
+    // Return Ok(()) if the deposit matches
+    Ok(())
+}
+
+// Asserts that a received NFT is one of the NFTs of the expected asset.
+pub fn assert_nft_matches_asset(
+    collection: &Addr,
+    token_id: &str,
+    asset: &Asset,
+) -> Result<(), ContractError> {
+    // Check if the NFT belongs to the asset's collection and is one of its token ids
+    match asset {
+        Asset::Cw721 { address, token_ids }
+            if address == collection && token_ids.iter().any(|id| id == token_id) =>
+        {
+            Ok(())
+        }
+        _ => Err(ContractError::UnexpectedFunds {
+            expected: asset.to_string(),
+            found: format!("cw721:{}:{}", collection, token_id),
         }),
-        None => Err(ContractError::InsufficientFunds {}), // If no funds are found for the token, return insufficient funds error
     }
 }
 
-// Asserts that the contract has sufficient funds of a specific token.
-pub fn assert_contract_has_sufficient_funds(
-    deps: &DepsMut,
-    env: &Env,
-    token_info: &TokenInfo,
+// Returns how much of an asset the escrow ledger holds for a depositor of the given agreement.
+pub fn escrowed_amount(
+    storage: &dyn Storage,
+    id: u64,
+    depositor: &Addr,
+    asset: &Asset,
+) -> StdResult<Uint128> {
+    // Sum up every ledger entry of the same token as the asset
+    let deposits = DEPOSITS
+        .may_load(storage, (id, depositor))?
+        .unwrap_or_default();
+    Ok(deposits
+        .iter()
+        .map(|deposit| deposit.covered_amount(asset))
+        .sum())
+}
+
+// Asserts that every NFT of an asset has been deposited by the given party.
+pub fn assert_deposit_complete(
+    storage: &dyn Storage,
+    id: u64,
+    depositor: &Addr,
+    asset: &Asset,
 ) -> Result<(), ContractError> {
-    // Clone the contract address from the environment
-    let contract_addr = env.contract.address.clone();
+    // Check if the ledger holds less than the full asset
+    if escrowed_amount(storage, id, depositor, asset)? < asset.amount() {
+        // If so, return an IncompleteDeposit error
+        return Err(ContractError::IncompleteDeposit {
+            asset: asset.to_string(),
+        });
+    }
 
-    // Query the balance of the contract for the specified token
-    let contract_balance = deps
-        .querier
-        .query_balance(&contract_addr, &token_info.address)?;
+    // Return Ok(()) if the asset is fully escrowed
+    Ok(())
+}
 
-    // Convert token amount from u128 to Uint128 for consistency
-    let token_amount = Uint128::from(token_info.amount);
+// Asserts that the escrow ledger of an agreement holds enough of an asset for a depositor.
+pub fn assert_agreement_has_sufficient_funds(
+    storage: &dyn Storage,
+    id: u64,
+    depositor: &Addr,
+    asset: &Asset,
+) -> Result<(), ContractError> {
+    // Query the amount escrowed by the depositor for this agreement only
+    let escrowed = escrowed_amount(storage, id, depositor, asset)?;
 
-    // Check if the contract balance is less than the required token amount
-    if contract_balance.amount < token_amount {
+    // Check if the escrowed amount is less than the required asset amount
+    if escrowed < asset.amount() {
         // If insufficient funds, return an InsufficientContractFunds error indicating expected and found amounts
         return Err(ContractError::InsufficientContractFunds {
-            expected: token_amount.to_string(),
-            found: contract_balance.amount.to_string(),
+            expected: asset.amount().to_string(),
+            found: escrowed.to_string(),
         });
     }
 
-    // Return Ok(()) if contract has sufficient funds
+    // Return Ok(()) if the agreement has sufficient funds
     Ok(())
 }
+
+// Returns the initiator token paid for filling `amount` of the counterparty token after `filled`.
+// Payouts are rounded down on the cumulative filled amount, so the fill completing the agreement
+// receives whatever rounding left over and the fills add up to exactly the initiator token.
+pub fn fill_payout(
+    initiator_amount: Uint128,
+    counterparty_amount: Uint128,
+    filled: Uint128,
+    amount: Uint128,
+) -> Uint128 {
+    let paid = filled.multiply_ratio(initiator_amount, counterparty_amount);
+    let owed = (filled + amount).multiply_ratio(initiator_amount, counterparty_amount);
+    owed - paid
+}